@@ -0,0 +1,60 @@
+//! Example: drive many coroutines at once from a single thread
+//! (poll-based).
+//!
+//! Run with:
+//!
+//! ```sh
+//! cargo run --example poll_spawn_many --features poll
+//! ```
+
+use std::collections::HashMap;
+
+use io_process::{
+    command::Command,
+    coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
+    io::ProcessOutput,
+    runtimes::poll::{Poller, RequestId},
+};
+
+fn main() {
+    env_logger::init();
+
+    let mut poller = Poller::new();
+    let mut spawns: HashMap<RequestId, (usize, ProcessSpawnOut)> = HashMap::new();
+
+    for i in 0..10 {
+        let mut command = Command::new("sh");
+        command.arg("-c");
+        command.arg(format!("sleep 0.{}; echo {i}", 9 - i));
+
+        let spawn = ProcessSpawnOut::new(command);
+        resume(&mut poller, &mut spawns, i, spawn, None);
+    }
+
+    while let Some((id, output)) = poller.wait(None) {
+        let (i, spawn) = spawns.remove(&id).unwrap();
+        resume(&mut poller, &mut spawns, i, spawn, Some(output.unwrap()));
+    }
+}
+
+/// Resumes the given coroutine, submitting its next request to the
+/// poller or printing its output.
+fn resume(
+    poller: &mut Poller,
+    spawns: &mut HashMap<RequestId, (usize, ProcessSpawnOut)>,
+    i: usize,
+    mut spawn: ProcessSpawnOut,
+    arg: Option<ProcessOutput>,
+) {
+    match spawn.resume(arg) {
+        ProcessSpawnOutResult::Ok { status, stdout, .. } => {
+            let stdout = String::from_utf8_lossy(&stdout.unwrap_or_default()).into_owned();
+            println!("spawn {i} completed with {status:?}: {}", stdout.trim());
+        }
+        ProcessSpawnOutResult::Io { input } => {
+            let id = poller.submit(input);
+            spawns.insert(id, (i, spawn));
+        }
+        ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+    }
+}
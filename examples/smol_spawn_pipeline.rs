@@ -0,0 +1,55 @@
+//! Example: pipe the output of one process into another (async, smol).
+//!
+//! Run with:
+//!
+//! ```sh
+//! cargo run --example smol_spawn_pipeline --features smol
+//! ```
+
+use io_process::{
+    command::Command,
+    coroutines::spawn_pipeline::{SpawnPipeline, SpawnPipelineResult},
+    runtimes::smol::handle,
+};
+
+fn main() {
+    env_logger::init();
+    smol::block_on(run());
+}
+
+async fn run() {
+    let mut echo = Command::new("echo");
+    echo.arg("hello world");
+
+    let mut grep = Command::new("grep");
+    grep.arg("world");
+
+    println!("pipeline: {echo:#?} | {grep:#?}");
+    println!();
+
+    let mut arg = None;
+    let mut spawn = SpawnPipeline::new([echo, grep]);
+
+    let (status, stdout, stderr) = loop {
+        match spawn.resume(arg.take()) {
+            SpawnPipelineResult::Ok {
+                status,
+                stdout,
+                stderr,
+                ..
+            } => break (status, stdout, stderr),
+            SpawnPipelineResult::Io { input } => arg = Some(handle(input).await.unwrap()),
+            SpawnPipelineResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    println!("status: {status:#?}");
+    println!(
+        "stdout: {}",
+        String::from_utf8_lossy(&stdout.unwrap_or_default())
+    );
+    println!(
+        "stderr: {}",
+        String::from_utf8_lossy(&stderr.unwrap_or_default())
+    );
+}
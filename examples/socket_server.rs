@@ -0,0 +1,27 @@
+//! Example: serve process requests over a Unix domain socket.
+//!
+//! Run with:
+//!
+//! ```sh
+//! cargo run --example socket_server --features socket -- /tmp/io-process.sock
+//! ```
+
+use std::{env, fs, os::unix::net::UnixListener};
+
+use io_process::runtimes::socket::serve;
+
+fn main() {
+    env_logger::init();
+
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("/tmp/io-process.sock"));
+
+    // removes the socket left by a previous run, if any
+    let _ = fs::remove_file(&path);
+
+    let listener = UnixListener::bind(&path).unwrap();
+    println!("listening on {path}");
+
+    serve(&listener).unwrap();
+}
@@ -8,7 +8,7 @@
 
 use io_process::{
     command::Command,
-    coroutines::spawn_out::{SpawnOut, SpawnOutResult},
+    coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
     runtimes::std::handle,
 };
 
@@ -23,21 +23,28 @@ fn main() {
     println!();
 
     let mut arg = None;
-    let mut spawn = SpawnOut::new(command);
+    let mut spawn = ProcessSpawnOut::new(command);
 
     let (status, stdout, stderr) = loop {
         match spawn.resume(arg.take()) {
-            SpawnOutResult::Ok {
+            ProcessSpawnOutResult::Ok {
                 status,
                 stdout,
                 stderr,
+                ..
             } => break (status, stdout, stderr),
-            SpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
-            SpawnOutResult::Err { err } => panic!("{err}"),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
         }
     };
 
     println!("status: {status:#?}");
-    println!("stdout: {}", String::from_utf8_lossy(&stdout));
-    println!("stderr: {}", String::from_utf8_lossy(&stderr));
+    println!(
+        "stdout: {}",
+        String::from_utf8_lossy(&stdout.unwrap_or_default())
+    );
+    println!(
+        "stderr: {}",
+        String::from_utf8_lossy(&stderr.unwrap_or_default())
+    );
 }
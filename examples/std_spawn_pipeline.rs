@@ -33,6 +33,7 @@ fn main() {
                 status,
                 stdout,
                 stderr,
+                ..
             } => break (status, stdout, stderr),
             SpawnPipelineResult::Io { input } => arg = Some(handle(input).unwrap()),
             SpawnPipelineResult::Err { err } => panic!("{err}"),
@@ -40,6 +41,12 @@ fn main() {
     };
 
     println!("status: {status:#?}");
-    println!("stdout: {}", String::from_utf8_lossy(&stdout));
-    println!("stderr: {}", String::from_utf8_lossy(&stderr));
+    println!(
+        "stdout: {}",
+        String::from_utf8_lossy(&stdout.unwrap_or_default())
+    );
+    println!(
+        "stderr: {}",
+        String::from_utf8_lossy(&stderr.unwrap_or_default())
+    );
 }
@@ -34,6 +34,7 @@ async fn main() {
                 status,
                 stdout,
                 stderr,
+                ..
             } => break (status, stdout, stderr),
             SpawnPipelineResult::Io { input } => arg = Some(handle(input).await.unwrap()),
             SpawnPipelineResult::Err { err } => panic!("{err}"),
@@ -41,6 +42,12 @@ async fn main() {
     };
 
     println!("status: {status:#?}");
-    println!("stdout: {}", String::from_utf8_lossy(&stdout));
-    println!("stderr: {}", String::from_utf8_lossy(&stderr));
+    println!(
+        "stdout: {}",
+        String::from_utf8_lossy(&stdout.unwrap_or_default())
+    );
+    println!(
+        "stderr: {}",
+        String::from_utf8_lossy(&stderr.unwrap_or_default())
+    );
 }
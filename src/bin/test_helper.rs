@@ -0,0 +1,149 @@
+//! Helper program spawned by the crate's own tests, so that they do
+//! not depend on the system utilities.
+//!
+//! The helper is only built with the `test-helper` feature, so that
+//! it does not get installed along with the crate. The crate enables
+//! the feature through its own dev-dependency, so the helper is
+//! always built for the integration tests.
+//!
+//! The arguments form a script of actions, run in order:
+//!
+//! - `stdout TEXT`, `stderr TEXT`: write the text to stdout or stderr
+//! - `fill-stdout N`, `fill-stderr N`: write N `x` bytes to stdout or
+//!   stderr
+//! - `flood`: write to stdout until it gets closed
+//! - `cat`: copy stdin to stdout
+//! - `read-fd FD`: copy the file descriptor FD to stdout (Unix)
+//! - `write-fd FD TEXT`: write the text to the file descriptor FD
+//!   (Unix)
+//! - `env`: print the environment, one sorted `KEY=VALUE` per line
+//! - `cwd`: print the working directory, followed by a newline
+//! - `pid PATH`: write the process ID, followed by a newline, to the
+//!   file at PATH
+//! - `sleep MS`: sleep for MS milliseconds
+//! - `signal SIG`: kill itself with the signal number SIG (Unix)
+//! - `ignore SIG`: ignore the signal number SIG from now on (Unix)
+//! - `exit N`: exit with code N
+//!
+//! The helper exits with `0` once the script is over.
+
+use std::{
+    env, fs,
+    io::{self, ErrorKind, Read, Write},
+    process,
+    thread::sleep,
+    time::Duration,
+};
+
+#[cfg(unix)]
+unsafe extern "C" {
+    fn raise(sig: i32) -> i32;
+    fn signal(sig: i32, handler: usize) -> usize;
+}
+
+/// Disposition ignoring a signal.
+#[cfg(unix)]
+const SIG_IGN: usize = 1;
+
+fn main() {
+    let mut args = env::args().skip(1);
+
+    while let Some(action) = args.next() {
+        let mut arg = || {
+            args.next()
+                .unwrap_or_else(|| fail(&format!("missing argument of action `{action}`")))
+        };
+
+        let result = match action.as_str() {
+            "stdout" => write(&mut io::stdout(), arg().as_bytes()),
+            "stderr" => write(&mut io::stderr(), arg().as_bytes()),
+            "fill-stdout" => write(&mut io::stdout(), &vec![b'x'; number(arg())]),
+            "fill-stderr" => write(&mut io::stderr(), &vec![b'x'; number(arg())]),
+            "flood" => flood(),
+            "cat" => copy(&mut io::stdin(), &mut io::stdout()),
+            #[cfg(unix)]
+            "read-fd" => copy(&mut fd(number(arg())), &mut io::stdout()),
+            #[cfg(unix)]
+            "write-fd" => {
+                let mut file = fd(number(arg()));
+                write(&mut file, arg().as_bytes())
+            }
+            "env" => {
+                let mut vars: Vec<_> = env::vars_os()
+                    .map(|(key, val)| format!("{}={}\n", key.display(), val.display()))
+                    .collect();
+                vars.sort();
+                write(&mut io::stdout(), vars.concat().as_bytes())
+            }
+            "cwd" => {
+                let cwd = env::current_dir().unwrap_or_else(|err| fail(&err.to_string()));
+                write(&mut io::stdout(), format!("{}\n", cwd.display()).as_bytes())
+            }
+            "pid" => fs::write(arg(), format!("{}\n", process::id())),
+            "sleep" => {
+                sleep(Duration::from_millis(number(arg())));
+                Ok(())
+            }
+            #[cfg(unix)]
+            "signal" => {
+                // SAFETY: raise has no precondition
+                unsafe { raise(number(arg())) };
+                Ok(())
+            }
+            #[cfg(unix)]
+            "ignore" => {
+                // SAFETY: SIG_IGN is a valid disposition
+                unsafe { signal(number(arg()), SIG_IGN) };
+                Ok(())
+            }
+            "exit" => process::exit(number(arg())),
+            action => fail(&format!("unknown action `{action}`")),
+        };
+
+        if let Err(err) = result {
+            fail(&format!("action `{action}` failed: {err}"));
+        }
+    }
+}
+
+fn write(stream: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    stream.write_all(bytes)?;
+    stream.flush()
+}
+
+fn copy(from: &mut impl Read, to: &mut impl Write) -> io::Result<()> {
+    io::copy(from, to)?;
+    to.flush()
+}
+
+fn flood() -> io::Result<()> {
+    let chunk = [b'y'; 4096];
+    let mut stdout = io::stdout();
+
+    loop {
+        match stdout.write_all(&chunk) {
+            Ok(()) => continue,
+            Err(err) if err.kind() == ErrorKind::BrokenPipe => return Ok(()),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn fd(fd: i32) -> fs::File {
+    use std::os::fd::FromRawFd;
+
+    // SAFETY: the file descriptor is passed by the test, which owns
+    // it for the lifetime of the process
+    unsafe { fs::File::from_raw_fd(fd) }
+}
+
+fn number<T: std::str::FromStr>(arg: String) -> T {
+    arg.parse()
+        .unwrap_or_else(|_| fail(&format!("invalid number `{arg}`")))
+}
+
+fn fail(reason: &str) -> ! {
+    eprintln!("io-process-test-helper: {reason}");
+    process::exit(101)
+}
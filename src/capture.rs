@@ -0,0 +1,329 @@
+//! Process output capture configuration.
+
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Action taken by runtimes when a captured stream exceeds its size
+/// limit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum LimitAction {
+    /// Keep draining the stream, but stop storing bytes past the
+    /// limit.
+    #[default]
+    Truncate,
+    /// Stop storing bytes past the limit, and kill the process.
+    Kill,
+}
+
+/// Capture configuration of a child process's output streams.
+///
+/// Only applies to streams captured by the runtime, for example
+/// stdout and stderr of [`ProcessInput::SpawnOut`].
+///
+/// [`ProcessInput::SpawnOut`]: crate::io::ProcessInput::SpawnOut
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Capture {
+    /// Maximum number of stdout bytes to store.
+    pub max_stdout: Option<usize>,
+
+    /// Maximum number of stderr bytes to store.
+    pub max_stderr: Option<usize>,
+
+    /// Action taken when a stream exceeds its limit.
+    pub on_limit: LimitAction,
+
+    /// Whether to record a [`TranscriptEvent`] for each chunk read
+    /// from the captured streams.
+    pub transcript: bool,
+
+    /// Whether to forward captured stdout to the parent's stdout as
+    /// it arrives.
+    pub tee_stdout: bool,
+
+    /// Whether to forward captured stderr to the parent's stderr as
+    /// it arrives.
+    pub tee_stderr: bool,
+}
+
+/// Truncation report of captured output streams.
+///
+/// A `true` flag means that the stream exceeded its size limit and
+/// that the stored bytes are incomplete.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Truncated {
+    /// Whether stdout has been truncated.
+    pub stdout: bool,
+    /// Whether stderr has been truncated.
+    pub stderr: bool,
+}
+
+impl Truncated {
+    /// Returns `true` if any stream has been truncated.
+    pub fn any(&self) -> bool {
+        self.stdout || self.stderr
+    }
+}
+
+/// Output stream of a child process.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stream {
+    /// The standard output stream.
+    Stdout,
+    /// The standard error stream.
+    Stderr,
+}
+
+/// Chunk of output read from a captured stream, recorded when the
+/// [`Capture::transcript`] mode is enabled.
+///
+/// The list of events of a process tells which stream each chunk
+/// came from and when, in the order chunks have been read.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TranscriptEvent {
+    /// The stream the chunk was read from.
+    pub stream: Stream,
+    /// The time elapsed between the spawn of the process and the
+    /// read of the chunk.
+    pub elapsed: Duration,
+    /// The bytes of the chunk.
+    ///
+    /// Only contains the bytes stored within the stream limit.
+    pub bytes: Vec<u8>,
+}
+
+/// Accumulator of a captured stream enforcing its size limit.
+///
+/// Used by runtimes to store the chunks read from a captured stream.
+#[derive(Debug, Default)]
+#[cfg_attr(
+    not(any(feature = "std", feature = "tokio", feature = "smol", feature = "sim")),
+    allow(dead_code)
+)]
+pub(crate) struct Collector {
+    limit: Option<usize>,
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+#[cfg_attr(
+    not(any(feature = "std", feature = "tokio", feature = "smol", feature = "sim")),
+    allow(dead_code)
+)]
+impl Collector {
+    /// Creates a new collector storing at most `limit` bytes.
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            bytes: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    /// Stores the given chunk, up to the limit.
+    ///
+    /// Returns `false` if the chunk made the stream exceed its limit
+    /// for the first time.
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> bool {
+        let Some(limit) = self.limit else {
+            self.bytes.extend_from_slice(chunk);
+            return true;
+        };
+
+        let room = limit.saturating_sub(self.bytes.len());
+
+        if chunk.len() <= room {
+            self.bytes.extend_from_slice(chunk);
+            return true;
+        }
+
+        self.bytes.extend_from_slice(&chunk[..room]);
+
+        let exceeded = !self.truncated;
+        self.truncated = true;
+        !exceeded
+    }
+
+    /// Returns the stored bytes.
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns `true` if the stream exceeded its limit.
+    pub(crate) fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Consumes the collector, returning the stored bytes.
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+#[cfg_attr(
+    not(any(feature = "std", feature = "tokio", feature = "smol", feature = "sim")),
+    allow(dead_code)
+)]
+impl Capture {
+    /// Creates a [`Collector`] enforcing the limit of the given
+    /// stream.
+    pub(crate) fn collector(&self, stream: Stream) -> Collector {
+        match stream {
+            Stream::Stdout => Collector::new(self.max_stdout),
+            Stream::Stderr => Collector::new(self.max_stderr),
+        }
+    }
+
+    /// Returns `true` if the given stream needs to be forwarded to
+    /// the matching stream of the parent process.
+    pub(crate) fn tees(&self, stream: Stream) -> bool {
+        match stream {
+            Stream::Stdout => self.tee_stdout,
+            Stream::Stderr => self.tee_stderr,
+        }
+    }
+}
+
+/// Recorder of the captured streams of a process.
+///
+/// Used by runtimes to store the chunks read from the captured
+/// streams, enforcing the limits and recording the transcript of a
+/// [`Capture`] configuration.
+#[derive(Debug)]
+#[cfg_attr(
+    not(any(feature = "std", feature = "tokio", feature = "smol", feature = "sim")),
+    allow(dead_code)
+)]
+pub(crate) struct Recorder {
+    stdout: Collector,
+    stderr: Collector,
+    on_limit: LimitAction,
+    transcript: Option<Vec<TranscriptEvent>>,
+}
+
+#[cfg_attr(
+    not(any(feature = "std", feature = "tokio", feature = "smol", feature = "sim")),
+    allow(dead_code)
+)]
+impl Recorder {
+    /// Creates a new recorder for the given capture configuration.
+    pub(crate) fn new(capture: &Capture) -> Self {
+        Self {
+            stdout: capture.collector(Stream::Stdout),
+            stderr: capture.collector(Stream::Stderr),
+            on_limit: capture.on_limit,
+            transcript: capture.transcript.then(Vec::new),
+        }
+    }
+
+    /// Records a chunk read from the given stream, `elapsed` time
+    /// after the process got spawned.
+    ///
+    /// Returns `true` if the process needs to be killed, because the
+    /// chunk made the stream exceed its limit and the limit action is
+    /// [`LimitAction::Kill`].
+    pub(crate) fn record(&mut self, stream: Stream, elapsed: Duration, chunk: &[u8]) -> bool {
+        let collector = match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        };
+
+        let offset = collector.bytes().len();
+        let within_limit = collector.push(chunk);
+
+        if let Some(transcript) = &mut self.transcript {
+            let bytes = &collector.bytes()[offset..];
+
+            if !bytes.is_empty() {
+                transcript.push(TranscriptEvent {
+                    stream,
+                    elapsed,
+                    bytes: bytes.to_vec(),
+                });
+            }
+        }
+
+        !within_limit && self.on_limit == LimitAction::Kill
+    }
+
+    /// Consumes the recorder, returning what has been recorded.
+    ///
+    /// The `stdout` and `stderr` flags tell whether the matching
+    /// streams were captured at all: streams that were not are
+    /// returned as `None`.
+    pub(crate) fn finish(self, stdout: bool, stderr: bool) -> Recording {
+        Recording {
+            truncated: Truncated {
+                stdout: self.stdout.is_truncated(),
+                stderr: self.stderr.is_truncated(),
+            },
+            stdout: stdout.then(|| self.stdout.into_bytes()),
+            stderr: stderr.then(|| self.stderr.into_bytes()),
+            transcript: self.transcript,
+        }
+    }
+}
+
+/// Output recorded by a [`Recorder`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Recording {
+    /// The stored stdout bytes, or `None` if not captured.
+    pub(crate) stdout: Option<Vec<u8>>,
+    /// The stored stderr bytes, or `None` if not captured.
+    pub(crate) stderr: Option<Vec<u8>>,
+    /// Which streams exceeded their limit.
+    pub(crate) truncated: Truncated,
+    /// The transcript, or `None` if the transcript mode was not
+    /// enabled.
+    pub(crate) transcript: Option<Vec<TranscriptEvent>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{Capture, Collector, LimitAction, Recorder, Stream};
+
+    #[test]
+    fn collector() {
+        let mut collector = Collector::new(Some(4));
+        assert!(collector.push(b"ab"));
+        assert!(collector.push(b"cd"));
+        assert!(!collector.is_truncated());
+        assert!(!collector.push(b"ef"));
+        assert!(collector.push(b"gh"));
+        assert!(collector.is_truncated());
+        assert_eq!(b"abcd", collector.bytes());
+
+        let mut collector = Collector::new(None);
+        assert!(collector.push(b"abcdef"));
+        assert!(!collector.is_truncated());
+    }
+
+    #[test]
+    fn recorder() {
+        let capture = Capture {
+            max_stdout: Some(3),
+            on_limit: LimitAction::Kill,
+            transcript: true,
+            ..Default::default()
+        };
+
+        let mut recorder = Recorder::new(&capture);
+        let ms = Duration::from_millis;
+        assert!(!recorder.record(Stream::Stdout, ms(1), b"ab"));
+        assert!(!recorder.record(Stream::Stderr, ms(2), b"err"));
+        assert!(recorder.record(Stream::Stdout, ms(3), b"cd"));
+        assert!(!recorder.record(Stream::Stdout, ms(4), b"ef"));
+
+        let recording = recorder.finish(true, false);
+        assert_eq!(Some(b"abc".to_vec()), recording.stdout);
+        assert_eq!(None, recording.stderr);
+        assert!(recording.truncated.stdout);
+
+        let transcript = recording.transcript.unwrap();
+        assert_eq!(3, transcript.len());
+        assert_eq!(Stream::Stderr, transcript[1].stream);
+        assert_eq!(ms(2), transcript[1].elapsed);
+        assert_eq!(b"c", transcript[2].bytes.as_slice());
+    }
+}
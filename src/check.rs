@@ -0,0 +1,286 @@
+//! Process exit status checking.
+
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use thiserror::Error;
+
+use crate::status::ExitStatus;
+
+/// Default maximum length, in bytes, of the stderr excerpt carried
+/// by an [`ExitError`].
+pub const DEFAULT_MAX_STDERR_LEN: usize = 1024;
+
+/// Error emitted by coroutines in checked mode when a process exits
+/// with a status that does not count as a success.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+#[error("Command `{command}` failed with exit status {status:?}{}", display_stderr(.stderr))]
+pub struct ExitError {
+    /// The exit status of the process.
+    pub status: ExitStatus,
+    /// The rendered command line of the process.
+    pub command: String,
+    /// A bounded, UTF-8 sanitized excerpt of the end of stderr.
+    ///
+    /// Empty when stderr was not captured.
+    pub stderr: String,
+    /// Whether the stderr excerpt has been truncated.
+    pub stderr_truncated: bool,
+}
+
+fn display_stderr(stderr: &str) -> String {
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        alloc::format!(": {stderr}")
+    }
+}
+
+/// Exit status check performed by coroutines in checked mode.
+///
+/// By default, only the exit code `0` counts as a success and the
+/// stderr excerpt is bounded to [`DEFAULT_MAX_STDERR_LEN`] bytes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExitCheck {
+    /// Exit codes counting as a success.
+    success_codes: Vec<i32>,
+
+    /// Maximum length, in bytes, of the stderr excerpt.
+    max_stderr_len: usize,
+}
+
+impl Default for ExitCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExitCheck {
+    /// Creates a new check where only the exit code `0` counts as a
+    /// success.
+    pub fn new() -> Self {
+        Self {
+            success_codes: vec![0],
+            max_stderr_len: DEFAULT_MAX_STDERR_LEN,
+        }
+    }
+
+    /// Replaces the exit codes counting as a success.
+    ///
+    /// For example, `grep` exits with `1` when no line matched.
+    pub fn success_codes(&mut self, codes: impl IntoIterator<Item = i32>) -> &mut Self {
+        self.success_codes = codes.into_iter().collect();
+        self
+    }
+
+    /// Sets the maximum length, in bytes, of the stderr excerpt.
+    pub fn max_stderr_len(&mut self, len: usize) -> &mut Self {
+        self.max_stderr_len = len;
+        self
+    }
+
+    /// Returns `true` if the given status counts as a success.
+    ///
+    /// A process terminated by a signal never counts as a success.
+    pub fn is_success(&self, status: &ExitStatus) -> bool {
+        match status.code() {
+            Some(code) => self.success_codes.contains(&code),
+            None => false,
+        }
+    }
+
+    /// Checks the given status, returning an [`ExitError`] if it
+    /// does not count as a success.
+    pub fn check(
+        &self,
+        command: impl ToString,
+        status: &ExitStatus,
+        stderr: &[u8],
+    ) -> Result<(), ExitError> {
+        if self.is_success(status) {
+            return Ok(());
+        }
+
+        let (stderr, stderr_truncated) = excerpt(stderr, self.max_stderr_len);
+
+        Err(ExitError {
+            status: status.clone(),
+            command: command.to_string(),
+            stderr,
+            stderr_truncated,
+        })
+    }
+}
+
+/// Builds a UTF-8 sanitized excerpt of at most `max` bytes from the
+/// end of `bytes`.
+///
+/// The end of stderr is kept since it usually contains the actual
+/// error. Invalid UTF-8 sequences are replaced, escape sequences are
+/// stripped, and control characters other than newlines and tabs are
+/// dropped. The limit applies to the sanitized excerpt.
+fn excerpt(bytes: &[u8], max: usize) -> (String, bool) {
+    let sanitized = sanitize(&String::from_utf8_lossy(bytes));
+    let truncated = sanitized.len() > max;
+    let mut start = sanitized.len().saturating_sub(max);
+
+    // a cut in the middle of a multi-byte character is moved to the
+    // start of the next one
+    while !sanitized.is_char_boundary(start) {
+        start += 1;
+    }
+
+    (sanitized[start..].trim().to_string(), truncated)
+}
+
+/// Strips the escape sequences and the control characters other
+/// than newlines and tabs from the given text.
+///
+/// Control sequences (`ESC [ … final`), operating system commands
+/// (`ESC ] … BEL` or `ESC ] … ESC \`) and sequences with
+/// intermediate bytes (`ESC ( B`) are removed whole, other escape
+/// sequences along with the character following `ESC`.
+fn sanitize(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            if !c.is_control() || c == '\n' || c == '\t' {
+                sanitized.push(c);
+            }
+            continue;
+        }
+
+        match chars.next() {
+            // parameter and intermediate bytes, up to the final byte
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // string terminated by BEL or ST
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' {
+                        break;
+                    }
+
+                    if c == '\x1b' && chars.peek() == Some(&'\\') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            // intermediate bytes, up to the final byte
+            Some(' '..='/') => {
+                for c in chars.by_ref() {
+                    if ('0'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use crate::status::ExitStatus;
+
+    use super::ExitCheck;
+
+    #[test]
+    fn success_codes() {
+        let mut check = ExitCheck::new();
+        assert!(check.is_success(&ExitStatus::new(Some(0))));
+        assert!(!check.is_success(&ExitStatus::new(Some(1))));
+        assert!(!check.is_success(&ExitStatus::new(None)));
+
+        check.success_codes([0, 1]);
+        assert!(check.is_success(&ExitStatus::new(Some(1))));
+        assert!(!check.is_success(&ExitStatus::new(Some(2))));
+    }
+
+    #[test]
+    fn error() {
+        let check = ExitCheck::new();
+        let status = ExitStatus::new(Some(2));
+        let err = check
+            .check("grep foo", &status, b"grep: bar\n")
+            .unwrap_err();
+
+        assert_eq!("grep: bar", err.stderr);
+        assert!(!err.stderr_truncated);
+        assert_eq!(
+            "Command `grep foo` failed with exit status 2: grep: bar",
+            err.to_string(),
+        );
+
+        let err = check.check("true", &status, b"").unwrap_err();
+        assert_eq!("Command `true` failed with exit status 2", err.to_string());
+    }
+
+    #[test]
+    fn excerpt() {
+        let mut check = ExitCheck::new();
+        check.max_stderr_len(5);
+
+        let status = ExitStatus::new(Some(1));
+        let err = check
+            .check("cmd", &status, "début\x1b[0m\né\u{7}!".as_bytes())
+            .unwrap_err();
+
+        assert_eq!("t\né!", err.stderr);
+        assert!(err.stderr_truncated);
+
+        // replacement characters of the output itself are kept
+        let stderr = b"abc\xff\xffdef";
+        check.max_stderr_len(9);
+        let err = check.check("cmd", &status, stderr).unwrap_err();
+        assert_eq!("\u{FFFD}\u{FFFD}def", err.stderr);
+        assert!(err.stderr_truncated);
+
+        check.max_stderr_len(6);
+        let stderr = "x\u{FFFD}é!".as_bytes();
+        let err = check.check("cmd", &status, stderr).unwrap_err();
+        assert_eq!("\u{FFFD}é!", err.stderr);
+        assert!(err.stderr_truncated);
+    }
+
+    #[test]
+    fn excerpt_invalid_utf8() {
+        let mut check = ExitCheck::new();
+        check.max_stderr_len(8);
+
+        // each invalid byte is replaced by a 3-byte character, the
+        // limit applies to the replaced excerpt
+        let status = ExitStatus::new(Some(1));
+        let err = check.check("cmd", &status, &[0xff; 64]).unwrap_err();
+
+        assert_eq!("\u{FFFD}\u{FFFD}", err.stderr);
+        assert!(err.stderr_truncated);
+    }
+
+    #[test]
+    fn excerpt_escape_sequences() {
+        let check = ExitCheck::new();
+
+        let status = ExitStatus::new(Some(1));
+        let stderr = b"\x1b]0;title\x07\x1b[1;31merror\x1b[0m: \x1b]8;;url\x1b\\bad\x1b(B";
+        let err = check.check("cmd", &status, stderr).unwrap_err();
+
+        assert_eq!("error: bad", err.stderr);
+        assert!(!err.stderr_truncated);
+    }
+}
@@ -1,5 +1,7 @@
 //! I/O-free command builder.
 
+use core::{fmt, time::Duration};
+
 use alloc::{
     borrow::Cow,
     collections::BTreeMap,
@@ -7,7 +9,14 @@ use alloc::{
     vec::Vec,
 };
 
-use crate::stdio::Stdio;
+use crate::{
+    capture::{Capture, LimitAction},
+    fd::ExtraFd,
+    limit::{Resource, Rlimit},
+    pty::Pty,
+    stdio::Stdio,
+    termination::{OnDrop, Termination},
+};
 
 /// I/O-free command builder.
 ///
@@ -20,10 +29,10 @@ use crate::stdio::Stdio;
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Command {
     /// Path to the program.
-    program: String,
+    pub(crate) program: String,
 
     /// Arguments passed to the program.
-    args: Option<Vec<String>>,
+    pub(crate) args: Option<Vec<String>>,
 
     /// Environment variables explicitly set for the child process.
     pub envs: Option<BTreeMap<String, String>>,
@@ -40,6 +49,62 @@ pub struct Command {
     /// Configuration for the child process's stderr handle.
     pub stderr: Option<Stdio>,
 
+    /// Configuration for the child process's extra file descriptors,
+    /// by descriptor number.
+    pub fds: BTreeMap<i32, ExtraFd>,
+
+    /// Pseudo-terminal configuration of the child process, if it
+    /// runs in PTY mode.
+    pub pty: Option<Pty>,
+
+    /// Configuration of the output captured by runtimes.
+    pub capture: Capture,
+
+    /// Maximum duration the child process is allowed to run.
+    ///
+    /// Past this duration, runtimes terminate the process according
+    /// to [`Command::termination`], and fail with
+    /// [`std::io::ErrorKind::TimedOut`].
+    pub timeout: Option<Duration>,
+
+    /// Configuration of the termination of the child process.
+    pub termination: Termination,
+
+    /// User ID of the child process.
+    #[cfg(unix)]
+    pub uid: Option<u32>,
+
+    /// Group ID of the child process.
+    #[cfg(unix)]
+    pub gid: Option<u32>,
+
+    /// Supplementary group IDs of the child process.
+    #[cfg(unix)]
+    pub groups: Option<Vec<u32>>,
+
+    /// Process group of the child process.
+    ///
+    /// `0` puts the child in a new process group whose ID is its own
+    /// process ID.
+    #[cfg(unix)]
+    pub process_group: Option<i32>,
+
+    /// Whether to run the child process in a new session.
+    #[cfg(unix)]
+    pub setsid: bool,
+
+    /// File mode creation mask of the child process.
+    #[cfg(unix)]
+    pub umask: Option<u32>,
+
+    /// Resource limits of the child process.
+    #[cfg(unix)]
+    pub rlimits: BTreeMap<Resource, Rlimit>,
+
+    /// Nice value of the child process.
+    #[cfg(unix)]
+    pub nice: Option<i32>,
+
     /// Whether to shell-expand program and arguments.
     ///
     /// When `true`, tilde `~` and environment variables `$ENV` are
@@ -64,6 +129,27 @@ impl Command {
             stdin: None,
             stdout: None,
             stderr: None,
+            fds: BTreeMap::new(),
+            pty: None,
+            capture: Capture::default(),
+            timeout: None,
+            termination: Termination::default(),
+            #[cfg(unix)]
+            uid: None,
+            #[cfg(unix)]
+            gid: None,
+            #[cfg(unix)]
+            groups: None,
+            #[cfg(unix)]
+            process_group: None,
+            #[cfg(unix)]
+            setsid: false,
+            #[cfg(unix)]
+            umask: None,
+            #[cfg(unix)]
+            rlimits: BTreeMap::new(),
+            #[cfg(unix)]
+            nice: None,
             #[cfg(feature = "expand")]
             expand: false,
         }
@@ -208,4 +294,234 @@ impl Command {
         self.stderr = Some(cfg.into());
         self
     }
+
+    /// Configures the child process's extra file descriptor `fd`.
+    ///
+    /// Descriptors 0, 1 and 2 are configured with
+    /// [`Command::stdin`], [`Command::stdout`] and
+    /// [`Command::stderr`], and are rejected by runtimes.
+    pub fn fd(&mut self, fd: i32, cfg: ExtraFd) -> &mut Self {
+        self.fds.insert(fd, cfg);
+        self
+    }
+
+    /// Runs the child process in PTY mode, attaching its standard
+    /// streams that are not explicitly configured to a new
+    /// pseudo-terminal.
+    ///
+    /// See [`Pty`] for more details.
+    pub fn pty(&mut self, pty: Pty) -> &mut Self {
+        self.pty = Some(pty);
+        self
+    }
+
+    /// Sets the user ID of the child process.
+    #[cfg(unix)]
+    pub fn uid(&mut self, uid: u32) -> &mut Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Sets the group ID of the child process.
+    #[cfg(unix)]
+    pub fn gid(&mut self, gid: u32) -> &mut Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Sets the supplementary group IDs of the child process.
+    ///
+    /// Setting a user ID without supplementary groups drops the
+    /// supplementary groups of the parent.
+    #[cfg(unix)]
+    pub fn groups(&mut self, groups: impl IntoIterator<Item = u32>) -> &mut Self {
+        self.groups = Some(groups.into_iter().collect());
+        self
+    }
+
+    /// Puts the child process in the process group `pgroup`, or in a
+    /// new process group if `pgroup` is `0`.
+    ///
+    /// A new process group allows signals to be sent to the whole
+    /// process tree of the child.
+    #[cfg(unix)]
+    pub fn process_group(&mut self, pgroup: i32) -> &mut Self {
+        self.process_group = Some(pgroup);
+        self
+    }
+
+    /// Runs the child process in a new session, detached from the
+    /// controlling terminal of the parent.
+    ///
+    /// Cannot be combined with [`Command::process_group`], since the
+    /// new session comes with its own process group.
+    #[cfg(unix)]
+    pub fn setsid(&mut self, enable: bool) -> &mut Self {
+        self.setsid = enable;
+        self
+    }
+
+    /// Sets the file mode creation mask of the child process, for
+    /// example `0o077` to make the files it creates private.
+    #[cfg(unix)]
+    pub fn umask(&mut self, umask: u32) -> &mut Self {
+        self.umask = Some(umask);
+        self
+    }
+
+    /// Limits the given resource of the child process.
+    ///
+    /// A child process failing to apply a limit makes runtimes fail
+    /// with a [`LimitError`].
+    ///
+    /// [`LimitError`]: crate::limit::LimitError
+    #[cfg(unix)]
+    pub fn rlimit(&mut self, resource: Resource, limit: Rlimit) -> &mut Self {
+        self.rlimits.insert(resource, limit);
+        self
+    }
+
+    /// Sets the nice value of the child process, from `-20` (highest
+    /// priority) to `19` (lowest priority).
+    ///
+    /// Lowering the nice value below the one of the parent usually
+    /// requires privileges.
+    #[cfg(unix)]
+    pub fn nice(&mut self, nice: i32) -> &mut Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Limits the number of stdout bytes stored by runtimes capturing
+    /// it.
+    pub fn max_stdout(&mut self, max: usize) -> &mut Self {
+        self.capture.max_stdout = Some(max);
+        self
+    }
+
+    /// Limits the number of stderr bytes stored by runtimes capturing
+    /// it.
+    pub fn max_stderr(&mut self, max: usize) -> &mut Self {
+        self.capture.max_stderr = Some(max);
+        self
+    }
+
+    /// Configures the action taken by runtimes when a captured stream
+    /// exceeds its limit.
+    pub fn on_output_limit(&mut self, action: LimitAction) -> &mut Self {
+        self.capture.on_limit = action;
+        self
+    }
+
+    /// Enables or disables the recording of a timestamped transcript
+    /// of the captured streams.
+    pub fn transcript(&mut self, enable: bool) -> &mut Self {
+        self.capture.transcript = enable;
+        self
+    }
+
+    /// Enables or disables the forwarding of the captured stdout to
+    /// the parent's stdout, as it arrives.
+    ///
+    /// Forwarded bytes are still captured, up to the stdout limit.
+    pub fn tee_stdout(&mut self, enable: bool) -> &mut Self {
+        self.capture.tee_stdout = enable;
+        self
+    }
+
+    /// Enables or disables the forwarding of the captured stderr to
+    /// the parent's stderr, as it arrives.
+    ///
+    /// Forwarded bytes are still captured, up to the stderr limit.
+    pub fn tee_stderr(&mut self, enable: bool) -> &mut Self {
+        self.capture.tee_stderr = enable;
+        self
+    }
+
+    /// Limits the duration the child process is allowed to run.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Enables or disables the termination of the whole process tree
+    /// of the child.
+    ///
+    /// See [`Termination::tree`] for more details.
+    pub fn kill_tree(&mut self, enable: bool) -> &mut Self {
+        self.termination.tree = enable;
+        self
+    }
+
+    /// Sets the grace period given to the child process between the
+    /// termination request and the kill.
+    ///
+    /// See [`Termination::grace`] for more details.
+    pub fn grace_period(&mut self, grace: Duration) -> &mut Self {
+        self.termination.grace = Some(grace);
+        self
+    }
+
+    /// Configures what runtimes do with the child process when its
+    /// handling gets cancelled.
+    ///
+    /// See [`Termination::on_drop`] for more details.
+    pub fn on_drop(&mut self, on_drop: OnDrop) -> &mut Self {
+        self.termination.on_drop = on_drop;
+        self
+    }
+}
+
+/// Renders the command line, quoting the program and arguments the
+/// way a POSIX shell would need them.
+///
+/// Environment variables and working directory are not rendered.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_quoted(f, &self.get_program())?;
+
+        if let Some(args) = self.get_args() {
+            for arg in args {
+                f.write_str(" ")?;
+                write_quoted(f, &arg)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes `s` single-quoted if it contains characters interpreted by
+/// a POSIX shell.
+pub(crate) fn write_quoted(f: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c);
+
+    if !s.is_empty() && s.chars().all(is_safe) {
+        return f.write_str(s);
+    }
+
+    f.write_str("'")?;
+
+    for (i, part) in s.split('\'').enumerate() {
+        if i > 0 {
+            f.write_str("'\\''")?;
+        }
+        f.write_str(part)?;
+    }
+
+    f.write_str("'")
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::Command;
+
+    #[test]
+    fn display() {
+        let mut command = Command::new("git");
+        command.arg("commit").arg("-m").arg("it's done").arg("");
+        assert_eq!("git commit -m 'it'\\''s done' ''", command.to_string());
+    }
 }
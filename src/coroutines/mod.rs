@@ -10,4 +10,6 @@
 pub mod spawn;
 pub mod spawn_in;
 pub mod spawn_out;
+pub mod spawn_out_decode;
 pub mod spawn_pipeline;
+pub mod spawn_pipeline_decode;
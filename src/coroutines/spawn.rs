@@ -1,12 +1,14 @@
 //! I/O-free coroutine to spawn a process and wait for its exit
 //! status.
 
+use alloc::string::{String, ToString};
 use core::mem;
 
 use log::trace;
 use thiserror::Error;
 
 use crate::{
+    check::{ExitCheck, ExitError},
     command::Command,
     io::{ProcessInput, ProcessOutput},
     status::ExitStatus,
@@ -14,12 +16,18 @@ use crate::{
 
 /// Error emitted by the [`Spawn`] coroutine.
 #[derive(Debug, Error)]
+#[allow(clippy::large_enum_variant)]
 pub enum ProcessSpawnError {
     #[error("Invalid notify arg {arg:?} for state {state:?}")]
     Invalid {
         arg: Option<ProcessOutput>,
         state: ProcessSpawnState,
     },
+
+    /// The process exited with a status that does not count as a
+    /// success (checked mode only).
+    #[error(transparent)]
+    Exit(#[from] ExitError),
 }
 
 /// Result emitted on each step of the [`Spawn`] coroutine.
@@ -35,6 +43,7 @@ pub enum ProcessSpawnResult {
 }
 
 #[derive(Debug, Default)]
+#[allow(clippy::large_enum_variant)]
 pub enum ProcessSpawnState {
     WantsSpawn(Command),
     Spawning,
@@ -53,6 +62,8 @@ pub enum ProcessSpawnState {
 #[derive(Debug)]
 pub struct ProcessSpawn {
     state: ProcessSpawnState,
+    check: Option<ExitCheck>,
+    command: String,
 }
 
 impl ProcessSpawn {
@@ -61,7 +72,24 @@ impl ProcessSpawn {
         let cmd = cmd.into();
         trace!("prepares process to be spawned: {cmd:?}");
         let state = ProcessSpawnState::WantsSpawn(cmd);
-        Self { state }
+        let check = None;
+        let command = String::new();
+        Self {
+            state,
+            check,
+            command,
+        }
+    }
+
+    /// Enables the checked mode.
+    ///
+    /// A process exiting with a status that does not count as a
+    /// success for the given check makes the coroutine fail with
+    /// [`ProcessSpawnError::Exit`]. Since stderr is not captured,
+    /// the error does not carry any stderr excerpt.
+    pub fn checked(&mut self, check: ExitCheck) -> &mut Self {
+        self.check = Some(check);
+        self
     }
 
     /// Makes the spawn progress.
@@ -69,6 +97,9 @@ impl ProcessSpawn {
         match (mem::take(&mut self.state), arg) {
             (ProcessSpawnState::WantsSpawn(cmd), None) => {
                 trace!("wants I/O to spawn process");
+                if self.check.is_some() {
+                    self.command = cmd.to_string();
+                }
                 let input = ProcessInput::Spawn { cmd };
                 self.state = ProcessSpawnState::Spawning;
                 ProcessSpawnResult::Io { input }
@@ -76,6 +107,12 @@ impl ProcessSpawn {
             (ProcessSpawnState::Spawning, Some(ProcessOutput::Spawned { status })) => {
                 trace!("resumes after spawning process");
                 self.state = ProcessSpawnState::Spawned;
+                if let Some(check) = &self.check {
+                    if let Err(err) = check.check(&self.command, &status, &[]) {
+                        let err = err.into();
+                        return ProcessSpawnResult::Err { err };
+                    }
+                }
                 ProcessSpawnResult::Ok { status }
             }
             (state, arg) => {
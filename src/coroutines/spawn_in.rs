@@ -1,12 +1,16 @@
 //! I/O-free coroutine to spawn a process with bytes piped to its
 //! stdin.
 
-use alloc::vec::Vec;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 
 use log::{debug, trace};
 use thiserror::Error;
 
 use crate::{
+    check::{ExitCheck, ExitError},
     command::Command,
     io::{ProcessInput, ProcessOutput},
     status::ExitStatus,
@@ -23,10 +27,16 @@ pub enum SpawnInError {
     /// was already consumed.
     #[error("Command not initialized")]
     NotInitialized,
+
+    /// The process exited with a status that does not count as a
+    /// success (checked mode only).
+    #[error(transparent)]
+    Exit(#[from] ExitError),
 }
 
 /// Result emitted on each step of the [`SpawnIn`] coroutine.
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum SpawnInResult {
     /// The coroutine has successfully terminated its progression.
     Ok { status: ExitStatus },
@@ -40,17 +50,20 @@ pub enum SpawnInResult {
 /// I/O-free coroutine for spawning a process and feeding bytes to its
 /// stdin.
 ///
-/// The runtime pipes `stdin` bytes into the process's standard input
-/// regardless of the [`Stdio`] configuration on the command's stdin
-/// field.
+/// The runtime pipes `stdin` bytes into the process's standard input,
+/// which requires the command's stdin [`Stdio`] to be
+/// [`Stdio::Piped`] or unset.
 ///
-/// To also capture stdout and stderr, see [`SpawnOut`].
+/// To also capture stdout and stderr, see [`ProcessSpawnOut`].
 ///
 /// [`Stdio`]: crate::stdio::Stdio
-/// [`SpawnOut`]: super::spawn_out::SpawnOut
+/// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
+/// [`ProcessSpawnOut`]: super::spawn_out::ProcessSpawnOut
 #[derive(Debug)]
 pub struct SpawnIn {
     inner: Option<(Command, Vec<u8>)>,
+    check: Option<ExitCheck>,
+    command: String,
 }
 
 impl SpawnIn {
@@ -60,9 +73,22 @@ impl SpawnIn {
         trace!("prepare command to be spawned: {cmd:?}");
         Self {
             inner: Some((cmd, stdin)),
+            check: None,
+            command: String::new(),
         }
     }
 
+    /// Enables the checked mode.
+    ///
+    /// A process exiting with a status that does not count as a
+    /// success for the given check makes the coroutine fail with
+    /// [`SpawnInError::Exit`]. Since stderr is not captured, the
+    /// error does not carry any stderr excerpt.
+    pub fn checked(&mut self, check: ExitCheck) -> &mut Self {
+        self.check = Some(check);
+        self
+    }
+
     /// Makes the spawn-in progress.
     pub fn resume(&mut self, arg: Option<ProcessOutput>) -> SpawnInResult {
         match arg {
@@ -73,12 +99,21 @@ impl SpawnIn {
                     };
                 };
                 trace!("wants process I/O to spawn command with stdin bytes");
+                if self.check.is_some() {
+                    self.command = cmd.to_string();
+                }
                 SpawnInResult::Io {
                     input: ProcessInput::SpawnIn { cmd, stdin },
                 }
             }
             Some(ProcessOutput::SpawnedIn { status }) => {
                 debug!("resume after spawning command: {status:?}");
+                if let Some(check) = &self.check {
+                    if let Err(err) = check.check(&self.command, &status, &[]) {
+                        let err = err.into();
+                        return SpawnInResult::Err { err };
+                    }
+                }
                 SpawnInResult::Ok { status }
             }
             Some(output) => SpawnInResult::Err {
@@ -1,13 +1,19 @@
 //! I/O-free coroutine to spawn a process and wait for its exit
 //! status.
 
-use alloc::vec::Vec;
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::mem;
 
 use log::trace;
 use thiserror::Error;
 
 use crate::{
+    capture::{TranscriptEvent, Truncated},
+    check::{ExitCheck, ExitError},
     command::Command,
     coroutines::spawn::ProcessSpawnState,
     io::{ProcessInput, ProcessOutput},
@@ -16,12 +22,18 @@ use crate::{
 
 /// Error emitted by the [`Spawn`] coroutine.
 #[derive(Debug, Error)]
+#[allow(clippy::large_enum_variant)]
 pub enum ProcessSpawnOutError {
     #[error("Invalid process spawn arg {arg:?} for state {state:?}")]
     Invalid {
         arg: Option<ProcessOutput>,
         state: ProcessSpawnState,
     },
+
+    /// The process exited with a status that does not count as a
+    /// success (checked mode only).
+    #[error(transparent)]
+    Exit(#[from] ExitError),
 }
 
 /// Result emitted on each step of the [`Spawn`] coroutine.
@@ -30,8 +42,11 @@ pub enum ProcessSpawnOutResult {
     /// The coroutine has successfully terminated its progression.
     Ok {
         status: ExitStatus,
-        stdout: Vec<u8>,
-        stderr: Vec<u8>,
+        stdout: Option<Vec<u8>>,
+        stderr: Option<Vec<u8>>,
+        truncated: Truncated,
+        transcript: Option<Vec<TranscriptEvent>>,
+        fds: BTreeMap<i32, Vec<u8>>,
     },
     /// A process I/O needs to be performed to make the coroutine
     /// progress.
@@ -40,16 +55,23 @@ pub enum ProcessSpawnOutResult {
     Err { err: ProcessSpawnOutError },
 }
 
-/// I/O-free coroutine for spawning a process and waiting for its exit
-/// status.
+/// I/O-free coroutine for spawning a process, capturing its stdout
+/// and stderr, and waiting for its exit status.
 ///
-/// Use this when you only care about whether the process succeeded or
-/// failed. To also capture stdout and stderr, see [`SpawnOut`].
+/// Capture is driven by the [`Stdio`] configuration of the command:
+/// a stream is captured if it is [`Stdio::Piped`] or unset, otherwise
+/// it is returned as `None`. For example, setting stderr to
+/// [`Stdio::Inherit`] captures stdout while letting prompts written
+/// to stderr reach the user.
 ///
-/// [`SpawnOut`]: super::spawn_out::SpawnOut
+/// [`Stdio`]: crate::stdio::Stdio
+/// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
+/// [`Stdio::Inherit`]: crate::stdio::Stdio::Inherit
 #[derive(Debug)]
 pub struct ProcessSpawnOut {
     state: ProcessSpawnState,
+    check: Option<ExitCheck>,
+    command: String,
 }
 
 impl ProcessSpawnOut {
@@ -58,7 +80,24 @@ impl ProcessSpawnOut {
         let cmd = cmd.into();
         trace!("prepares process to be spawned: {cmd:?}");
         let state = ProcessSpawnState::WantsSpawn(cmd);
-        Self { state }
+        let check = None;
+        let command = String::new();
+        Self {
+            state,
+            check,
+            command,
+        }
+    }
+
+    /// Enables the checked mode.
+    ///
+    /// A process exiting with a status that does not count as a
+    /// success for the given check makes the coroutine fail with
+    /// [`ProcessSpawnOutError::Exit`], carrying an excerpt of the
+    /// captured stderr.
+    pub fn checked(&mut self, check: ExitCheck) -> &mut Self {
+        self.check = Some(check);
+        self
     }
 
     /// Makes the spawn progress.
@@ -66,6 +105,9 @@ impl ProcessSpawnOut {
         match (mem::take(&mut self.state), arg) {
             (ProcessSpawnState::WantsSpawn(cmd), None) => {
                 trace!("wants I/O to spawn process and collect output");
+                if self.check.is_some() {
+                    self.command = cmd.to_string();
+                }
                 let input = ProcessInput::SpawnOut { cmd };
                 self.state = ProcessSpawnState::Spawning;
                 ProcessSpawnOutResult::Io { input }
@@ -76,14 +118,30 @@ impl ProcessSpawnOut {
                     status,
                     stdout,
                     stderr,
+                    truncated,
+                    transcript,
+                    fds,
                 }),
             ) => {
                 trace!("resumes after spawning process and collecting output");
                 self.state = ProcessSpawnState::Spawned;
+                if let Some(check) = &self.check {
+                    if let Err(err) = check.check(
+                        &self.command,
+                        &status,
+                        stderr.as_deref().unwrap_or_default(),
+                    ) {
+                        let err = err.into();
+                        return ProcessSpawnOutResult::Err { err };
+                    }
+                }
                 ProcessSpawnOutResult::Ok {
                     status,
                     stdout,
                     stderr,
+                    truncated,
+                    transcript,
+                    fds,
                 }
             }
             (state, arg) => {
@@ -0,0 +1,416 @@
+//! I/O-free coroutine to spawn a process and decode its stdout.
+
+use alloc::{
+    string::{FromUtf8Error, String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "serde")]
+use core::{fmt, marker::PhantomData};
+
+use log::trace;
+use thiserror::Error;
+
+use crate::{
+    capture::Truncated,
+    check::ExitCheck,
+    command::Command,
+    coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutError, ProcessSpawnOutResult},
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+};
+
+/// Error emitted by a [`Decode`] implementation.
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    /// The captured stdout is not valid UTF-8.
+    #[error("Invalid UTF-8 at byte {}", .0.utf8_error().valid_up_to())]
+    Utf8(#[source] FromUtf8Error),
+
+    /// The captured stdout is not a valid JSON document for the
+    /// requested type.
+    #[cfg(feature = "serde")]
+    #[error("Invalid JSON at line {}, column {}", .0.line(), .0.column())]
+    Json(#[source] serde_json::Error),
+}
+
+/// Decoder turning the raw stdout of a process into a typed value.
+pub trait Decode {
+    /// The decoded value.
+    type Output;
+
+    /// Decodes the given raw stdout.
+    fn decode(&self, stdout: Vec<u8>) -> Result<Self::Output, DecodeError>;
+}
+
+/// Decodes stdout as a UTF-8 string, trimmed of leading and trailing
+/// whitespaces.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Text;
+
+impl Decode for Text {
+    type Output = String;
+
+    fn decode(&self, stdout: Vec<u8>) -> Result<Self::Output, DecodeError> {
+        let stdout = String::from_utf8(stdout).map_err(DecodeError::Utf8)?;
+        Ok(stdout.trim().to_string())
+    }
+}
+
+/// Decodes stdout as a list of UTF-8 lines.
+///
+/// Lines are split on `\n` or `\r\n`. A final line ending does not
+/// produce an empty trailing line.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Lines;
+
+impl Decode for Lines {
+    type Output = Vec<String>;
+
+    fn decode(&self, stdout: Vec<u8>) -> Result<Self::Output, DecodeError> {
+        let stdout = String::from_utf8(stdout).map_err(DecodeError::Utf8)?;
+        Ok(stdout.lines().map(ToString::to_string).collect())
+    }
+}
+
+/// Decodes stdout as a list of NUL-separated UTF-8 records, as
+/// produced by `find -print0` or `git ls-files -z`.
+///
+/// A final NUL terminator does not produce an empty trailing record.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NulRecords;
+
+impl Decode for NulRecords {
+    type Output = Vec<String>;
+
+    fn decode(&self, stdout: Vec<u8>) -> Result<Self::Output, DecodeError> {
+        let stdout = String::from_utf8(stdout).map_err(DecodeError::Utf8)?;
+        let stdout = stdout.strip_suffix('\0').unwrap_or(&stdout);
+
+        if stdout.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        Ok(stdout.split('\0').map(ToString::to_string).collect())
+    }
+}
+
+/// Decodes stdout as a JSON document deserialized into `T`.
+///
+/// Requires the `serde` cargo feature.
+#[cfg(feature = "serde")]
+pub struct Json<T>(PhantomData<fn() -> T>);
+
+#[cfg(feature = "serde")]
+impl<T> Json<T> {
+    /// Creates a new JSON decoder.
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Default for Json<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Clone for Json<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Copy for Json<T> {}
+
+#[cfg(feature = "serde")]
+impl<T> fmt::Debug for Json<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Json<{}>", core::any::type_name::<T>())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned> Decode for Json<T> {
+    type Output = T;
+
+    fn decode(&self, stdout: Vec<u8>) -> Result<Self::Output, DecodeError> {
+        serde_json::from_slice(&stdout).map_err(DecodeError::Json)
+    }
+}
+
+/// Error emitted by the [`ProcessSpawnOutDecode`] coroutine.
+#[derive(Debug, Error)]
+#[allow(clippy::large_enum_variant)]
+pub enum ProcessSpawnOutDecodeError {
+    #[error(transparent)]
+    SpawnOut(#[from] ProcessSpawnOutError),
+
+    /// The stdout of the process was not captured, because of the
+    /// [`Stdio`] configuration of the command.
+    ///
+    /// Carries the exit status and the raw stderr of the process.
+    ///
+    /// [`Stdio`]: crate::stdio::Stdio
+    #[error("Cannot decode stdout of process (status {status:?}, stderr {:?}): stdout was not captured", String::from_utf8_lossy(.stderr.as_deref().unwrap_or_default()))]
+    NotCaptured {
+        status: ExitStatus,
+        stderr: Option<Vec<u8>>,
+    },
+
+    /// The stdout of the process could not be decoded.
+    ///
+    /// Carries the exit status and the raw stderr of the process,
+    /// which usually explain why the output is not the expected one.
+    #[error("Cannot decode stdout of process (status {status:?}, stderr {:?}): {err}", String::from_utf8_lossy(.stderr.as_deref().unwrap_or_default()))]
+    Decode {
+        #[source]
+        err: DecodeError,
+        status: ExitStatus,
+        stderr: Option<Vec<u8>>,
+    },
+}
+
+/// Result emitted on each step of the [`ProcessSpawnOutDecode`]
+/// coroutine.
+#[derive(Debug)]
+pub enum ProcessSpawnOutDecodeResult<T> {
+    /// The coroutine has successfully terminated its progression.
+    Ok {
+        status: ExitStatus,
+        output: T,
+        stderr: Option<Vec<u8>>,
+        truncated: Truncated,
+    },
+    /// A process I/O needs to be performed to make the coroutine
+    /// progress.
+    Io { input: ProcessInput },
+    /// The coroutine encountered an unrecoverable error.
+    Err { err: ProcessSpawnOutDecodeError },
+}
+
+/// I/O-free coroutine for spawning a process and decoding its
+/// stdout.
+///
+/// Wraps [`ProcessSpawnOut`] and decodes the captured stdout using
+/// the given [`Decode`] implementation: [`Text`], [`Lines`],
+/// [`NulRecords`] or `Json` (requires the `serde` cargo feature).
+#[derive(Debug)]
+pub struct ProcessSpawnOutDecode<D> {
+    spawn: ProcessSpawnOut,
+    decoder: D,
+}
+
+impl<D: Decode> ProcessSpawnOutDecode<D> {
+    /// Creates a new coroutine that will spawn the given command and
+    /// decode its stdout with `decoder`.
+    pub fn new(cmd: impl Into<Command>, decoder: D) -> Self {
+        let spawn = ProcessSpawnOut::new(cmd);
+        Self { spawn, decoder }
+    }
+
+    /// Enables the checked mode of the underlying
+    /// [`ProcessSpawnOut`] coroutine.
+    ///
+    /// The exit status is checked before stdout gets decoded.
+    pub fn checked(&mut self, check: ExitCheck) -> &mut Self {
+        self.spawn.checked(check);
+        self
+    }
+
+    /// Makes the spawn progress.
+    pub fn resume(&mut self, arg: Option<ProcessOutput>) -> ProcessSpawnOutDecodeResult<D::Output> {
+        let (status, stdout, stderr, truncated) = match self.spawn.resume(arg) {
+            ProcessSpawnOutResult::Ok {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                ..
+            } => (status, stdout, stderr, truncated),
+            ProcessSpawnOutResult::Io { input } => {
+                return ProcessSpawnOutDecodeResult::Io { input };
+            }
+            ProcessSpawnOutResult::Err { err } => {
+                let err = err.into();
+                return ProcessSpawnOutDecodeResult::Err { err };
+            }
+        };
+
+        let Some(stdout) = stdout else {
+            let err = ProcessSpawnOutDecodeError::NotCaptured { status, stderr };
+            return ProcessSpawnOutDecodeResult::Err { err };
+        };
+
+        trace!("decodes process stdout");
+
+        match self.decoder.decode(stdout) {
+            Ok(output) => ProcessSpawnOutDecodeResult::Ok {
+                status,
+                output,
+                stderr,
+                truncated,
+            },
+            Err(err) => {
+                let err = ProcessSpawnOutDecodeError::Decode {
+                    err,
+                    status,
+                    stderr,
+                };
+                ProcessSpawnOutDecodeResult::Err { err }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+
+    use crate::{
+        command::Command,
+        io::{ProcessInput, ProcessOutput},
+        status::ExitStatus,
+    };
+
+    use super::*;
+
+    fn spawned_out(stdout: &[u8]) -> ProcessOutput {
+        ProcessOutput::SpawnedOut {
+            status: ExitStatus::new(Some(0)),
+            stdout: Some(stdout.to_vec()),
+            stderr: Some(b"warning".to_vec()),
+            truncated: Default::default(),
+            transcript: None,
+            fds: Default::default(),
+        }
+    }
+
+    fn run<D: Decode>(decoder: D, stdout: &[u8]) -> ProcessSpawnOutDecodeResult<D::Output> {
+        let mut spawn = ProcessSpawnOutDecode::new(Command::new("program"), decoder);
+
+        let ProcessSpawnOutDecodeResult::Io {
+            input: ProcessInput::SpawnOut { .. },
+        } = spawn.resume(None)
+        else {
+            panic!("should emit spawn out I/O");
+        };
+
+        spawn.resume(Some(spawned_out(stdout)))
+    }
+
+    #[test]
+    fn text() {
+        let ProcessSpawnOutDecodeResult::Ok { output, .. } = run(Text, b"  hello world\n") else {
+            panic!("should decode text");
+        };
+
+        assert_eq!("hello world", output);
+    }
+
+    #[test]
+    fn lines() {
+        let ProcessSpawnOutDecodeResult::Ok { output, .. } = run(Lines, b"a\r\nb\n\nc\n") else {
+            panic!("should decode lines");
+        };
+
+        assert_eq!(vec!["a", "b", "", "c"], output);
+    }
+
+    #[test]
+    fn nul_records() {
+        let ProcessSpawnOutDecodeResult::Ok { output, .. } = run(NulRecords, b"a b\0c\0") else {
+            panic!("should decode records");
+        };
+
+        assert_eq!(vec!["a b", "c"], output);
+
+        let ProcessSpawnOutDecodeResult::Ok { output, .. } = run(NulRecords, b"") else {
+            panic!("should decode empty records");
+        };
+
+        assert_eq!(Vec::<String>::new(), output);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let ProcessSpawnOutDecodeResult::Err { err } = run(Text, b"ab\xffcd") else {
+            panic!("should fail to decode text");
+        };
+
+        let ProcessSpawnOutDecodeError::Decode {
+            err: DecodeError::Utf8(_),
+            ref status,
+            ref stderr,
+        } = err
+        else {
+            panic!("should be a UTF-8 decode error");
+        };
+
+        assert!(status.success());
+        assert_eq!(Some(b"warning".as_slice()), stderr.as_deref());
+
+        assert_eq!(
+            "Cannot decode stdout of process (status 0, stderr \"warning\"): Invalid UTF-8 at byte 2",
+            err.to_string(),
+        );
+    }
+
+    #[test]
+    fn not_captured() {
+        let mut spawn = ProcessSpawnOutDecode::new(Command::new("program"), Text);
+        spawn.resume(None);
+
+        let output = ProcessOutput::SpawnedOut {
+            status: ExitStatus::new(Some(1)),
+            stdout: None,
+            stderr: Some(b"failed".to_vec()),
+            truncated: Default::default(),
+            transcript: None,
+            fds: Default::default(),
+        };
+
+        let ProcessSpawnOutDecodeResult::Err { err } = spawn.resume(Some(output)) else {
+            panic!("should fail to decode uncaptured stdout");
+        };
+
+        assert_eq!(
+            "Cannot decode stdout of process (status 1, stderr \"failed\"): stdout was not captured",
+            err.to_string(),
+        );
+
+        let ProcessSpawnOutDecodeError::NotCaptured { stderr, .. } = err else {
+            panic!("should be a not captured error");
+        };
+
+        assert_eq!(Some(b"failed".to_vec()), stderr);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let decoder = Json::<Vec<u32>>::new();
+        let ProcessSpawnOutDecodeResult::Ok { output, .. } = run(decoder, b"[1, 2, 3]\n") else {
+            panic!("should decode json");
+        };
+
+        assert_eq!(vec![1, 2, 3], output);
+
+        let decoder = Json::<Vec<u32>>::new();
+        let ProcessSpawnOutDecodeResult::Err { err } = run(decoder, b"[1,\n2,") else {
+            panic!("should fail to decode json");
+        };
+
+        let ProcessSpawnOutDecodeError::Decode {
+            err: DecodeError::Json(err),
+            ..
+        } = err
+        else {
+            panic!("should be a JSON decode error");
+        };
+
+        assert_eq!(2, err.line());
+    }
+}
@@ -1,11 +1,14 @@
 //! I/O-free coroutine to spawn a pipeline of processes.
 
-use alloc::vec::Vec;
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use core::fmt::Write;
 
 use log::{debug, trace};
 use thiserror::Error;
 
 use crate::{
+    capture::{TranscriptEvent, Truncated},
+    check::{ExitCheck, ExitError},
     command::Command,
     io::{ProcessInput, ProcessOutput},
     status::ExitStatus,
@@ -22,16 +25,25 @@ pub enum SpawnPipelineError {
     /// commands were already consumed.
     #[error("Commands not initialized")]
     NotInitialized,
+
+    /// The last process exited with a status that does not count as
+    /// a success (checked mode only).
+    #[error(transparent)]
+    Exit(#[from] ExitError),
 }
 
 /// Result emitted on each step of the [`SpawnPipeline`] coroutine.
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum SpawnPipelineResult {
     /// The coroutine has successfully terminated its progression.
     Ok {
         status: ExitStatus,
-        stdout: Vec<u8>,
-        stderr: Vec<u8>,
+        stdout: Option<Vec<u8>>,
+        stderr: Option<Vec<u8>>,
+        truncated: Truncated,
+        transcript: Option<Vec<TranscriptEvent>>,
+        fds: BTreeMap<i32, Vec<u8>>,
     },
     /// A process I/O needs to be performed to make the coroutine
     /// progress.
@@ -50,6 +62,8 @@ pub enum SpawnPipelineResult {
 #[derive(Debug)]
 pub struct SpawnPipeline {
     cmds: Option<Vec<Command>>,
+    check: Option<ExitCheck>,
+    command: String,
 }
 
 impl SpawnPipeline {
@@ -61,7 +75,23 @@ impl SpawnPipeline {
             "prepare {} commands to be spawned as a pipeline",
             cmds.len()
         );
-        Self { cmds: Some(cmds) }
+        Self {
+            cmds: Some(cmds),
+            check: None,
+            command: String::new(),
+        }
+    }
+
+    /// Enables the checked mode.
+    ///
+    /// The last process exiting with a status that does not count as
+    /// a success for the given check makes the coroutine fail with
+    /// [`SpawnPipelineError::Exit`], carrying an excerpt of the
+    /// captured stderr. The rendered command line joins all the
+    /// commands with `|`.
+    pub fn checked(&mut self, check: ExitCheck) -> &mut Self {
+        self.check = Some(check);
+        self
     }
 
     /// Makes the spawn-pipeline progress.
@@ -74,6 +104,9 @@ impl SpawnPipeline {
                     };
                 };
                 trace!("wants process I/O to spawn pipeline");
+                if self.check.is_some() {
+                    self.command = render_pipeline(&cmds);
+                }
                 SpawnPipelineResult::Io {
                     input: ProcessInput::SpawnPipeline { cmds },
                 }
@@ -82,12 +115,28 @@ impl SpawnPipeline {
                 status,
                 stdout,
                 stderr,
+                truncated,
+                transcript,
+                fds,
             }) => {
                 debug!("resume after spawning pipeline: {:?}", status);
+                if let Some(check) = &self.check {
+                    if let Err(err) = check.check(
+                        &self.command,
+                        &status,
+                        stderr.as_deref().unwrap_or_default(),
+                    ) {
+                        let err = err.into();
+                        return SpawnPipelineResult::Err { err };
+                    }
+                }
                 SpawnPipelineResult::Ok {
                     status,
                     stdout,
                     stderr,
+                    truncated,
+                    transcript,
+                    fds,
                 }
             }
             Some(output) => SpawnPipelineResult::Err {
@@ -96,3 +145,18 @@ impl SpawnPipeline {
         }
     }
 }
+
+/// Renders the command line of a pipeline, joining each command with
+/// `|`.
+fn render_pipeline(cmds: &[Command]) -> String {
+    let mut line = String::new();
+
+    for (i, cmd) in cmds.iter().enumerate() {
+        if i > 0 {
+            line.push_str(" | ");
+        }
+        let _ = write!(line, "{cmd}");
+    }
+
+    line
+}
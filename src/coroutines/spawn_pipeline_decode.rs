@@ -0,0 +1,220 @@
+//! I/O-free coroutine to spawn a pipeline of processes and decode
+//! the stdout of the last one.
+
+use alloc::{string::String, vec::Vec};
+
+use log::trace;
+use thiserror::Error;
+
+use crate::{
+    capture::Truncated,
+    check::ExitCheck,
+    command::Command,
+    coroutines::{
+        spawn_out_decode::{Decode, DecodeError},
+        spawn_pipeline::{SpawnPipeline, SpawnPipelineError, SpawnPipelineResult},
+    },
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+};
+
+/// Error emitted by the [`SpawnPipelineDecode`] coroutine.
+#[derive(Debug, Error)]
+pub enum SpawnPipelineDecodeError {
+    #[error(transparent)]
+    SpawnPipeline(#[from] SpawnPipelineError),
+
+    /// The stdout of the last process was not captured, because of
+    /// the [`Stdio`] configuration of its command.
+    ///
+    /// Carries the exit status and the raw stderr of the last
+    /// process.
+    ///
+    /// [`Stdio`]: crate::stdio::Stdio
+    #[error("Cannot decode stdout of pipeline (status {status:?}, stderr {:?}): stdout was not captured", String::from_utf8_lossy(.stderr.as_deref().unwrap_or_default()))]
+    NotCaptured {
+        status: ExitStatus,
+        stderr: Option<Vec<u8>>,
+    },
+
+    /// The stdout of the last process could not be decoded.
+    ///
+    /// Carries the exit status and the raw stderr of the last
+    /// process, which usually explain why the output is not the
+    /// expected one.
+    #[error("Cannot decode stdout of pipeline (status {status:?}, stderr {:?}): {err}", String::from_utf8_lossy(.stderr.as_deref().unwrap_or_default()))]
+    Decode {
+        #[source]
+        err: DecodeError,
+        status: ExitStatus,
+        stderr: Option<Vec<u8>>,
+    },
+}
+
+/// Result emitted on each step of the [`SpawnPipelineDecode`]
+/// coroutine.
+#[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum SpawnPipelineDecodeResult<T> {
+    /// The coroutine has successfully terminated its progression.
+    Ok {
+        status: ExitStatus,
+        output: T,
+        stderr: Option<Vec<u8>>,
+        truncated: Truncated,
+    },
+    /// A process I/O needs to be performed to make the coroutine
+    /// progress.
+    Io { input: ProcessInput },
+    /// The coroutine encountered an unrecoverable error.
+    Err { err: SpawnPipelineDecodeError },
+}
+
+/// I/O-free coroutine for spawning a pipeline of processes and
+/// decoding the stdout of the last one.
+///
+/// Wraps [`SpawnPipeline`] and decodes the captured stdout using the
+/// given [`Decode`] implementation, like [`ProcessSpawnOutDecode`]
+/// does for a single process.
+///
+/// [`ProcessSpawnOutDecode`]: super::spawn_out_decode::ProcessSpawnOutDecode
+#[derive(Debug)]
+pub struct SpawnPipelineDecode<D> {
+    spawn: SpawnPipeline,
+    decoder: D,
+}
+
+impl<D: Decode> SpawnPipelineDecode<D> {
+    /// Creates a new coroutine that will spawn the given commands as
+    /// a pipeline and decode the stdout of the last one with
+    /// `decoder`.
+    pub fn new(cmds: impl IntoIterator<Item = Command>, decoder: D) -> Self {
+        let spawn = SpawnPipeline::new(cmds);
+        Self { spawn, decoder }
+    }
+
+    /// Enables the checked mode of the underlying [`SpawnPipeline`]
+    /// coroutine.
+    ///
+    /// The exit status is checked before stdout gets decoded.
+    pub fn checked(&mut self, check: ExitCheck) -> &mut Self {
+        self.spawn.checked(check);
+        self
+    }
+
+    /// Makes the spawn-pipeline progress.
+    pub fn resume(&mut self, arg: Option<ProcessOutput>) -> SpawnPipelineDecodeResult<D::Output> {
+        let (status, stdout, stderr, truncated) = match self.spawn.resume(arg) {
+            SpawnPipelineResult::Ok {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                ..
+            } => (status, stdout, stderr, truncated),
+            SpawnPipelineResult::Io { input } => {
+                return SpawnPipelineDecodeResult::Io { input };
+            }
+            SpawnPipelineResult::Err { err } => {
+                let err = err.into();
+                return SpawnPipelineDecodeResult::Err { err };
+            }
+        };
+
+        let Some(stdout) = stdout else {
+            let err = SpawnPipelineDecodeError::NotCaptured { status, stderr };
+            return SpawnPipelineDecodeResult::Err { err };
+        };
+
+        trace!("decodes pipeline stdout");
+
+        match self.decoder.decode(stdout) {
+            Ok(output) => SpawnPipelineDecodeResult::Ok {
+                status,
+                output,
+                stderr,
+                truncated,
+            },
+            Err(err) => {
+                let err = SpawnPipelineDecodeError::Decode {
+                    err,
+                    status,
+                    stderr,
+                };
+                SpawnPipelineDecodeResult::Err { err }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use crate::{
+        command::Command,
+        coroutines::spawn_out_decode::{Lines, Text},
+        io::{ProcessInput, ProcessOutput},
+        status::ExitStatus,
+    };
+
+    use super::*;
+
+    fn spawned_pipeline(stdout: Option<&[u8]>) -> ProcessOutput {
+        ProcessOutput::SpawnedPipeline {
+            status: ExitStatus::new(Some(0)),
+            stdout: stdout.map(<[u8]>::to_vec),
+            stderr: Some(b"warning".to_vec()),
+            truncated: Default::default(),
+            transcript: None,
+            fds: Default::default(),
+        }
+    }
+
+    fn run<D: Decode>(decoder: D, stdout: Option<&[u8]>) -> SpawnPipelineDecodeResult<D::Output> {
+        let cmds = [Command::new("first"), Command::new("last")];
+        let mut spawn = SpawnPipelineDecode::new(cmds, decoder);
+
+        let SpawnPipelineDecodeResult::Io {
+            input: ProcessInput::SpawnPipeline { .. },
+        } = spawn.resume(None)
+        else {
+            panic!("should emit spawn pipeline I/O");
+        };
+
+        spawn.resume(Some(spawned_pipeline(stdout)))
+    }
+
+    #[test]
+    fn lines() {
+        let SpawnPipelineDecodeResult::Ok { output, stderr, .. } = run(Lines, Some(b"a\nb\n"))
+        else {
+            panic!("should decode lines");
+        };
+
+        assert_eq!(vec!["a", "b"], output);
+        assert_eq!(Some(b"warning".to_vec()), stderr);
+    }
+
+    #[test]
+    fn errors() {
+        let SpawnPipelineDecodeResult::Err { err } = run(Text, Some(b"ab\xffcd")) else {
+            panic!("should fail to decode text");
+        };
+
+        assert_eq!(
+            "Cannot decode stdout of pipeline (status 0, stderr \"warning\"): Invalid UTF-8 at byte 2",
+            err.to_string(),
+        );
+
+        let SpawnPipelineDecodeResult::Err { err } = run(Text, None) else {
+            panic!("should fail to decode uncaptured stdout");
+        };
+
+        let SpawnPipelineDecodeError::NotCaptured { stderr, .. } = err else {
+            panic!("should be a not captured error");
+        };
+
+        assert_eq!(Some(b"warning".to_vec()), stderr);
+    }
+}
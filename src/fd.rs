@@ -0,0 +1,34 @@
+//! Extra file descriptor configuration.
+
+use alloc::{string::String, vec::Vec};
+
+/// Configuration for an extra file descriptor of a child process,
+/// beyond stdin, stdout and stderr.
+///
+/// Used in [`Command`] to pass descriptors to tools like `gpg
+/// --passphrase-fd 3` or `--status-fd 4`.
+///
+/// Runtimes set extra descriptors up at spawn time. They are only
+/// supported on Unix.
+///
+/// [`Command`]: crate::command::Command
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ExtraFd {
+    /// Create a new pipe from which the child reads the given bytes.
+    Input(Vec<u8>),
+    /// Create a new pipe whose output is captured by the runtime.
+    ///
+    /// Only supported by requests capturing output, like
+    /// [`ProcessInput::SpawnOut`].
+    ///
+    /// [`ProcessInput::SpawnOut`]: crate::io::ProcessInput::SpawnOut
+    Capture,
+    /// Read from the file at the given path (`3< path`).
+    ReadFile(String),
+    /// Write to the file at the given path, creating it or
+    /// truncating it (`3> path`).
+    WriteFile(String),
+    /// Append to the file at the given path, creating it if needed
+    /// (`3>> path`).
+    AppendFile(String),
+}
@@ -1,8 +1,12 @@
 //! Process input and output.
 
-use alloc::vec::Vec;
+use alloc::{collections::BTreeMap, vec::Vec};
 
-use crate::{command::Command, status::ExitStatus};
+use crate::{
+    capture::{TranscriptEvent, Truncated},
+    command::Command,
+    status::ExitStatus,
+};
 
 /// Process input emitted by [coroutines] and processed by [runtimes].
 ///
@@ -18,13 +22,31 @@ pub enum ProcessInput {
     Spawn { cmd: Command },
     /// Request to spawn a process, capture its stdout and stderr,
     /// and wait for its exit status.
+    ///
+    /// A stream is captured if its [`Stdio`] is [`Stdio::Piped`] or
+    /// unset. Streams configured otherwise are not captured.
+    ///
+    /// Extra file descriptors configured with [`ExtraFd::Capture`]
+    /// are captured as well.
+    ///
+    /// [`Stdio`]: crate::stdio::Stdio
+    /// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
+    /// [`ExtraFd::Capture`]: crate::fd::ExtraFd::Capture
     SpawnOut { cmd: Command },
     /// Request to spawn a process, feed bytes to its stdin, and wait
     /// for its exit status.
+    ///
+    /// The stdin [`Stdio`] must be [`Stdio::Piped`] or unset.
+    ///
+    /// [`Stdio`]: crate::stdio::Stdio
+    /// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
     SpawnIn { cmd: Command, stdin: Vec<u8> },
     /// Request to spawn a pipeline of processes, feeding each
     /// process's stdout into the next process's stdin, and collecting
     /// the last process's stdout, stderr, and exit status.
+    ///
+    /// The last process's streams are captured the same way as
+    /// [`ProcessInput::SpawnOut`].
     SpawnPipeline { cmds: Vec<Command> },
 }
 
@@ -43,10 +65,23 @@ pub enum ProcessOutput {
     SpawnedOut {
         /// The exit status of the process.
         status: ExitStatus,
-        /// The raw bytes written to stdout.
-        stdout: Vec<u8>,
-        /// The raw bytes written to stderr.
-        stderr: Vec<u8>,
+        /// The raw bytes written to stdout, or `None` if stdout was
+        /// not captured.
+        stdout: Option<Vec<u8>>,
+        /// The raw bytes written to stderr, or `None` if stderr was
+        /// not captured.
+        stderr: Option<Vec<u8>>,
+        /// Which captured streams exceeded their size limit.
+        truncated: Truncated,
+        /// The timestamped transcript of the captured streams, or
+        /// `None` if the transcript mode was not enabled.
+        transcript: Option<Vec<TranscriptEvent>>,
+        /// The raw bytes written to the extra file descriptors
+        /// configured with [`ExtraFd::Capture`], by descriptor
+        /// number.
+        ///
+        /// [`ExtraFd::Capture`]: crate::fd::ExtraFd::Capture
+        fds: BTreeMap<i32, Vec<u8>>,
     },
     /// Response to a [`ProcessInput::SpawnIn`] request.
     SpawnedIn { status: ExitStatus },
@@ -54,9 +89,22 @@ pub enum ProcessOutput {
     SpawnedPipeline {
         /// The exit status of the process.
         status: ExitStatus,
-        /// The raw bytes written to stdout.
-        stdout: Vec<u8>,
-        /// The raw bytes written to stderr.
-        stderr: Vec<u8>,
+        /// The raw bytes written to stdout, or `None` if stdout was
+        /// not captured.
+        stdout: Option<Vec<u8>>,
+        /// The raw bytes written to stderr, or `None` if stderr was
+        /// not captured.
+        stderr: Option<Vec<u8>>,
+        /// Which captured streams exceeded their size limit.
+        truncated: Truncated,
+        /// The timestamped transcript of the captured streams, or
+        /// `None` if the transcript mode was not enabled.
+        transcript: Option<Vec<TranscriptEvent>>,
+        /// The raw bytes written to the extra file descriptors
+        /// configured with [`ExtraFd::Capture`], by descriptor
+        /// number.
+        ///
+        /// [`ExtraFd::Capture`]: crate::fd::ExtraFd::Capture
+        fds: BTreeMap<i32, Vec<u8>>,
     },
 }
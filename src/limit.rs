@@ -0,0 +1,68 @@
+//! Process resource limits configuration.
+
+#[cfg(any(feature = "std", feature = "tokio", feature = "smol"))]
+use thiserror::Error;
+
+/// Resource of a child process that can be limited.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Resource {
+    /// Maximum size of the virtual memory, in bytes (`RLIMIT_AS`).
+    AddressSpace,
+    /// Maximum amount of CPU time, in seconds (`RLIMIT_CPU`).
+    Cpu,
+    /// Maximum number of open file descriptors (`RLIMIT_NOFILE`).
+    OpenFiles,
+    /// Maximum size of core dump files, in bytes (`RLIMIT_CORE`).
+    CoreSize,
+    /// Maximum size of the files created, in bytes (`RLIMIT_FSIZE`).
+    FileSize,
+}
+
+/// Soft and hard limits of a [`Resource`].
+///
+/// The soft limit is the one enforced, the hard limit is the ceiling
+/// up to which the process can raise its soft limit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rlimit {
+    /// The soft limit.
+    pub soft: u64,
+    /// The hard limit.
+    pub hard: u64,
+}
+
+impl Rlimit {
+    /// Value of an unlimited resource.
+    pub const INFINITY: u64 = u64::MAX;
+
+    /// Creates new limits with the same soft and hard values.
+    pub fn new(limit: u64) -> Self {
+        Self {
+            soft: limit,
+            hard: limit,
+        }
+    }
+}
+
+/// Error reported by runtimes when a child process fails to apply
+/// one of its limits before executing the program.
+///
+/// Runtimes return it wrapped in the [`std::io::Error`] of the
+/// spawn, from which it can be downcast.
+#[cfg(any(feature = "std", feature = "tokio", feature = "smol"))]
+#[derive(Debug, Error)]
+pub enum LimitError {
+    #[error("Cannot set limit of resource {resource:?} to {limit:?}: {err}")]
+    Rlimit {
+        resource: Resource,
+        limit: Rlimit,
+        #[source]
+        err: std::io::Error,
+    },
+
+    #[error("Cannot set nice value to {nice}: {err}")]
+    Nice {
+        nice: i32,
+        #[source]
+        err: std::io::Error,
+    },
+}
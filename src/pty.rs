@@ -0,0 +1,43 @@
+//! Pseudo-terminal configuration.
+
+/// Configuration of the pseudo-terminal allocated by runtimes for a
+/// child process.
+///
+/// Some programs (`ssh`, `pinentry-tty`, `less`) behave differently
+/// or refuse to run when their standard streams are not attached to
+/// a terminal. In PTY mode, the standard streams of a [`Command`]
+/// that are not explicitly configured are attached to a new
+/// pseudo-terminal, which also becomes the controlling terminal of
+/// the child.
+///
+/// The terminal output is captured as stdout by requests capturing
+/// output, like [`ProcessInput::SpawnOut`]. Since stdout and stderr
+/// share the same terminal, they end up in the same buffer, with the
+/// line endings translated by the terminal (`\r\n`). Other requests
+/// drain and discard the terminal output.
+///
+/// Only supported on Linux.
+///
+/// [`Command`]: crate::command::Command
+/// [`ProcessInput::SpawnOut`]: crate::io::ProcessInput::SpawnOut
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Pty {
+    /// Number of rows of the terminal window.
+    pub rows: u16,
+    /// Number of columns of the terminal window.
+    pub cols: u16,
+}
+
+impl Default for Pty {
+    fn default() -> Self {
+        Self { rows: 24, cols: 80 }
+    }
+}
+
+impl Pty {
+    /// Creates a new pseudo-terminal configuration with the given
+    /// window size.
+    pub fn new(rows: u16, cols: u16) -> Self {
+        Self { rows, cols }
+    }
+}
@@ -0,0 +1,802 @@
+//! Conversions of I/O-free builders into [`std::process`] types,
+//! shared by the std-based runtimes.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::{File, OpenOptions},
+    io, mem,
+    process::{Command as StdCommand, Stdio as StdStdio},
+};
+
+#[cfg(unix)]
+use crate::limit::{LimitError, Resource, Rlimit};
+use crate::{command::Command, fd::ExtraFd, pty::Pty, stdio::Stdio};
+
+/// Converts a [`Command`] builder into a [`std::process::Command`].
+///
+/// The conversion opens the file-backed [`Stdio`] of the builder, so
+/// it fails for the same reasons as [`Command::into_std_command`].
+impl TryFrom<Command> for StdCommand {
+    type Error = io::Error;
+
+    fn try_from(builder: Command) -> io::Result<Self> {
+        builder.into_std_command()
+    }
+}
+
+impl Command {
+    /// Converts the builder into a [`std::process::Command`], opening
+    /// its file-backed [`Stdio`].
+    ///
+    /// Fails if a file-backed [`Stdio`] cannot be opened, or if the
+    /// command has extra file descriptors or runs in PTY mode, which
+    /// can only be set up by runtimes.
+    pub fn into_std_command(self) -> io::Result<StdCommand> {
+        if !self.fds.is_empty() || self.pty.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot convert a command with extra fds or in PTY mode",
+            ));
+        }
+
+        let mut command = StdCommand::new(&*self.get_program());
+
+        if let Some(args) = self.get_args() {
+            for arg in args {
+                command.arg(&*arg);
+            }
+        }
+
+        #[cfg(unix)]
+        set_unix_attrs(&mut command, &self)?;
+
+        if let Some(envs) = self.envs {
+            for (key, val) in envs {
+                command.env(key, val);
+            }
+        }
+
+        if let Some(dir) = self.current_dir {
+            command.current_dir(&dir);
+        }
+
+        let stdio = ChildStdio::open(&mut command, self.stdin, self.stdout, self.stderr)?;
+        stdio.apply(&mut command);
+
+        Ok(command)
+    }
+}
+
+/// Converts a [`Command`] into a [`std::process::Command`], setting
+/// up its extra file descriptors and its pseudo-terminal.
+#[cfg(any(feature = "std", feature = "tokio"))]
+pub(crate) fn command(cmd: Command) -> io::Result<(StdCommand, ChildSetup)> {
+    let (mut command, stdio, setup) = command_parts(cmd)?;
+    stdio.apply(&mut command);
+    Ok((command, setup))
+}
+
+/// Converts a [`Command`] the same way as [`command`], but returns
+/// its standard streams instead of setting them.
+///
+/// Used by the runtimes wrapping the [`std::process::Command`] into
+/// a type that does not keep the streams already set on it.
+pub(crate) fn command_parts(mut cmd: Command) -> io::Result<(StdCommand, ChildStdio, ChildSetup)> {
+    let fds = mem::take(&mut cmd.fds);
+    let pty = cmd.pty.take();
+
+    // the PTY mode runs the child in a new session, whose terminal
+    // is the pseudo-terminal
+    #[cfg(unix)]
+    if pty.is_some() {
+        if cmd.process_group.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot run a command in PTY mode in a process group",
+            ));
+        }
+
+        cmd.setsid = true;
+    }
+
+    let unset = [
+        cmd.stdin.is_none(),
+        cmd.stdout.is_none(),
+        cmd.stderr.is_none(),
+    ];
+
+    let (stdin, stdout, stderr) = (cmd.stdin.take(), cmd.stdout.take(), cmd.stderr.take());
+
+    #[cfg(unix)]
+    let (rlimits, nice) = (mem::take(&mut cmd.rlimits), cmd.nice.take());
+
+    let mut command = cmd.into_std_command()?;
+    let mut stdio = ChildStdio::open(&mut command, stdin, stdout, stderr)?;
+    let mut setup = extra_fds(&mut command, fds)?;
+
+    if let Some(pty) = pty {
+        let (master, slave) = attach_pty(&mut command, &mut stdio, &pty, unset)?;
+        setup.pty = Some(master);
+        setup.child_ends.push(slave);
+    }
+
+    #[cfg(unix)]
+    if !rlimits.is_empty() || nice.is_some() {
+        let (reader, writer) = io::pipe()?;
+        let report = LimitReport {
+            limits: rlimits.into_iter().collect(),
+            nice,
+            reader,
+            writer,
+        };
+        set_limits(&mut command, &report.limits, nice, Some(&report.writer));
+        setup.limits = Some(report);
+    }
+
+    Ok((command, stdio, setup))
+}
+
+/// Fails if the given command captures extra file descriptors.
+///
+/// Used by requests that do not capture output.
+pub(crate) fn reject_captured_fds(cmd: &Command) -> io::Result<()> {
+    if cmd.fds.values().any(|fd| *fd == ExtraFd::Capture) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot capture extra fds of a command without output capture",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Standard streams of a child process, opened from their [`Stdio`]
+/// configuration.
+///
+/// Streams left unset are inherited from the parent, unless the
+/// spawning function defaults them otherwise.
+#[derive(Debug, Default)]
+pub(crate) struct ChildStdio {
+    pub stdin: Option<StdStdio>,
+    pub stdout: Option<StdStdio>,
+    pub stderr: Option<StdStdio>,
+}
+
+impl ChildStdio {
+    /// Opens the given stream configurations.
+    ///
+    /// On Unix, stderr redirected to stdout gets duplicated by the
+    /// child itself, which is set up on the given command.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn open(
+        command: &mut StdCommand,
+        stdin: Option<Stdio>,
+        stdout: Option<Stdio>,
+        stderr: Option<Stdio>,
+    ) -> io::Result<Self> {
+        let mut stdio = Self::default();
+
+        if let Some(stdin) = stdin {
+            stdio.stdin = Some(open(stdin, "stdin")?);
+        }
+
+        #[cfg(not(unix))]
+        let stdout_cfg = stdout.clone();
+
+        if let Some(stdout) = stdout {
+            stdio.stdout = Some(open(stdout, "stdout")?);
+        }
+
+        match stderr {
+            #[cfg(unix)]
+            Some(Stdio::Stdout) => merge_stderr(command),
+            #[cfg(not(unix))]
+            Some(Stdio::Stdout) => stdio.stderr = Some(merge_stderr(stdout_cfg)?),
+            Some(stderr) => stdio.stderr = Some(open(stderr, "stderr")?),
+            None => (),
+        }
+
+        Ok(stdio)
+    }
+
+    /// Sets the opened streams on the given command.
+    pub(crate) fn apply(self, command: &mut StdCommand) {
+        if let Some(stdin) = self.stdin {
+            command.stdin(stdin);
+        }
+
+        if let Some(stdout) = self.stdout {
+            command.stdout(stdout);
+        }
+
+        if let Some(stderr) = self.stderr {
+            command.stderr(stderr);
+        }
+    }
+}
+
+/// Parent side of the setup of a child process, done by [`command`].
+#[derive(Debug, Default)]
+pub(crate) struct ChildSetup {
+    /// Write ends of the [`ExtraFd::Input`] pipes, with the bytes to
+    /// write.
+    pub inputs: Vec<(File, Vec<u8>)>,
+    /// Read ends of the [`ExtraFd::Capture`] pipes, by child
+    /// descriptor number.
+    pub captures: Vec<(i32, File)>,
+    /// Child ends of the descriptors, to be closed once the child got
+    /// spawned so that pipes get closed when the child exits.
+    pub child_ends: Vec<File>,
+    /// Master side of the pseudo-terminal, in PTY mode.
+    pub pty: Option<PtyMaster>,
+    /// Report of the limit the child failed to apply, if any.
+    #[cfg(unix)]
+    pub limits: Option<LimitReport>,
+}
+
+impl ChildSetup {
+    /// Enriches the given error of a failed spawn, for example with
+    /// the limit the child failed to apply.
+    pub fn spawn_error(&mut self, err: io::Error) -> io::Error {
+        #[cfg(unix)]
+        if let Some(limits) = self.limits.take() {
+            return limits.error(err);
+        }
+
+        err
+    }
+}
+
+/// Master side of a pseudo-terminal.
+///
+/// Reading from the master side fails with `EIO` once all the slave
+/// sides got closed, which is reported as EOF.
+#[derive(Debug)]
+pub(crate) struct PtyMaster(pub File);
+
+impl io::Read for PtyMaster {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            #[cfg(target_os = "linux")]
+            Err(err) if err.raw_os_error() == Some(libc::EIO) => Ok(0),
+            res => res,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsRawFd for PtyMaster {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsFd for PtyMaster {
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// Allocates a new pseudo-terminal, and attaches to its slave side
+/// the given standard streams flagged as unset (stdin, stdout,
+/// stderr).
+///
+/// The slave side also becomes the controlling terminal of the child,
+/// which needs to run in a new session.
+#[cfg(target_os = "linux")]
+fn attach_pty(
+    command: &mut StdCommand,
+    stdio: &mut ChildStdio,
+    pty: &Pty,
+    unset: [bool; 3],
+) -> io::Result<(PtyMaster, File)> {
+    use std::{
+        ffi::CStr,
+        os::{
+            fd::{AsRawFd, FromRawFd},
+            unix::{fs::OpenOptionsExt, process::CommandExt},
+        },
+    };
+
+    // SAFETY: the returned descriptor is checked, then owned by the
+    // master file.
+    let master = unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        File::from_raw_fd(fd)
+    };
+
+    let mut path = [0; 64];
+
+    // SAFETY: the master descriptor is valid, and the buffer length
+    // matches the buffer.
+    unsafe {
+        let fd = master.as_raw_fd();
+
+        if libc::grantpt(fd) == -1 || libc::unlockpt(fd) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let err = libc::ptsname_r(fd, path.as_mut_ptr(), path.len());
+
+        if err != 0 {
+            return Err(io::Error::from_raw_os_error(err));
+        }
+
+        let size = libc::winsize {
+            ws_row: pty.rows,
+            ws_col: pty.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        if libc::ioctl(fd, libc::TIOCSWINSZ, &size) == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    // SAFETY: ptsname_r succeeded, so the buffer contains a
+    // nul-terminated path.
+    let path = unsafe { CStr::from_ptr(path.as_ptr()) };
+
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(&*path.to_string_lossy())?;
+
+    let [stdin, stdout, stderr] = unset;
+
+    if stdin {
+        stdio.stdin = Some(slave.try_clone()?.into());
+    }
+
+    if stdout {
+        stdio.stdout = Some(slave.try_clone()?.into());
+    }
+
+    if stderr {
+        stdio.stderr = Some(slave.try_clone()?.into());
+    }
+
+    let tty = slave.as_raw_fd();
+
+    // SAFETY: the closure only calls ioctl, which is
+    // async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            if libc::ioctl(tty, libc::TIOCSCTTY, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok((PtyMaster(master), slave))
+}
+
+/// Allocates a new pseudo-terminal.
+///
+/// Pseudo-terminals are not supported on this platform.
+#[cfg(not(target_os = "linux"))]
+fn attach_pty(
+    _command: &mut StdCommand,
+    _stdio: &mut ChildStdio,
+    _pty: &Pty,
+    _unset: [bool; 3],
+) -> io::Result<(PtyMaster, File)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "PTY mode is not supported on this platform",
+    ))
+}
+
+/// Sets up the given extra file descriptors.
+///
+/// Pipes get created and files get opened in the parent, then the
+/// child duplicates them onto their descriptor number before
+/// executing the program.
+#[cfg(unix)]
+fn extra_fds(command: &mut StdCommand, fds: BTreeMap<i32, ExtraFd>) -> io::Result<ChildSetup> {
+    use std::os::{
+        fd::{AsRawFd, OwnedFd},
+        unix::process::CommandExt,
+    };
+
+    let mut extra = ChildSetup::default();
+
+    if fds.is_empty() {
+        return Ok(extra);
+    }
+
+    let mut mapping = Vec::with_capacity(fds.len());
+
+    for (fd, cfg) in fds {
+        if fd <= libc::STDERR_FILENO {
+            let msg = format!("cannot configure fd {fd} as an extra fd");
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+
+        let name = format!("fd {fd}");
+
+        let child_end = match cfg {
+            ExtraFd::Input(bytes) => {
+                let (reader, writer) = io::pipe()?;
+                extra.inputs.push((OwnedFd::from(writer).into(), bytes));
+                OwnedFd::from(reader).into()
+            }
+            ExtraFd::Capture => {
+                let (reader, writer) = io::pipe()?;
+                extra.captures.push((fd, OwnedFd::from(reader).into()));
+                OwnedFd::from(writer).into()
+            }
+            ExtraFd::ReadFile(path) => open_file(File::open(&path), &name, &path)?,
+            ExtraFd::WriteFile(path) => open_file(File::create(&path), &name, &path)?,
+            ExtraFd::AppendFile(path) => {
+                let file = OpenOptions::new().create(true).append(true).open(&path);
+                open_file(file, &name, &path)?
+            }
+        };
+
+        mapping.push((child_end.as_raw_fd(), fd));
+        extra.child_ends.push(child_end);
+    }
+
+    let floor = mapping
+        .iter()
+        .map(|(_, fd)| *fd)
+        .max()
+        .unwrap_or_default()
+        .saturating_add(1);
+
+    // SAFETY: the closure only calls fcntl and dup2, which are
+    // async-signal-safe, and does not allocate.
+    unsafe {
+        command.pre_exec(move || {
+            // moves the sources above all the targets first, so that
+            // duplicating a source onto its target cannot overwrite
+            // another source
+            for (src, _) in mapping.iter_mut() {
+                let tmp = libc::fcntl(*src, libc::F_DUPFD_CLOEXEC, floor);
+
+                if tmp == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                *src = tmp;
+            }
+
+            for (src, fd) in &mapping {
+                if libc::dup2(*src, *fd) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok(extra)
+}
+
+/// Sets up the given extra file descriptors.
+///
+/// Extra file descriptors are not supported on this platform.
+#[cfg(not(unix))]
+fn extra_fds(_command: &mut StdCommand, fds: BTreeMap<i32, ExtraFd>) -> io::Result<ChildSetup> {
+    if fds.is_empty() {
+        return Ok(ChildSetup::default());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "extra fds are not supported on this platform",
+    ))
+}
+
+/// Sets the Unix process attributes of the given builder.
+///
+/// Fails if both a process group and a new session are requested,
+/// or if the whole process tree needs to be terminated while the
+/// child joins an existing process group.
+#[cfg(unix)]
+fn set_unix_attrs(command: &mut StdCommand, builder: &Command) -> io::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    if builder.setsid && builder.process_group.is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot run a command in a new session and in a process group",
+        ));
+    }
+
+    // the process tree is signaled through the process group led by
+    // the child, which an existing group is not
+    if builder.termination.tree && builder.process_group.is_some_and(|pgroup| pgroup != 0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot kill the process tree of a command joining an existing process group",
+        ));
+    }
+
+    if let Some(pgroup) = builder.process_group {
+        command.process_group(pgroup);
+    } else if builder.termination.tree && !builder.setsid {
+        // a new session comes with its own process group
+        command.process_group(0);
+    }
+
+    match builder.groups.clone() {
+        // supplementary groups need to be set before the user ID,
+        // which [`CommandExt`] does not support yet
+        Some(groups) => {
+            let (uid, gid) = (builder.uid, builder.gid);
+
+            // SAFETY: the closure only calls setgroups, setgid and
+            // setuid, which are async-signal-safe, and does not
+            // allocate.
+            unsafe {
+                command.pre_exec(move || {
+                    if libc::setgroups(groups.len() as _, groups.as_ptr()) == -1 {
+                        return Err(io::Error::last_os_error());
+                    }
+
+                    if let Some(gid) = gid {
+                        if libc::setgid(gid) == -1 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+
+                    if let Some(uid) = uid {
+                        if libc::setuid(uid) == -1 {
+                            return Err(io::Error::last_os_error());
+                        }
+                    }
+
+                    Ok(())
+                });
+            }
+        }
+        None => {
+            if let Some(gid) = builder.gid {
+                command.gid(gid);
+            }
+
+            if let Some(uid) = builder.uid {
+                command.uid(uid);
+            }
+        }
+    }
+
+    if builder.setsid {
+        // SAFETY: the closure only calls setsid, which is
+        // async-signal-safe.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+    }
+
+    if let Some(umask) = builder.umask {
+        // SAFETY: the closure only calls umask, which is
+        // async-signal-safe.
+        unsafe {
+            command.pre_exec(move || {
+                libc::umask(umask as libc::mode_t);
+                Ok(())
+            });
+        }
+    }
+
+    let limits: Vec<_> = builder.rlimits.iter().map(|(r, l)| (*r, *l)).collect();
+
+    if !limits.is_empty() || builder.nice.is_some() {
+        set_limits(command, &limits, builder.nice, None);
+    }
+
+    Ok(())
+}
+
+/// Sets the resource limits and the nice value of the child.
+///
+/// A limit that fails to apply gets reported to the given writer, as
+/// its index in `limits` (or [`u8::MAX`] for the nice value) followed
+/// by the OS error code.
+#[cfg(unix)]
+fn set_limits(
+    command: &mut StdCommand,
+    limits: &[(Resource, Rlimit)],
+    nice: Option<i32>,
+    report: Option<&io::PipeWriter>,
+) {
+    use std::os::{fd::AsRawFd, unix::process::CommandExt};
+
+    let rlim = |limit: u64| match limit {
+        Rlimit::INFINITY => libc::RLIM_INFINITY,
+        limit => limit as libc::rlim_t,
+    };
+
+    let limits: Vec<_> = limits
+        .iter()
+        .map(|(resource, limit)| {
+            let limit = libc::rlimit {
+                rlim_cur: rlim(limit.soft),
+                rlim_max: rlim(limit.hard),
+            };
+
+            (*resource, limit)
+        })
+        .collect();
+
+    let report = report.map(AsRawFd::as_raw_fd);
+
+    let fail = move |index: u8| {
+        let err = io::Error::last_os_error();
+
+        if let Some(fd) = report {
+            let mut buf = [index; 5];
+            let code = err.raw_os_error().unwrap_or_default();
+            buf[1..].copy_from_slice(&code.to_ne_bytes());
+
+            // SAFETY: the buffer is valid for its whole length.
+            unsafe { libc::write(fd, buf.as_ptr().cast(), buf.len()) };
+        }
+
+        err
+    };
+
+    // SAFETY: the closure only calls setrlimit, setpriority and
+    // write, which are async-signal-safe, and does not allocate.
+    unsafe {
+        command.pre_exec(move || {
+            for (index, (resource, limit)) in limits.iter().enumerate() {
+                let resource = match resource {
+                    Resource::AddressSpace => libc::RLIMIT_AS,
+                    Resource::Cpu => libc::RLIMIT_CPU,
+                    Resource::OpenFiles => libc::RLIMIT_NOFILE,
+                    Resource::CoreSize => libc::RLIMIT_CORE,
+                    Resource::FileSize => libc::RLIMIT_FSIZE,
+                };
+
+                if libc::setrlimit(resource, limit) == -1 {
+                    return Err(fail(index as u8));
+                }
+            }
+
+            if let Some(nice) = nice {
+                if libc::setpriority(libc::PRIO_PROCESS, 0, nice) == -1 {
+                    return Err(fail(u8::MAX));
+                }
+            }
+
+            Ok(())
+        });
+    }
+}
+
+/// Report of the limit a child failed to apply, written by the child
+/// before failing to spawn.
+#[cfg(unix)]
+#[derive(Debug)]
+pub(crate) struct LimitReport {
+    limits: Vec<(Resource, Rlimit)>,
+    nice: Option<i32>,
+    reader: io::PipeReader,
+    writer: io::PipeWriter,
+}
+
+#[cfg(unix)]
+impl LimitReport {
+    /// Wraps the given spawn error into a [`LimitError`] if the child
+    /// reported a limit it failed to apply.
+    fn error(mut self, err: io::Error) -> io::Error {
+        use std::io::Read;
+
+        // closes the parent copy of the writer, so that reading stops
+        // at the end of the report
+        drop(self.writer);
+
+        let mut buf = Vec::new();
+
+        if self.reader.read_to_end(&mut buf).is_err() || buf.len() != 5 {
+            return err;
+        }
+
+        let code = i32::from_ne_bytes([buf[1], buf[2], buf[3], buf[4]]);
+        let source = io::Error::from_raw_os_error(code);
+
+        let limit_err = match (buf[0], self.nice) {
+            (u8::MAX, Some(nice)) => LimitError::Nice { nice, err: source },
+            (index, _) => match self.limits.get(index as usize) {
+                Some((resource, limit)) => LimitError::Rlimit {
+                    resource: *resource,
+                    limit: *limit,
+                    err: source,
+                },
+                None => return err,
+            },
+        };
+
+        io::Error::new(err.kind(), limit_err)
+    }
+}
+
+/// Redirects stderr to stdout (`2>&1`).
+///
+/// The redirection happens in the child, after its stdio got set up,
+/// so stderr follows stdout wherever it goes, including pipes set up
+/// by runtimes after the conversion.
+#[cfg(unix)]
+fn merge_stderr(command: &mut StdCommand) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: the closure only calls dup2, which is
+    // async-signal-safe.
+    unsafe {
+        command.pre_exec(|| {
+            if libc::dup2(libc::STDOUT_FILENO, libc::STDERR_FILENO) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        });
+    }
+}
+
+/// Redirects stderr to stdout (`2>&1`).
+///
+/// Only inherited and null stdout are supported on this platform.
+#[cfg(not(unix))]
+fn merge_stderr(stdout: Option<Stdio>) -> io::Result<StdStdio> {
+    match stdout {
+        None | Some(Stdio::Inherit) => Ok(io::stdout().into()),
+        Some(Stdio::Null) => Ok(StdStdio::null()),
+        Some(_) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "cannot redirect stderr to non-inherited stdout on this platform",
+        )),
+    }
+}
+
+/// Converts a [`Stdio`] into a [`std::process::Stdio`], opening the
+/// file of file-backed variants.
+pub(crate) fn open(stdio: Stdio, stream: &str) -> io::Result<StdStdio> {
+    let file = match stdio {
+        Stdio::Inherit => return Ok(StdStdio::inherit()),
+        Stdio::Null => return Ok(StdStdio::null()),
+        Stdio::Piped => return Ok(StdStdio::piped()),
+        Stdio::Stdout => {
+            let msg = format!("cannot redirect {stream} to stdout");
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        Stdio::ReadFile(path) => open_file(File::open(&path), stream, &path)?,
+        Stdio::WriteFile(path) => open_file(File::create(&path), stream, &path)?,
+        Stdio::AppendFile(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(&path);
+            open_file(file, stream, &path)?
+        }
+    };
+
+    Ok(file.into())
+}
+
+/// Mentions the stream name and the file path in the error of a file
+/// that cannot be opened, keeping its original kind.
+fn open_file(file: io::Result<File>, stream: impl fmt::Display, path: &str) -> io::Result<File> {
+    file.map_err(|err| {
+        let msg = format!("cannot open {stream} file {path}: {err}");
+        io::Error::new(err.kind(), msg)
+    })
+}
@@ -0,0 +1,196 @@
+//! Human-readable descriptions of process inputs, shared by the
+//! runtimes matching inputs against expected ones.
+
+use alloc::{format, string::String, vec::Vec};
+use core::{cmp, fmt::Write};
+
+use crate::{command::Command, io::ProcessInput};
+
+/// Kind of [`ProcessInput`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Kind {
+    Spawn,
+    SpawnOut,
+    SpawnIn,
+    SpawnPipeline,
+}
+
+/// Describes the given input.
+pub(crate) fn input(input: &ProcessInput) -> String {
+    match input {
+        ProcessInput::Spawn { cmd } => commands(Kind::Spawn, core::slice::from_ref(cmd), None),
+        ProcessInput::SpawnOut { cmd } => {
+            commands(Kind::SpawnOut, core::slice::from_ref(cmd), None)
+        }
+        ProcessInput::SpawnIn { cmd, stdin } => {
+            commands(Kind::SpawnIn, core::slice::from_ref(cmd), Some(stdin))
+        }
+        ProcessInput::SpawnPipeline { cmds } => commands(Kind::SpawnPipeline, cmds, None),
+    }
+}
+
+/// Describes an input of the given kind, one matched part per line:
+/// the command lines, their explicitly set environment variables,
+/// then stdin.
+///
+/// Words that are not made of safe characters only are rendered by
+/// [`escape`], so that each part stays on its own line.
+pub(crate) fn commands(kind: Kind, cmds: &[Command], stdin: Option<&[u8]>) -> String {
+    let mut description = format!("{kind:?}");
+
+    for cmd in cmds {
+        description.push_str("\n  command: ");
+        word(&mut description, &cmd.get_program());
+
+        for arg in cmd.get_args().into_iter().flatten() {
+            description.push(' ');
+            word(&mut description, &arg);
+        }
+
+        for (key, val) in cmd.envs.iter().flatten() {
+            description.push_str("\n    env: ");
+            word(&mut description, key);
+            description.push('=');
+            word(&mut description, val);
+        }
+    }
+
+    if let Some(stdin) = stdin {
+        let _ = write!(description, "\n  stdin: {}", escape(stdin));
+    }
+
+    description
+}
+
+/// Diffs the given descriptions, line by line.
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    let mut diff = Vec::new();
+
+    for i in 0..cmp::max(expected.len(), actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(expected), Some(actual)) if expected == actual => {
+                diff.push(format!("  {expected}"));
+            }
+            (expected, actual) => {
+                if let Some(expected) = expected {
+                    diff.push(format!("- {expected}"));
+                }
+
+                if let Some(actual) = actual {
+                    diff.push(format!("+ {actual}"));
+                }
+            }
+        }
+    }
+
+    diff.join("\n")
+}
+
+/// Appends the given word to the description, as is if it only
+/// contains safe characters, or rendered by [`escape`] otherwise.
+fn word(description: &mut String, word: &str) {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./:@%+,".contains(c);
+
+    if !word.is_empty() && word.chars().all(is_safe) {
+        description.push_str(word);
+    } else {
+        description.push_str(&escape(word.as_bytes()));
+    }
+}
+
+/// Renders the given bytes as a double-quoted string on a single
+/// line.
+///
+/// Quotes, backslashes and control characters are escaped the way
+/// Rust string literals escape them, and bytes that are not valid
+/// UTF-8 are escaped as `\xNN`, so that the rendering can be parsed
+/// back with [`unescape`].
+pub(crate) fn escape(bytes: &[u8]) -> String {
+    let mut escaped = String::from("\"");
+
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                c if c.is_control() => {
+                    let _ = write!(escaped, "\\u{{{:x}}}", c as u32);
+                }
+                c => escaped.push(c),
+            }
+        }
+
+        for byte in chunk.invalid() {
+            let _ = write!(escaped, "\\x{byte:02x}");
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+/// Parses bytes rendered by [`escape`].
+///
+/// Returns `None` if the given string is not a valid rendering.
+#[cfg_attr(not(feature = "replay"), allow(dead_code))]
+pub(crate) fn unescape(escaped: &str) -> Option<Vec<u8>> {
+    let escaped = escaped.strip_prefix('"')?.strip_suffix('"')?;
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+
+        match chars.next()? {
+            '"' => bytes.push(b'"'),
+            '\\' => bytes.push(b'\\'),
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            't' => bytes.push(b'\t'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+            }
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let (hex, rest) = rest.split_once('}')?;
+                let c = char::from_u32(u32::from_str_radix(hex, 16).ok()?)?;
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                chars = rest.chars();
+            }
+            _ => return None,
+        }
+    }
+
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, unescape};
+
+    #[test]
+    fn escaping() {
+        let bytes = b"say \"hi\"\\\n\t\x1b[0m \xff\xfe caf\xc3\xa9";
+        let escaped = escape(bytes);
+
+        assert_eq!(
+            "\"say \\\"hi\\\"\\\\\\n\\t\\u{1b}[0m \\xff\\xfe café\"",
+            escaped,
+        );
+        assert_eq!(Some(bytes.to_vec()), unescape(&escaped));
+
+        assert_eq!(None, unescape("\"\\q\""));
+        assert_eq!(None, unescape("unquoted"));
+    }
+}
@@ -0,0 +1,719 @@
+//! Executor-independent driving of spawned children, shared by the
+//! async runtimes.
+//!
+//! Reading the output of a child and sending the signals of its
+//! termination sequence do not depend on the executor. The async
+//! runtimes only provide their glue through the [`Runtime`] trait:
+//! commands, child handles, readers, timers and background tasks.
+
+use std::{
+    collections::BTreeMap,
+    future::{Future, poll_fn},
+    io,
+    marker::PhantomData,
+    pin::pin,
+    process::ExitStatus as StdExitStatus,
+    slice,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use super::convert::{self, ChildSetup, PtyMaster};
+#[cfg(unix)]
+use super::kill;
+#[cfg(target_os = "linux")]
+use super::pidfd::Pidfd;
+use crate::{
+    capture::{Capture, Recorder, Recording, Stream},
+    command::Command,
+    io::ProcessOutput,
+    status::ExitStatus,
+    stdio::Stdio,
+    termination::{OnDrop, Signal, Termination, Terminator},
+};
+
+/// Executor glue of an async runtime.
+pub(crate) trait Runtime {
+    /// Command of the executor, about to be spawned.
+    type Command;
+    /// Handle of a spawned child.
+    type Child;
+    /// Writer of the piped stdin of a child.
+    type Stdin;
+    /// Reader of the piped stdout of a child.
+    type Stdout: Reader;
+    /// Reader of the piped stderr of a child.
+    type Stderr: Reader;
+    /// Reader of the master side of a pseudo-terminal.
+    type Pty: Reader;
+    /// Background tasks serving the extra file descriptors of a
+    /// child.
+    type FdTasks;
+
+    /// Converts the given builder into a command of the executor,
+    /// setting up its extra file descriptors.
+    fn command(cmd: Command) -> io::Result<(Self::Command, ChildSetup)>;
+
+    /// Spawns the given command.
+    fn spawn(command: &mut Self::Command) -> io::Result<Self::Child>;
+
+    /// Pipes the stdout of the given command, to be fed to the next
+    /// stage of a pipeline.
+    fn pipe_stdout(command: &mut Self::Command);
+
+    /// Hands the piped stdout of the given child over to the stdin
+    /// of the given command.
+    async fn pipe_into(child: &mut Self::Child, command: &mut Self::Command) -> io::Result<()>;
+
+    /// Takes the piped stdin of the given child.
+    fn stdin(child: &mut Self::Child) -> Option<Self::Stdin>;
+
+    /// Writes the given bytes to the given stdin, then closes it.
+    async fn feed(stdin: Self::Stdin, bytes: &[u8]) -> io::Result<()>;
+
+    /// Closes the child ends of the given extra file descriptors,
+    /// then serves them from background tasks.
+    fn start(setup: ChildSetup) -> io::Result<Self::FdTasks>;
+
+    /// Waits for the given tasks to finish, returning the captured
+    /// bytes by descriptor number.
+    async fn join(tasks: Self::FdTasks) -> io::Result<BTreeMap<i32, Vec<u8>>>;
+
+    /// Returns the ID of the given child, unless it got reaped.
+    fn id(child: &Self::Child) -> Option<u32>;
+
+    /// Closes the stdin of the given child, then takes its piped
+    /// stdout and stderr.
+    fn pipes(child: &mut Self::Child) -> (Option<Self::Stdout>, Option<Self::Stderr>);
+
+    /// Registers the given master side of a pseudo-terminal in the
+    /// reactor.
+    fn pty(master: PtyMaster) -> io::Result<Self::Pty>;
+
+    /// Waits for the exit of the given child, then reaps it.
+    async fn wait(child: &mut Self::Child) -> io::Result<StdExitStatus>;
+
+    /// Waits for the process of the given pidfd to exit, without
+    /// reaping it.
+    #[cfg(target_os = "linux")]
+    async fn exited(pidfd: &Pidfd) -> io::Result<()>;
+
+    /// Kills the given child.
+    #[cfg(not(unix))]
+    fn kill(child: &mut Self::Child) -> io::Result<()>;
+
+    /// Sleeps for the given duration.
+    async fn sleep(duration: Duration);
+
+    /// Forwards the given chunk to the matching stream of the parent
+    /// process.
+    async fn tee(stream: Stream, chunk: &[u8]) -> io::Result<()>;
+
+    /// Runs the given function in the background, once the given
+    /// delay elapsed.
+    ///
+    /// This is called from destructors, possibly outside of any
+    /// executor.
+    #[cfg(unix)]
+    fn defer(delay: Duration, f: impl FnOnce() + Send + 'static);
+}
+
+/// Non-blocking reader of a stream of a child.
+///
+/// Abstracts over the `AsyncRead` traits of the executors.
+pub(crate) trait Reader: Unpin {
+    /// Polls a read into `buf`, returning the number of bytes read.
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>>;
+}
+
+/// Reader of the master side of a pseudo-terminal.
+///
+/// Pseudo-terminals are not supported on this platform.
+#[cfg(not(unix))]
+pub(crate) enum NoPty {}
+
+#[cfg(not(unix))]
+impl NoPty {
+    pub(crate) fn new(_master: PtyMaster) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "PTY mode is not supported on this platform",
+        ))
+    }
+}
+
+#[cfg(not(unix))]
+impl Reader for NoPty {
+    fn poll_read(&mut self, _cx: &mut Context<'_>, _buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        match *self {}
+    }
+}
+
+/// Spawns a process and waits for its exit status.
+pub(crate) async fn spawn<R: Runtime>(cmd: Command) -> io::Result<ProcessOutput> {
+    convert::reject_captured_fds(&cmd)?;
+    let mut watchdog = Watchdog::<R>::new(&cmd);
+    let tree = cmd.termination.tree;
+    let (mut command, mut setup) = R::command(cmd)?;
+
+    let mut child = R::spawn(&mut command).map_err(|err| setup.spawn_error(err))?;
+    watchdog.watch(&child, tree)?;
+    // closes the parent copies of the child stdio, like the terminal
+    drop(command);
+    let fds = R::start(setup)?;
+    let status = watchdog.wait(slice::from_mut(&mut child)).await?;
+    R::join(fds).await?;
+
+    Ok(ProcessOutput::Spawned {
+        status: ExitStatus::new(status.code()),
+    })
+}
+
+/// Spawns a process, captures its stdout and stderr, and waits for
+/// its exit status.
+pub(crate) async fn spawn_out<R: Runtime>(mut cmd: Command) -> io::Result<ProcessOutput> {
+    if cmd.pty.is_none() {
+        cmd.stdout.get_or_insert(Stdio::Piped);
+        cmd.stderr.get_or_insert(Stdio::Piped);
+    }
+
+    let capture = cmd.capture.clone();
+    let mut watchdog = Watchdog::<R>::new(&cmd);
+    let tree = cmd.termination.tree;
+    let (mut command, mut setup) = R::command(cmd)?;
+
+    let mut child = R::spawn(&mut command).map_err(|err| setup.spawn_error(err))?;
+    watchdog.watch(&child, tree)?;
+    // closes the parent copies of the child stdio, like the terminal
+    drop(command);
+    let terminal = setup.pty.take();
+    let fds = R::start(setup)?;
+    let Recording {
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+    } = read_output(
+        slice::from_mut(&mut child),
+        terminal,
+        &capture,
+        &mut watchdog,
+    )
+    .await?;
+    let status = watchdog.wait(slice::from_mut(&mut child)).await?;
+    let fds = R::join(fds).await?;
+
+    Ok(ProcessOutput::SpawnedOut {
+        status: ExitStatus::new(status.code()),
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+        fds,
+    })
+}
+
+/// Spawns a process, feeds bytes to its stdin, and waits for its exit
+/// status.
+pub(crate) async fn spawn_in<R: Runtime>(
+    mut cmd: Command,
+    stdin: Vec<u8>,
+) -> io::Result<ProcessOutput> {
+    if *cmd.stdin.get_or_insert(Stdio::Piped) != Stdio::Piped {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot feed stdin of a command with non-piped stdin",
+        ));
+    }
+
+    convert::reject_captured_fds(&cmd)?;
+    let mut watchdog = Watchdog::<R>::new(&cmd);
+    let tree = cmd.termination.tree;
+    let (mut command, mut setup) = R::command(cmd)?;
+
+    let mut child = R::spawn(&mut command).map_err(|err| setup.spawn_error(err))?;
+    watchdog.watch(&child, tree)?;
+    // closes the parent copies of the child stdio, like the terminal
+    drop(command);
+    let fds = R::start(setup)?;
+
+    let handle = R::stdin(&mut child);
+    let mut fed = pin!(async {
+        match handle {
+            Some(handle) => R::feed(handle, &stdin).await,
+            None => Ok(()),
+        }
+    });
+    let mut fed_done = false;
+    let mut exited = pin!(watchdog.wait(slice::from_mut(&mut child)));
+
+    // stdin is fed while waiting for the exit, so that a process that
+    // does not read it still gets terminated at its deadline
+    let status = poll_fn(|cx| {
+        if !fed_done {
+            if let Poll::Ready(res) = fed.as_mut().poll(cx) {
+                match res {
+                    // the process may exit without reading its whole
+                    // stdin
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => (),
+                    Err(err) => return Poll::Ready(Err(err)),
+                    Ok(()) => (),
+                }
+
+                fed_done = true;
+            }
+        }
+
+        exited.as_mut().poll(cx)
+    })
+    .await?;
+
+    R::join(fds).await?;
+
+    Ok(ProcessOutput::SpawnedIn {
+        status: ExitStatus::new(status.code()),
+    })
+}
+
+/// Spawns a pipeline of processes, piping each process's stdout into
+/// the next process's stdin.
+///
+/// The whole pipeline is watched, with the timeout and the
+/// termination configuration of the last process.
+pub(crate) async fn spawn_pipeline<R: Runtime>(cmds: Vec<Command>) -> io::Result<ProcessOutput> {
+    let n = cmds.len();
+    if n == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "empty pipeline",
+        ));
+    }
+
+    let mut children: Vec<R::Child> = Vec::new();
+    let mut tasks = Vec::new();
+    let mut terminal = None;
+    let mut capture = Capture::default();
+    let mut watchdog = Watchdog::<R>::new(&cmds[n - 1]);
+    let termination = cmds[n - 1].termination;
+
+    for (i, mut cmd) in cmds.into_iter().enumerate() {
+        // the termination sequence of the last process applies to
+        // every process, on cancellation too
+        cmd.termination.grace = termination.grace;
+        cmd.termination.on_drop = termination.on_drop;
+
+        let is_last = i == n - 1;
+        if is_last {
+            if cmd.pty.is_none() {
+                cmd.stdout.get_or_insert(Stdio::Piped);
+                cmd.stderr.get_or_insert(Stdio::Piped);
+            }
+            capture = cmd.capture.clone();
+        } else {
+            convert::reject_captured_fds(&cmd)?;
+        }
+        let tree = cmd.termination.tree;
+        let (mut command, mut setup) = R::command(cmd)?;
+
+        if let Some(prev) = children.last_mut() {
+            R::pipe_into(prev, &mut command).await?;
+        }
+
+        if !is_last {
+            R::pipe_stdout(&mut command);
+        }
+
+        let child = R::spawn(&mut command).map_err(|err| setup.spawn_error(err))?;
+        watchdog.watch(&child, tree)?;
+        // closes the parent copies of the child stdio, like the terminal
+        drop(command);
+
+        if is_last {
+            terminal = setup.pty.take();
+        }
+
+        tasks.push(R::start(setup)?);
+        children.push(child);
+    }
+
+    let Recording {
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+    } = read_output(&mut children, terminal, &capture, &mut watchdog).await?;
+    let status = watchdog.wait(&mut children).await?;
+
+    let mut fds = BTreeMap::new();
+
+    for tasks in tasks {
+        fds.append(&mut R::join(tasks).await?);
+    }
+
+    Ok(ProcessOutput::SpawnedPipeline {
+        status: ExitStatus::new(status.code()),
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+        fds,
+    })
+}
+
+/// Reads the piped stdout and stderr of the last of the given
+/// children concurrently, until both reach EOF.
+///
+/// Streams that are not piped are returned as `None`. The output of
+/// the given terminal, if any, is read as stdout. Chunks are
+/// recorded according to the capture configuration, timestamped
+/// relatively to the spawn of the children, and forwarded to the
+/// parent's streams in tee mode. The children get terminated by the
+/// watchdog if they time out or if a stream exceeds its limit.
+pub(crate) async fn read_output<R: Runtime>(
+    children: &mut [R::Child],
+    terminal: Option<PtyMaster>,
+    capture: &Capture,
+    watchdog: &mut Watchdog<R>,
+) -> io::Result<Recording> {
+    // closes stdin so that the child does not wait for more input
+    let (mut stdout_pipe, mut stderr_pipe) = match children.last_mut() {
+        Some(child) => R::pipes(child),
+        None => (None, None),
+    };
+    let mut terminal = terminal.map(R::pty).transpose()?;
+    let captured = (
+        stdout_pipe.is_some() || terminal.is_some(),
+        stderr_pipe.is_some(),
+    );
+
+    let mut recorder = Recorder::new(capture);
+
+    let mut stdout_buf = [0; 8192];
+    let mut stderr_buf = [0; 8192];
+    let mut terminal_buf = [0; 8192];
+
+    while stdout_pipe.is_some() || stderr_pipe.is_some() || terminal.is_some() {
+        // the terminal output is recorded as stdout
+        let read = poll_fn(|cx| {
+            if let Poll::Ready(n) = poll_read(cx, &mut stdout_pipe, &mut stdout_buf) {
+                return Poll::Ready((Stream::Stdout, false, n));
+            }
+
+            if let Poll::Ready(n) = poll_read(cx, &mut stderr_pipe, &mut stderr_buf) {
+                return Poll::Ready((Stream::Stderr, false, n));
+            }
+
+            if let Poll::Ready(n) = poll_read(cx, &mut terminal, &mut terminal_buf) {
+                return Poll::Ready((Stream::Stdout, true, n));
+            }
+
+            Poll::Pending
+        });
+
+        let (stream, from_terminal, n) = match watchdog.remaining() {
+            None => read.await,
+            Some(remaining) => match timeout::<R, _>(remaining, read).await {
+                Some(read) => read,
+                None => {
+                    watchdog.poll(children)?;
+                    continue;
+                }
+            },
+        };
+
+        let n = n?;
+
+        if n == 0 {
+            match (stream, from_terminal) {
+                (_, true) => terminal = None,
+                (Stream::Stdout, false) => stdout_pipe = None,
+                (Stream::Stderr, false) => stderr_pipe = None,
+            }
+            continue;
+        }
+
+        let chunk = match (stream, from_terminal) {
+            (_, true) => &terminal_buf[..n],
+            (Stream::Stdout, false) => &stdout_buf[..n],
+            (Stream::Stderr, false) => &stderr_buf[..n],
+        };
+
+        if capture.tees(stream) {
+            R::tee(stream, chunk).await?;
+        }
+
+        if recorder.record(stream, watchdog.start.elapsed(), chunk) {
+            debug!("{stream:?} exceeded its limit, terminating process");
+            watchdog.terminate(children)?;
+        }
+    }
+
+    Ok(recorder.finish(captured.0, captured.1))
+}
+
+/// Polls a read of the given optional reader into `buf`.
+///
+/// A missing reader never gets ready.
+fn poll_read<T: Reader>(
+    cx: &mut Context<'_>,
+    reader: &mut Option<T>,
+    buf: &mut [u8],
+) -> Poll<io::Result<usize>> {
+    match reader {
+        Some(reader) => reader.poll_read(cx, buf),
+        None => Poll::Pending,
+    }
+}
+
+/// Runs the given future until the given duration elapsed, using the
+/// timer of the runtime.
+///
+/// Returns `None` if the future did not complete in time.
+async fn timeout<R: Runtime, T>(duration: Duration, fut: impl Future<Output = T>) -> Option<T> {
+    let mut fut = pin!(fut);
+    let mut elapsed = pin!(R::sleep(duration));
+
+    poll_fn(|cx| {
+        if let Poll::Ready(out) = fut.as_mut().poll(cx) {
+            return Poll::Ready(Some(out));
+        }
+
+        match elapsed.as_mut().poll(cx) {
+            Poll::Ready(()) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    })
+    .await
+}
+
+/// Returns `true` if the runtime needs to kill the child spawned with
+/// the given termination when its handle gets dropped.
+///
+/// In tree mode with a grace period, the [`Watchdog`] sends the
+/// termination sequence itself, so that the child is not killed
+/// before its grace period.
+pub(crate) fn kills_on_drop(termination: &Termination) -> bool {
+    let delegated = cfg!(unix) && termination.tree && termination.grace.is_some();
+    termination.on_drop == OnDrop::Kill && !delegated
+}
+
+/// Terminator of spawned children, sending the signals of their
+/// termination sequence.
+///
+/// The children of a pipeline share the same termination sequence.
+/// When dropped before the children exited, for example because the
+/// future of the runtime got cancelled, the termination sequence is
+/// sent to their whole process tree in tree mode, unless they need to
+/// be detached. The other children are killed by the runtime when
+/// their handle gets dropped, see [`kills_on_drop`]. All of them are
+/// reaped in the background by the runtime.
+pub(crate) struct Watchdog<R: Runtime> {
+    terminator: Terminator,
+    termination: Termination,
+    start: Instant,
+    /// Targets of the watched children, by spawn order, taken once
+    /// the child got reaped.
+    #[cfg(unix)]
+    targets: Vec<Option<kill::Target>>,
+    runtime: PhantomData<R>,
+}
+
+impl<R: Runtime> Watchdog<R> {
+    /// Creates a new watchdog for the given command, about to be
+    /// spawned.
+    pub(crate) fn new(cmd: &Command) -> Self {
+        Self {
+            terminator: Terminator::new(cmd.timeout, &cmd.termination),
+            termination: cmd.termination,
+            start: Instant::now(),
+            #[cfg(unix)]
+            targets: Vec::new(),
+            runtime: PhantomData,
+        }
+    }
+
+    /// Watches the given spawned child, and its whole process tree if
+    /// `tree` is `true`.
+    ///
+    /// A child that cannot be watched gets handled according to its
+    /// [`Termination::on_drop`] configuration once its handle is
+    /// dropped.
+    #[cfg(unix)]
+    pub(crate) fn watch(&mut self, child: &R::Child, tree: bool) -> io::Result<()> {
+        let target = R::id(child)
+            .map(|pid| kill::Target::new(pid, tree))
+            .transpose()?;
+        self.targets.push(target);
+        Ok(())
+    }
+
+    /// Watches the given spawned child, which has nothing to prepare
+    /// on this platform.
+    #[cfg(not(unix))]
+    pub(crate) fn watch(&mut self, _child: &R::Child, _tree: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the time remaining until the next deadline, if any.
+    fn remaining(&self) -> Option<Duration> {
+        let deadline = self.terminator.deadline()?;
+        Some(deadline.saturating_sub(self.start.elapsed()))
+    }
+
+    /// Starts the termination sequence of the given children.
+    fn terminate(&mut self, children: &mut [R::Child]) -> io::Result<()> {
+        match self.terminator.terminate(self.start.elapsed()) {
+            Some(signal) => self.send(children, signal),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes the termination sequence of the given children progress.
+    fn poll(&mut self, children: &mut [R::Child]) -> io::Result<()> {
+        match self.terminator.poll(self.start.elapsed()) {
+            Some(signal) => self.send(children, signal),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for the exit of the given children, making their
+    /// termination sequence progress meanwhile.
+    ///
+    /// Returns the exit status of the last child. Fails with
+    /// [`io::ErrorKind::TimedOut`] if the children timed out.
+    pub(crate) async fn wait(&mut self, children: &mut [R::Child]) -> io::Result<StdExitStatus> {
+        let mut status = None;
+
+        for i in 0..children.len() {
+            status = Some(self.reap(children, i).await?);
+        }
+
+        if self.terminator.timed_out() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "process ran past its timeout",
+            ));
+        }
+
+        status.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no process to wait"))
+    }
+
+    /// Waits for the exit of the child at the given index, making the
+    /// termination sequence of all the children progress meanwhile,
+    /// then reaps it.
+    async fn reap(&mut self, children: &mut [R::Child], i: usize) -> io::Result<StdExitStatus> {
+        #[cfg(target_os = "linux")]
+        self.exited(children, i).await?;
+
+        let status = loop {
+            let Some(remaining) = self.remaining() else {
+                break R::wait(&mut children[i]).await?;
+            };
+
+            match timeout::<R, _>(remaining, R::wait(&mut children[i])).await {
+                Some(status) => break status?,
+                None => self.poll(children)?,
+            }
+        };
+
+        // the child got reaped, its process group may be reused
+        #[cfg(unix)]
+        {
+            self.targets[i] = None;
+        }
+
+        Ok(status)
+    }
+
+    /// Waits for the exit of the child at the given index through its
+    /// pidfd, if any, making the termination sequence of all the
+    /// children progress meanwhile.
+    ///
+    /// The child is not reaped, so that its ID cannot be recycled
+    /// before the last signal got sent.
+    #[cfg(target_os = "linux")]
+    async fn exited(&mut self, children: &mut [R::Child], i: usize) -> io::Result<()> {
+        loop {
+            let Some(pidfd) = self.targets[i].as_ref().and_then(kill::Target::pidfd) else {
+                return Ok(());
+            };
+
+            let exited = match self.remaining() {
+                None => Some(R::exited(pidfd).await),
+                Some(remaining) => timeout::<R, _>(remaining, R::exited(pidfd)).await,
+            };
+
+            match exited {
+                Some(res) => return res,
+                None => self.poll(children)?,
+            }
+        }
+    }
+
+    /// Sends the given signal to the children not reaped yet, or to
+    /// their whole process tree.
+    #[cfg(unix)]
+    fn send(&self, _children: &mut [R::Child], signal: Signal) -> io::Result<()> {
+        for target in self.targets.iter().flatten() {
+            debug!("sends {signal:?} to {target:?}");
+            target.signal(signal)?;
+        }
+
+        Ok(())
+    }
+
+    /// Kills the children not reaped yet, which is the only
+    /// termination supported on this platform.
+    #[cfg(not(unix))]
+    fn send(&self, children: &mut [R::Child], _signal: Signal) -> io::Result<()> {
+        for child in children {
+            if R::id(child).is_some() {
+                R::kill(child)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl<R: Runtime> Drop for Watchdog<R> {
+    fn drop(&mut self) {
+        if self.termination.on_drop == OnDrop::Detach {
+            return;
+        }
+
+        // the direct children are handled by the runtime
+        let targets: Vec<_> = self
+            .targets
+            .drain(..)
+            .flatten()
+            .filter(kill::Target::tree)
+            .collect();
+
+        if targets.is_empty() {
+            return;
+        }
+
+        debug!("terminates process trees of dropped children {targets:?}");
+
+        let Some(grace) = self.termination.grace else {
+            for target in &targets {
+                let _ = target.signal(Signal::Kill);
+            }
+            return;
+        };
+
+        for target in &targets {
+            let _ = target.signal(Signal::Terminate);
+        }
+
+        R::defer(grace, move || {
+            for target in &targets {
+                let _ = target.signal(Signal::Kill);
+            }
+        });
+    }
+}
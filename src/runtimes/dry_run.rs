@@ -0,0 +1,287 @@
+//! Dry-run process runtime, logging processes instead of spawning
+//! them.
+//!
+//! A [`DryRun`] accepts any [`ProcessInput`], renders it as a shell
+//! command line, and answers it with a synthetic [`ProcessOutput`],
+//! so that coroutines can complete without side effects.
+//!
+//! The dry run performs no I/O at all, which makes it usable in
+//! `no_std` environments.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::fmt::{self, Write};
+
+use log::info;
+
+use super::synthetic::Synthetic;
+use crate::{
+    command::{Command, write_quoted},
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+    stdio::Stdio,
+};
+
+/// Function answering inputs with custom outputs.
+type Responder = Box<dyn FnMut(&ProcessInput) -> Option<ProcessOutput>>;
+
+/// Dry-run runtime.
+///
+/// Each processed input is logged at the info level and recorded as
+/// a command line, see [`render`]. Inputs are answered with the
+/// synthetic output configured with [`DryRun::status`],
+/// [`DryRun::stdout`] and [`DryRun::stderr`], which exits with `0`
+/// and writes nothing by default, unless a custom responder answers
+/// them first.
+#[derive(Default)]
+pub struct DryRun {
+    output: Synthetic,
+    responder: Option<Responder>,
+    commands: Vec<String>,
+}
+
+impl DryRun {
+    /// Creates a new dry run.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the synthetic exit status.
+    pub fn status(&mut self, status: ExitStatus) -> &mut Self {
+        self.output.status = status;
+        self
+    }
+
+    /// Sets the synthetic stdout bytes.
+    pub fn stdout(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.output.stdout = bytes.into();
+        self
+    }
+
+    /// Sets the synthetic stderr bytes.
+    pub fn stderr(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.output.stderr = bytes.into();
+        self
+    }
+
+    /// Sets a responder answering inputs with custom outputs.
+    ///
+    /// Inputs the responder answers with `None` get the synthetic
+    /// output. The responder must return an output matching the
+    /// input, for example [`ProcessOutput::Spawned`] for
+    /// [`ProcessInput::Spawn`].
+    pub fn respond(
+        &mut self,
+        responder: impl FnMut(&ProcessInput) -> Option<ProcessOutput> + 'static,
+    ) -> &mut Self {
+        self.responder = Some(Box::new(responder));
+        self
+    }
+
+    /// Returns the command lines of the inputs processed so far.
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// Processes a [`ProcessInput`] request, without spawning any
+    /// process.
+    pub fn handle(&mut self, input: ProcessInput) -> ProcessOutput {
+        let command = render(&input);
+        info!("dry run: {command}");
+        self.commands.push(command);
+
+        if let Some(responder) = &mut self.responder {
+            if let Some(output) = responder(&input) {
+                return output;
+            }
+        }
+
+        self.output.clone().into_output(&input)
+    }
+}
+
+impl fmt::Debug for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DryRun")
+            .field("output", &self.output)
+            .field("responder", &self.responder.is_some())
+            .field("commands", &self.commands)
+            .finish()
+    }
+}
+
+/// Renders the given input as a POSIX shell command line.
+///
+/// Pipeline stages are joined with `|`. Each command is prefixed with
+/// its working directory and its explicitly set environment
+/// variables, and followed by its file redirections. The stdin fed to
+/// [`ProcessInput::SpawnIn`] is summarized in a trailing comment.
+///
+/// For example, `cd /tmp && LANG=C grep foo < in.txt 2>&1`.
+pub fn render(input: &ProcessInput) -> String {
+    let mut line = String::new();
+    let _ = write_input(&mut line, input);
+    line
+}
+
+fn write_input(line: &mut String, input: &ProcessInput) -> fmt::Result {
+    match input {
+        ProcessInput::Spawn { cmd } | ProcessInput::SpawnOut { cmd } => {
+            write_command(line, cmd, false)
+        }
+        ProcessInput::SpawnIn { cmd, stdin } => {
+            write_command(line, cmd, false)?;
+            write!(line, " # with {} byte(s) on stdin", stdin.len())
+        }
+        ProcessInput::SpawnPipeline { cmds } => {
+            for (i, cmd) in cmds.iter().enumerate() {
+                if i > 0 {
+                    line.push_str(" | ");
+                }
+
+                write_command(line, cmd, cmds.len() > 1)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Writes a command, in a subshell if it has a working directory and
+/// is a pipeline stage.
+fn write_command(line: &mut String, cmd: &Command, stage: bool) -> fmt::Result {
+    let subshell = stage && cmd.current_dir.is_some();
+
+    if subshell {
+        line.push('(');
+    }
+
+    if let Some(dir) = &cmd.current_dir {
+        line.push_str("cd ");
+        write_quoted(line, dir)?;
+        line.push_str(" && ");
+    }
+
+    for (key, val) in cmd.envs.iter().flatten() {
+        write_quoted(line, key)?;
+        line.push('=');
+        write_quoted(line, val)?;
+        line.push(' ');
+    }
+
+    write!(line, "{cmd}")?;
+
+    if let Some(Stdio::ReadFile(path)) = &cmd.stdin {
+        line.push_str(" < ");
+        write_quoted(line, path)?;
+    }
+
+    write_redirection(line, "", cmd.stdout.as_ref())?;
+    write_redirection(line, "2", cmd.stderr.as_ref())?;
+
+    if subshell {
+        line.push(')');
+    }
+
+    Ok(())
+}
+
+/// Writes the redirection of the given output stream, if any.
+fn write_redirection(line: &mut String, fd: &str, stdio: Option<&Stdio>) -> fmt::Result {
+    let (op, path) = match stdio {
+        Some(Stdio::Null) => (">", "/dev/null"),
+        Some(Stdio::WriteFile(path)) => (">", path.as_str()),
+        Some(Stdio::AppendFile(path)) => (">>", path.as_str()),
+        Some(Stdio::Stdout) => return write!(line, " {fd}>&1"),
+        _ => return Ok(()),
+    };
+
+    write!(line, " {fd}{op} ")?;
+    write_quoted(line, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use crate::{
+        command::Command,
+        coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
+        io::{ProcessInput, ProcessOutput},
+        status::ExitStatus,
+        stdio::Stdio,
+    };
+
+    use super::{DryRun, render};
+
+    #[test]
+    fn rendering() {
+        let mut grep = Command::new("grep");
+        grep.arg("it's")
+            .env("LANG", "C")
+            .current_dir("/my dir")
+            .stdin(Stdio::ReadFile("in.txt".to_string()))
+            .stderr(Stdio::Stdout);
+
+        let input = ProcessInput::SpawnOut { cmd: grep.clone() };
+        assert_eq!(
+            "cd '/my dir' && LANG=C grep 'it'\\''s' < in.txt 2>&1",
+            render(&input),
+        );
+
+        let mut wc = Command::new("wc");
+        wc.arg("-l")
+            .stdout(Stdio::AppendFile("out.txt".to_string()));
+
+        let input = ProcessInput::SpawnPipeline {
+            cmds: vec![grep, wc],
+        };
+        assert_eq!(
+            "(cd '/my dir' && LANG=C grep 'it'\\''s' < in.txt 2>&1) | wc -l >> out.txt",
+            render(&input),
+        );
+
+        let input = ProcessInput::SpawnIn {
+            cmd: Command::new("cat"),
+            stdin: b"abc".to_vec(),
+        };
+        assert_eq!("cat # with 3 byte(s) on stdin", render(&input));
+    }
+
+    #[test]
+    fn outputs() {
+        let mut dry_run = DryRun::new();
+        dry_run.stdout("synthetic\n").respond(|input| match input {
+            ProcessInput::Spawn { cmd } if cmd.get_program() == "false" => {
+                let status = ExitStatus::new(Some(1));
+                Some(ProcessOutput::Spawned { status })
+            }
+            _ => None,
+        });
+
+        let mut command = Command::new("rm");
+        command.arg("-rf").arg("/important");
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawnOut::new(command);
+
+        let stdout = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnOutResult::Ok { stdout, .. } => break stdout,
+                ProcessSpawnOutResult::Io { input } => arg = Some(dry_run.handle(input)),
+                ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(Some(b"synthetic\n".to_vec()), stdout);
+
+        let input = ProcessInput::Spawn {
+            cmd: Command::new("false"),
+        };
+        let ProcessOutput::Spawned { status } = dry_run.handle(input) else {
+            panic!("unexpected output");
+        };
+
+        assert_eq!(Some(1), status.code());
+        assert_eq!(["rm -rf /important", "false"], dry_run.commands());
+    }
+}
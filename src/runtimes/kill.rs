@@ -0,0 +1,92 @@
+//! Delivery of termination signals, shared by the std-based
+//! runtimes.
+
+use std::io;
+
+#[cfg(target_os = "linux")]
+use super::pidfd::Pidfd;
+use crate::termination::Signal;
+
+/// Spawned process to deliver signals to, or whose whole process
+/// tree to deliver signals to.
+///
+/// On Linux, signals are delivered through a [`Pidfd`] when the
+/// kernel supports it, so that they can never hit another process
+/// that recycled the ID of the child.
+#[derive(Debug)]
+pub(crate) struct Target {
+    pid: u32,
+    tree: bool,
+    #[cfg(target_os = "linux")]
+    pidfd: Option<Pidfd>,
+}
+
+impl Target {
+    /// Creates a new target for the process `pid`, not reaped yet,
+    /// or for its whole process group if `tree` is `true`.
+    ///
+    /// Falls back to the numeric process ID only if the kernel does
+    /// not support pidfds. Any other failure to open the pidfd, like
+    /// running out of file descriptors, is returned instead, so that
+    /// signals can never hit a recycled process ID.
+    pub(crate) fn new(pid: u32, tree: bool) -> io::Result<Self> {
+        Ok(Self {
+            pid,
+            tree,
+            #[cfg(target_os = "linux")]
+            pidfd: Pidfd::open(pid)?,
+        })
+    }
+
+    /// Returns the pidfd of the process, if supported.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn pidfd(&self) -> Option<&Pidfd> {
+        self.pidfd.as_ref()
+    }
+
+    /// Returns `true` if signals are delivered to the whole process
+    /// tree.
+    #[cfg_attr(not(any(feature = "tokio", feature = "smol")), allow(dead_code))]
+    pub(crate) fn tree(&self) -> bool {
+        self.tree
+    }
+
+    /// Sends the given signal to the process, or to its whole process
+    /// group in tree mode.
+    ///
+    /// Process groups have no pidfd, but their ID cannot be recycled
+    /// as long as the child, their leader, is not reaped.
+    pub(crate) fn signal(&self, signal: Signal) -> io::Result<()> {
+        #[cfg(target_os = "linux")]
+        if let (false, Some(pidfd)) = (self.tree, &self.pidfd) {
+            return pidfd.send_signal(signal);
+        }
+
+        self::signal(self.pid, self.tree, signal)
+    }
+}
+
+/// Sends the given signal to the process `pid`, or to its whole
+/// process group if `tree` is `true`.
+///
+/// A process that does not exist anymore is not an error.
+pub(crate) fn signal(pid: u32, tree: bool, signal: Signal) -> io::Result<()> {
+    let pid = pid as libc::pid_t;
+    let target = if tree { -pid } else { pid };
+
+    let signal = match signal {
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+
+    // SAFETY: kill does not access memory.
+    if unsafe { libc::kill(target, signal) } == -1 {
+        let err = io::Error::last_os_error();
+
+        if err.raw_os_error() != Some(libc::ESRCH) {
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,300 @@
+//! Scripted mock runtime, for testing coroutines without spawning
+//! processes.
+//!
+//! A [`Mock`] is configured with a script of [`Expectation`]s, each
+//! matching one [`ProcessInput`] and answering it with a canned
+//! [`ProcessOutput`]. Inputs are expected in the order of the script.
+//!
+//! The mock performs no I/O at all, which makes it usable in `no_std`
+//! environments.
+
+use alloc::{collections::VecDeque, string::String, vec, vec::Vec};
+
+use thiserror::Error;
+
+use super::{
+    describe::{self, Kind},
+    synthetic::Synthetic,
+};
+use crate::{
+    command::Command,
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+};
+
+/// Error emitted by the [`Mock`] runtime.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum MockError {
+    /// The input does not match the next expectation.
+    #[error("Unexpected process input (- expected, + actual):\n{diff}")]
+    Mismatch { diff: String },
+
+    /// The input came after all expectations got consumed.
+    #[error("Unexpected process input, no expectation left:\n{input}")]
+    Exhausted { input: String },
+
+    /// Some expectations have not been consumed.
+    #[error("{} expectation(s) not consumed:\n{}", .remaining.len(), .remaining.join("\n"))]
+    Unconsumed { remaining: Vec<String> },
+}
+
+/// Expected [`ProcessInput`] of a [`Mock`], with its canned
+/// [`ProcessOutput`].
+///
+/// An input matches if it has the same kind, and if its commands
+/// have the same program, arguments and explicitly set environment
+/// variables as the expected ones. For [`ProcessInput::SpawnIn`],
+/// stdin needs to match as well. Other parts of the commands, like
+/// their [`Stdio`] configuration, are not matched.
+///
+/// The canned output exits with `0` and writes nothing by default.
+/// Streams are returned as captured the same way runtimes capture
+/// them, according to the [`Stdio`] configuration of the input.
+///
+/// [`Stdio`]: crate::stdio::Stdio
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    kind: Kind,
+    cmds: Vec<Command>,
+    stdin: Option<Vec<u8>>,
+    output: Synthetic,
+}
+
+impl Expectation {
+    fn new(kind: Kind, cmds: Vec<Command>, stdin: Option<Vec<u8>>) -> Self {
+        Self {
+            kind,
+            cmds,
+            stdin,
+            output: Synthetic::default(),
+        }
+    }
+
+    /// Expects a [`ProcessInput::Spawn`] of the given command.
+    pub fn spawn(cmd: impl Into<Command>) -> Self {
+        Self::new(Kind::Spawn, vec![cmd.into()], None)
+    }
+
+    /// Expects a [`ProcessInput::SpawnOut`] of the given command.
+    pub fn spawn_out(cmd: impl Into<Command>) -> Self {
+        Self::new(Kind::SpawnOut, vec![cmd.into()], None)
+    }
+
+    /// Expects a [`ProcessInput::SpawnIn`] of the given command, fed
+    /// with the given stdin.
+    pub fn spawn_in(cmd: impl Into<Command>, stdin: impl Into<Vec<u8>>) -> Self {
+        Self::new(Kind::SpawnIn, vec![cmd.into()], Some(stdin.into()))
+    }
+
+    /// Expects a [`ProcessInput::SpawnPipeline`] of the given
+    /// commands.
+    pub fn spawn_pipeline(cmds: impl IntoIterator<Item = Command>) -> Self {
+        Self::new(Kind::SpawnPipeline, cmds.into_iter().collect(), None)
+    }
+
+    /// Sets the canned exit status.
+    pub fn status(&mut self, status: ExitStatus) -> &mut Self {
+        self.output.status = status;
+        self
+    }
+
+    /// Sets the canned stdout bytes.
+    pub fn stdout(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.output.stdout = bytes.into();
+        self
+    }
+
+    /// Sets the canned stderr bytes.
+    pub fn stderr(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.output.stderr = bytes.into();
+        self
+    }
+
+    /// Sets the canned bytes of a captured extra file descriptor.
+    pub fn fd(&mut self, fd: i32, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.output.fds.insert(fd, bytes.into());
+        self
+    }
+}
+
+/// Scripted mock runtime.
+///
+/// Processes [`ProcessInput`] requests by matching them against its
+/// script of [`Expectation`]s, in order, and answering them with the
+/// canned outputs. Call [`Mock::verify`] at the end of a test to make
+/// sure the whole script has been consumed.
+#[derive(Clone, Debug, Default)]
+pub struct Mock {
+    expectations: VecDeque<Expectation>,
+}
+
+impl Mock {
+    /// Creates a new mock, expecting no input.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the given expectation to the script.
+    pub fn expect(&mut self, expectation: Expectation) -> &mut Self {
+        self.expectations.push_back(expectation);
+        self
+    }
+
+    /// Processes a [`ProcessInput`] request, consuming the next
+    /// expectation.
+    ///
+    /// Fails with a diff between the expected and the actual input if
+    /// they do not match. The expectation is then kept in the script.
+    pub fn handle(&mut self, input: ProcessInput) -> Result<ProcessOutput, MockError> {
+        let actual = describe::input(&input);
+
+        let Some(expectation) = self.expectations.pop_front() else {
+            return Err(MockError::Exhausted { input: actual });
+        };
+
+        let expected = describe_expectation(&expectation);
+
+        if expected != actual {
+            self.expectations.push_front(expectation);
+            let diff = describe::diff(&expected, &actual);
+            return Err(MockError::Mismatch { diff });
+        }
+
+        Ok(expectation.output.into_output(&input))
+    }
+
+    /// Checks that all expectations have been consumed.
+    pub fn verify(&self) -> Result<(), MockError> {
+        if self.expectations.is_empty() {
+            return Ok(());
+        }
+
+        let remaining = self.expectations.iter().map(describe_expectation).collect();
+
+        Err(MockError::Unconsumed { remaining })
+    }
+}
+
+fn describe_expectation(expectation: &Expectation) -> String {
+    let stdin = expectation.stdin.as_deref();
+    describe::commands(expectation.kind, &expectation.cmds, stdin)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec};
+
+    use crate::{
+        command::Command,
+        coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
+        io::{ProcessInput, ProcessOutput},
+        status::ExitStatus,
+        stdio::Stdio,
+    };
+
+    use super::{Expectation, Mock, MockError};
+
+    #[test]
+    fn script() {
+        let mut command = Command::new("echo");
+        command.arg("hello").env("LANG", "C");
+
+        let mut expectation = Expectation::spawn_out(command.clone());
+        expectation.stdout("hello\n").stderr("warning\n");
+
+        let mut mock = Mock::new();
+        mock.expect(expectation);
+
+        // stderr is not captured, so it is not returned either
+        command.stderr(Stdio::Inherit);
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawnOut::new(command);
+
+        let (stdout, stderr) = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnOutResult::Ok { stdout, stderr, .. } => break (stdout, stderr),
+                ProcessSpawnOutResult::Io { input } => arg = Some(mock.handle(input).unwrap()),
+                ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(Some(b"hello\n".to_vec()), stdout);
+        assert_eq!(None, stderr);
+        assert_eq!(Ok(()), mock.verify());
+    }
+
+    #[test]
+    fn mismatch() {
+        let mut expected = Command::new("grep");
+        expected.arg("foo");
+
+        let mut expectation = Expectation::spawn_in(expected, "foo\nbar\n");
+        expectation.status(ExitStatus::new(Some(1)));
+
+        let mut mock = Mock::new();
+        mock.expect(expectation);
+
+        let mut actual = Command::new("grep");
+        actual.arg("bar");
+        let stdin = b"foo\nbar\n".to_vec();
+        let input = ProcessInput::SpawnIn { cmd: actual, stdin };
+
+        let err = mock.handle(input).unwrap_err();
+        let MockError::Mismatch { diff } = &err else {
+            panic!("unexpected error: {err}");
+        };
+
+        let expected = [
+            "  SpawnIn",
+            "-   command: grep foo",
+            "+   command: grep bar",
+            "    stdin: \"foo\\nbar\\n\"",
+        ];
+        assert_eq!(expected.join("\n"), *diff);
+
+        // the expectation is kept
+        let mut expected = Command::new("grep");
+        expected.arg("foo");
+        let stdin = b"foo\nbar\n".to_vec();
+        let input = ProcessInput::SpawnIn {
+            cmd: expected,
+            stdin,
+        };
+
+        let Ok(ProcessOutput::SpawnedIn { status }) = mock.handle(input) else {
+            panic!("unexpected output");
+        };
+
+        assert_eq!(Some(1), status.code());
+    }
+
+    #[test]
+    fn unconsumed() {
+        let mut mock = Mock::new();
+        mock.expect(Expectation::spawn(Command::new("true")));
+        mock.expect(Expectation::spawn_pipeline(vec![
+            Command::new("ls"),
+            Command::new("wc"),
+        ]));
+
+        let input = ProcessInput::Spawn {
+            cmd: Command::new("true"),
+        };
+        mock.handle(input).unwrap();
+
+        let err = mock.verify().unwrap_err();
+        assert_eq!(
+            "1 expectation(s) not consumed:\nSpawnPipeline\n  command: ls\n  command: wc",
+            err.to_string(),
+        );
+
+        let input = ProcessInput::Spawn {
+            cmd: Command::new("false"),
+        };
+        assert!(matches!(
+            mock.handle(input),
+            Err(MockError::Mismatch { .. }),
+        ));
+    }
+}
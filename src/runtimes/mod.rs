@@ -6,13 +6,53 @@
 //!
 //! If you miss a runtime matching your requirements, you can easily
 //! implement your own by taking example on the existing ones. PRs are
-//! welcomed!
+//! welcomed! The [`testkit`] checks that a custom runtime behaves
+//! like the built-in ones.
 //!
 //! [`ProcessInput`]: crate::io::ProcessInput
 //! [`ProcessOutput`]: crate::io::ProcessOutput
 //! [coroutines]: crate::coroutines
 
+#[cfg(any(feature = "std", feature = "tokio", feature = "smol"))]
+mod convert;
+#[cfg(any(feature = "mock", feature = "replay"))]
+mod describe;
+#[cfg(any(feature = "tokio", feature = "smol"))]
+mod driver;
+#[cfg(feature = "dry-run")]
+pub mod dry_run;
+#[cfg(all(unix, any(feature = "std", feature = "tokio", feature = "smol")))]
+mod kill;
+#[cfg(feature = "mock")]
+pub mod mock;
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "std", feature = "tokio", feature = "smol")
+))]
+pub mod pidfd;
+#[cfg(all(unix, feature = "poll"))]
+pub mod poll;
+#[cfg(feature = "replay")]
+pub mod replay;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(feature = "smol")]
+pub mod smol;
+#[cfg(all(unix, feature = "socket"))]
+pub mod socket;
 #[cfg(feature = "std")]
 pub mod std;
+#[cfg(any(feature = "mock", feature = "dry-run"))]
+mod synthetic;
+#[cfg(any(
+    all(
+        target_os = "linux",
+        any(feature = "std", feature = "tokio", feature = "smol")
+    ),
+    all(unix, feature = "poll")
+))]
+mod sys;
+#[cfg(feature = "testkit")]
+pub mod testkit;
 #[cfg(feature = "tokio")]
 pub mod tokio;
@@ -0,0 +1,153 @@
+//! Race-free process handles on Linux.
+//!
+//! A [`Pidfd`] refers to a process, not to its numeric ID: signaling
+//! or polling a process through it can never hit another process
+//! that recycled the ID after the original one got reaped.
+//!
+//! The built-in runtimes signal their children and wait for their
+//! exit through pidfds. The child handles of the async runtimes can
+//! be turned into a pidfd with [`Pidfd::open`] and their ID.
+//!
+//! Pidfds are available since Linux 5.3. On older kernels,
+//! [`Pidfd::open`] returns `None` and runtimes fall back to numeric
+//! process IDs.
+
+use std::{
+    io,
+    mem::MaybeUninit,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    process::Child,
+    time::Duration,
+};
+
+use super::sys;
+use crate::termination::Signal;
+
+/// File descriptor referring to a process.
+///
+/// The descriptor becomes readable once the process exited, which
+/// makes it usable with `poll(2)`, `epoll(7)` or any event loop, for
+/// example via [`tokio::io::unix::AsyncFd`].
+///
+/// [`tokio::io::unix::AsyncFd`]: https://docs.rs/tokio/latest/tokio/io/unix/struct.AsyncFd.html
+#[derive(Debug)]
+pub struct Pidfd(OwnedFd);
+
+impl Pidfd {
+    /// Opens a pidfd for the process `pid`, using `pidfd_open(2)`.
+    ///
+    /// Returns `None` if the kernel does not support pidfds. The
+    /// process must not have been reaped yet, otherwise the pidfd
+    /// may refer to another process.
+    pub fn open(pid: u32) -> io::Result<Option<Self>> {
+        // SAFETY: pidfd_open does not access memory.
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+
+        if fd == -1 {
+            let err = io::Error::last_os_error();
+
+            return match err.raw_os_error() {
+                Some(libc::ENOSYS) => Ok(None),
+                _ => Err(err),
+            };
+        }
+
+        // SAFETY: the syscall returned a new, owned file descriptor.
+        Ok(Some(Self(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })))
+    }
+
+    /// Opens a pidfd for the given child, not reaped yet.
+    pub fn from_child(child: &Child) -> io::Result<Option<Self>> {
+        Self::open(child.id())
+    }
+
+    /// Sends the given signal to the process, using
+    /// `pidfd_send_signal(2)`.
+    ///
+    /// A process that already exited is not an error.
+    pub fn send_signal(&self, signal: Signal) -> io::Result<()> {
+        let signal = match signal {
+            Signal::Terminate => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        };
+
+        let fd = self.0.as_raw_fd();
+        let info = std::ptr::null::<libc::siginfo_t>();
+
+        // SAFETY: a null siginfo is allowed, and makes the kernel
+        // fill it like kill(2) does.
+        if unsafe { libc::syscall(libc::SYS_pidfd_send_signal, fd, signal, info, 0) } == -1 {
+            let err = io::Error::last_os_error();
+
+            if err.raw_os_error() != Some(libc::ESRCH) {
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the process exited, without reaping it.
+    ///
+    /// Uses `waitid(P_PIDFD)`, available since Linux 5.4, and falls
+    /// back to polling the pidfd on older kernels or for processes
+    /// that are not children of the current process.
+    pub fn has_exited(&self) -> io::Result<bool> {
+        let mut info = MaybeUninit::<libc::siginfo_t>::zeroed();
+        let id = self.0.as_raw_fd() as libc::id_t;
+        let options = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+
+        // SAFETY: info points to a valid siginfo.
+        if unsafe { libc::waitid(libc::P_PIDFD, id, info.as_mut_ptr(), options) } == -1 {
+            let err = io::Error::last_os_error();
+
+            return match err.raw_os_error() {
+                // the pidfd is non-blocking and the process is running
+                Some(libc::EAGAIN) => Ok(false),
+                // the process is not a child, or got reaped already
+                Some(libc::EINVAL | libc::ECHILD) => self.wait_timeout(Some(Duration::ZERO)),
+                _ => Err(err),
+            };
+        }
+
+        // SAFETY: waitid initialized the info, or left it zeroed if
+        // the process is still running.
+        let info = unsafe { info.assume_init() };
+        // SAFETY: the pid field is valid for SIGCHLD infos, and zero
+        // otherwise.
+        Ok(unsafe { info.si_pid() } != 0)
+    }
+
+    /// Waits for the exit of the process, without reaping it.
+    ///
+    /// Returns `false` if the process did not exit within the given
+    /// timeout, if any.
+    pub fn wait_timeout(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let mut fds = [sys::pollfd(self.0.as_raw_fd(), libc::POLLIN)];
+
+        loop {
+            match sys::poll(&mut fds, timeout) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                res => return res.map(|n| n > 0),
+            }
+        }
+    }
+}
+
+impl AsFd for Pidfd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl AsRawFd for Pidfd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl From<Pidfd> for OwnedFd {
+    fn from(pidfd: Pidfd) -> Self {
+        pidfd.0
+    }
+}
@@ -0,0 +1,801 @@
+//! Single-threaded process runtime multiplexing many requests with
+//! `poll(2)`.
+//!
+//! A [`Poller`] accepts any number of in-flight [`ProcessInput`]
+//! requests, and drives all their processes from the calling thread:
+//! the parent ends of the pipes are switched to non-blocking mode and
+//! served as soon as they are ready, and exits are watched through
+//! pidfds. Outputs are returned as their requests complete, tagged
+//! with the [`RequestId`] returned on submission.
+//!
+//! ```rust,ignore
+//! let mut poller = Poller::new();
+//!
+//! for cmd in cmds {
+//!     poller.submit(ProcessInput::SpawnOut { cmd });
+//! }
+//!
+//! for (id, output) in &mut poller {
+//!     println!("request {id} completed: {output:?}");
+//! }
+//! ```
+//!
+//! Exits are not watched through `SIGCHLD`, whose handler is
+//! process-wide and belongs to the application. Without pidfds (on
+//! Linux before 5.3, or on other Unix platforms), children are polled
+//! at a short interval instead.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    process::{Child, ExitStatus as StdExitStatus, Stdio as StdStdio},
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use super::{
+    convert, kill,
+    std::tee,
+    sys::{self, pollfd},
+};
+use crate::{
+    capture::{Capture, Recorder, Stream},
+    command::Command,
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+    stdio::Stdio,
+    termination::{OnDrop, Signal, Termination, Terminator},
+};
+
+/// Size of the buffer pipes are read with, in bytes.
+const CHUNK: usize = 8192;
+
+/// Interval at which children without pidfd are polled for their
+/// exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Processes a single [`ProcessInput`] request with a new [`Poller`].
+///
+/// Mostly useful to check the runtime against the other ones: a
+/// single request is better served by the [`std`](super::std)
+/// runtime.
+pub fn handle(input: ProcessInput) -> io::Result<ProcessOutput> {
+    let mut poller = Poller::new();
+    poller.submit(input);
+
+    match poller.wait(None) {
+        Some((_, output)) => output,
+        None => unreachable!("the submitted request is in flight"),
+    }
+}
+
+/// Identifier of a request submitted to a [`Poller`].
+///
+/// Identifiers are attributed in submission order, starting at `0`,
+/// and are unique within their poller.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RequestId(u64);
+
+impl RequestId {
+    /// Returns the numeric value of the identifier.
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Event loop driving many in-flight requests from a single thread.
+///
+/// Requests are spawned as soon as they are [submitted], then make
+/// progress whenever the poller [waits] for a completion. Iterating
+/// over the poller returns the completed requests until none is left
+/// in flight.
+///
+/// When the poller gets dropped, the processes of the requests still
+/// in flight are handled according to their [`Termination::on_drop`]
+/// configuration: by default, they are killed right away, without
+/// grace period, and reaped.
+///
+/// [submitted]: Poller::submit
+/// [waits]: Poller::wait
+#[derive(Debug, Default)]
+pub struct Poller {
+    next_id: u64,
+    jobs: Vec<(RequestId, Job)>,
+    done: VecDeque<(RequestId, io::Result<ProcessOutput>)>,
+}
+
+impl Poller {
+    /// Creates a new poller, without request in flight.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Submits a request, spawning its processes right away.
+    ///
+    /// A request that cannot be spawned completes immediately with
+    /// the spawn error.
+    pub fn submit(&mut self, input: ProcessInput) -> RequestId {
+        let id = RequestId(self.next_id);
+        self.next_id += 1;
+
+        match Job::start(input) {
+            Ok(job) => self.jobs.push((id, job)),
+            Err(err) => self.done.push_back((id, Err(err))),
+        }
+
+        id
+    }
+
+    /// Returns the number of requests whose output has not been
+    /// returned yet.
+    pub fn len(&self) -> usize {
+        self.jobs.len() + self.done.len()
+    }
+
+    /// Returns `true` if all the submitted requests have been
+    /// returned.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drives the in-flight requests until one of them completes,
+    /// and returns its output.
+    ///
+    /// Returns `None` if no request is in flight, or if none
+    /// completed within the given timeout, if any.
+    pub fn wait(
+        &mut self,
+        timeout: Option<Duration>,
+    ) -> Option<(RequestId, io::Result<ProcessOutput>)> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            if let Some(completion) = self.done.pop_front() {
+                return Some(completion);
+            }
+
+            if self.jobs.is_empty() {
+                return None;
+            }
+
+            let remaining =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            self.turn(remaining);
+
+            if self.done.is_empty() && remaining == Some(Duration::ZERO) {
+                return None;
+            }
+        }
+    }
+
+    /// Waits for the pipes and the children of all the in-flight
+    /// requests, then makes the ready ones progress.
+    ///
+    /// The wait does not last past the given timeout, nor past the
+    /// next deadline of a request.
+    fn turn(&mut self, mut timeout: Option<Duration>) {
+        let mut fds = Vec::new();
+        let mut ranges = Vec::with_capacity(self.jobs.len());
+
+        for (_, job) in &self.jobs {
+            let start = fds.len();
+            job.register(&mut fds);
+            ranges.push(start..fds.len());
+
+            if let Some(wakeup) = job.wakeup() {
+                timeout = Some(timeout.map_or(wakeup, |timeout| timeout.min(wakeup)));
+            }
+        }
+
+        // an interrupted wait only returns early
+        let res = match sys::poll(&mut fds, timeout) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => Ok(0),
+            res => res,
+        };
+
+        if let Err(err) = res {
+            // none of the requests can make progress anymore
+            for (id, _) in self.jobs.drain(..) {
+                let err = io::Error::new(err.kind(), err.to_string());
+                self.done.push_back((id, Err(err)));
+            }
+
+            return;
+        }
+
+        for ((_, job), range) in self.jobs.iter_mut().zip(ranges) {
+            job.progress(&fds[range]);
+        }
+
+        let mut i = 0;
+
+        while i < self.jobs.len() {
+            match self.jobs[i].1.finish() {
+                Some(output) => {
+                    let (id, _) = self.jobs.remove(i);
+                    self.done.push_back((id, output));
+                }
+                None => i += 1,
+            }
+        }
+    }
+}
+
+/// Returns the completed requests, blocking until one completes,
+/// until none is left in flight.
+impl Iterator for Poller {
+    type Item = (RequestId, io::Result<ProcessOutput>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.wait(None)
+    }
+}
+
+/// Kind of a request, telling which output it completes with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    Spawn,
+    SpawnOut,
+    SpawnIn,
+    SpawnPipeline,
+}
+
+impl Kind {
+    /// Returns `true` if the request captures the output of its last
+    /// process.
+    fn captures(self) -> bool {
+        matches!(self, Self::SpawnOut | Self::SpawnPipeline)
+    }
+}
+
+/// In-flight request, with its processes and the parent ends of
+/// their pipes.
+#[derive(Debug)]
+struct Job {
+    kind: Kind,
+    /// The spawned processes, in pipeline order: the last one is the
+    /// one whose exit status and output are returned.
+    procs: Vec<Proc>,
+    pipes: Vec<Pipe>,
+    capture: Capture,
+    recorder: Recorder,
+    captured: (bool, bool),
+    fds: BTreeMap<i32, Vec<u8>>,
+    terminator: Terminator,
+    start: Instant,
+    /// The first error the request ran into.
+    error: Option<io::Error>,
+}
+
+impl Job {
+    /// Spawns the processes of the given request.
+    fn start(input: ProcessInput) -> io::Result<Self> {
+        match input {
+            ProcessInput::Spawn { cmd } => Self::spawn(Kind::Spawn, vec![cmd], None),
+            ProcessInput::SpawnOut { cmd } => Self::spawn(Kind::SpawnOut, vec![cmd], None),
+            ProcessInput::SpawnIn { mut cmd, stdin } => {
+                if *cmd.stdin.get_or_insert(Stdio::Piped) != Stdio::Piped {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "cannot feed stdin of a command with non-piped stdin",
+                    ));
+                }
+
+                Self::spawn(Kind::SpawnIn, vec![cmd], Some(stdin))
+            }
+            ProcessInput::SpawnPipeline { cmds } if cmds.is_empty() => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty pipeline",
+            )),
+            ProcessInput::SpawnPipeline { cmds } => Self::spawn(Kind::SpawnPipeline, cmds, None),
+        }
+    }
+
+    /// Spawns the given commands, piping each process's stdout into
+    /// the next process's stdin.
+    ///
+    /// The capture, timeout and termination configuration of the
+    /// last command apply to the request.
+    fn spawn(kind: Kind, cmds: Vec<Command>, mut stdin: Option<Vec<u8>>) -> io::Result<Self> {
+        let n = cmds.len();
+        let mut job = Self {
+            kind,
+            procs: Vec::with_capacity(n),
+            pipes: Vec::new(),
+            capture: Capture::default(),
+            recorder: Recorder::new(&Capture::default()),
+            captured: (false, false),
+            fds: BTreeMap::new(),
+            terminator: Terminator::new(None, &Termination::default()),
+            start: Instant::now(),
+            error: None,
+        };
+        let mut prev_stdout = None;
+
+        for (i, mut cmd) in cmds.into_iter().enumerate() {
+            let is_last = i == n - 1;
+
+            if is_last && kind.captures() {
+                if cmd.pty.is_none() {
+                    cmd.stdout.get_or_insert(Stdio::Piped);
+                    cmd.stderr.get_or_insert(Stdio::Piped);
+                }
+
+                job.capture = cmd.capture.clone();
+                job.recorder = Recorder::new(&cmd.capture);
+            } else {
+                convert::reject_captured_fds(&cmd)?;
+            }
+
+            if is_last {
+                job.terminator = Terminator::new(cmd.timeout, &cmd.termination);
+                job.start = Instant::now();
+            }
+
+            let termination = cmd.termination;
+            let (mut command, mut setup) = convert::command(cmd)?;
+
+            if let Some(stdout) = prev_stdout.take() {
+                command.stdin(stdout);
+            }
+
+            if !is_last {
+                command.stdout(StdStdio::piped());
+            }
+
+            let mut child = command.spawn().map_err(|err| setup.spawn_error(err))?;
+            // closes the parent copies of the child stdio, like the terminal
+            drop(command);
+            drop(setup.child_ends);
+
+            for (pipe, bytes) in setup.inputs {
+                job.pipes
+                    .push(Pipe::new(pipe, Role::Input { bytes, written: 0 })?);
+            }
+
+            for (fd, pipe) in setup.captures {
+                job.fds.insert(fd, Vec::new());
+                job.pipes.push(Pipe::new(pipe, Role::Capture(fd))?);
+            }
+
+            let captures = is_last && kind.captures();
+
+            if let Some(terminal) = setup.pty {
+                let role = if captures {
+                    job.captured.0 = true;
+                    Role::Output(Stream::Stdout)
+                } else {
+                    Role::Drain
+                };
+
+                job.pipes.push(Pipe::new(terminal.0, role)?);
+            }
+
+            // closes stdin so that the child does not wait for more
+            // input, unless there is input to feed
+            let input = if is_last { stdin.take() } else { None };
+
+            if let (Some(pipe), Some(bytes)) = (child.stdin.take(), input) {
+                let role = Role::Input { bytes, written: 0 };
+                job.pipes.push(Pipe::new(pipe, role)?);
+            }
+
+            if is_last {
+                if let Some(pipe) = child.stdout.take() {
+                    let role = if captures {
+                        Role::Output(Stream::Stdout)
+                    } else {
+                        Role::Drain
+                    };
+                    job.captured.0 |= captures;
+                    job.pipes.push(Pipe::new(pipe, role)?);
+                }
+            } else {
+                prev_stdout = child.stdout.take();
+            }
+
+            if let Some(pipe) = child.stderr.take() {
+                let role = if captures {
+                    Role::Output(Stream::Stderr)
+                } else {
+                    Role::Drain
+                };
+                job.captured.1 |= captures;
+                job.pipes.push(Pipe::new(pipe, role)?);
+            }
+
+            job.procs.push(Proc::new(child, termination)?);
+        }
+
+        Ok(job)
+    }
+
+    /// Returns the last process of the request.
+    fn last(&self) -> &Proc {
+        self.procs
+            .last()
+            .expect("a request spawns at least a process")
+    }
+
+    /// Returns `true` if any process of the request is still
+    /// running.
+    fn running(&self) -> bool {
+        self.procs.iter().any(|proc| proc.status.is_none())
+    }
+
+    /// Adds to the given list the descriptors the request waits for:
+    /// its pipes first, then the pidfds of its running processes.
+    fn register(&self, fds: &mut Vec<libc::pollfd>) {
+        for pipe in &self.pipes {
+            let events = match pipe.role {
+                Role::Input { .. } => libc::POLLOUT,
+                _ => libc::POLLIN,
+            };
+
+            fds.push(pollfd(pipe.file.as_raw_fd(), events));
+        }
+
+        for proc in &self.procs {
+            if let (None, Some(pidfd)) = (&proc.status, proc.pidfd()) {
+                fds.push(pollfd(pidfd, libc::POLLIN));
+            }
+        }
+    }
+
+    /// Returns the time remaining until the request needs to make
+    /// progress on its own, if any.
+    ///
+    /// The request needs to be woken up to make its termination
+    /// sequence progress, or to poll processes that have no pidfd.
+    fn wakeup(&self) -> Option<Duration> {
+        let mut wakeup = None;
+
+        if self.running() {
+            if let Some(deadline) = self.terminator.deadline() {
+                wakeup = Some(deadline.saturating_sub(self.start.elapsed()));
+            }
+        }
+
+        let polled = self
+            .procs
+            .iter()
+            .any(|proc| proc.status.is_none() && proc.pidfd().is_none());
+
+        if polled {
+            wakeup = Some(wakeup.map_or(POLL_INTERVAL, |wakeup| wakeup.min(POLL_INTERVAL)));
+        }
+
+        wakeup
+    }
+
+    /// Serves the ready pipes, reaps the exited processes and makes
+    /// the termination sequence progress.
+    ///
+    /// The given descriptors are the ones added by
+    /// [`Job::register`], with their returned events.
+    fn progress(&mut self, fds: &[libc::pollfd]) {
+        let (pipes, pidfds) = fds.split_at(self.pipes.len());
+        let mut pipes = pipes.iter().map(|fd| fd.revents != 0);
+
+        self.pipes = std::mem::take(&mut self.pipes)
+            .into_iter()
+            .filter_map(|mut pipe| {
+                if pipes.next() == Some(false) {
+                    return Some(pipe);
+                }
+
+                match self.serve(&mut pipe) {
+                    Ok(true) => Some(pipe),
+                    Ok(false) => None,
+                    Err(err) => {
+                        self.fail(err);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let mut pidfds = pidfds.iter().map(|fd| fd.revents != 0);
+
+        for i in 0..self.procs.len() {
+            let proc = &mut self.procs[i];
+
+            if proc.status.is_some() {
+                continue;
+            }
+
+            // processes with pidfd are only reaped once it is ready
+            if proc.pidfd().is_some() && pidfds.next() != Some(true) {
+                continue;
+            }
+
+            proc.status = match proc.child.try_wait() {
+                Ok(None) => continue,
+                Ok(status) => status,
+                // the process cannot be waited for anymore
+                Err(err) => {
+                    self.error.get_or_insert(err);
+                    Some(StdExitStatus::default())
+                }
+            };
+
+            // the bytes left unread when the process exits are
+            // discarded
+            if i == self.procs.len() - 1 {
+                self.pipes.retain(|pipe| !pipe.role.is_input());
+            }
+        }
+
+        if self.running() {
+            let signal = self.terminator.poll(self.start.elapsed());
+            self.send(signal);
+        }
+    }
+
+    /// Reads from or writes to the given ready pipe.
+    ///
+    /// Returns `false` once the pipe is done with.
+    fn serve(&mut self, pipe: &mut Pipe) -> io::Result<bool> {
+        if let Role::Input { bytes, written } = &mut pipe.role {
+            return match pipe.file.write(&bytes[*written..]) {
+                Ok(n) => {
+                    *written += n;
+                    Ok(*written < bytes.len())
+                }
+                // the process is free not to read its input
+                Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+                Err(err) if is_transient(&err) => Ok(true),
+                Err(err) => Err(err),
+            };
+        }
+
+        let mut buf = [0; CHUNK];
+
+        let chunk = match pipe.file.read(&mut buf) {
+            Ok(0) => return Ok(false),
+            Ok(n) => &buf[..n],
+            // reading from the master side of a pseudo-terminal fails
+            // once all its slave sides got closed
+            Err(err) if err.raw_os_error() == Some(libc::EIO) => return Ok(false),
+            Err(err) if is_transient(&err) => return Ok(true),
+            Err(err) => return Err(err),
+        };
+
+        match pipe.role {
+            Role::Output(stream) => {
+                if self.capture.tees(stream) {
+                    tee(stream, chunk)?;
+                }
+
+                let elapsed = self.start.elapsed();
+
+                if self.recorder.record(stream, elapsed, chunk) {
+                    debug!("{stream:?} exceeded its limit, terminating process");
+                    let signal = self.terminator.terminate(elapsed);
+                    self.send(signal);
+                }
+            }
+            Role::Capture(fd) => {
+                if let Some(bytes) = self.fds.get_mut(&fd) {
+                    bytes.extend_from_slice(chunk);
+                }
+            }
+            Role::Drain | Role::Input { .. } => (),
+        }
+
+        Ok(true)
+    }
+
+    /// Records the given error, and terminates the running processes
+    /// so that the request completes with it.
+    fn fail(&mut self, err: io::Error) {
+        debug!("request failed, terminating process: {err}");
+        self.error.get_or_insert(err);
+
+        if self.running() {
+            let signal = self.terminator.terminate(self.start.elapsed());
+            self.send(signal);
+        }
+    }
+
+    /// Sends the given signal, if any, to each running process or to
+    /// its whole process tree.
+    fn send(&mut self, signal: Option<Signal>) {
+        let Some(signal) = signal else {
+            return;
+        };
+
+        for proc in &self.procs {
+            if proc.status.is_some() {
+                continue;
+            }
+
+            debug!("sends {signal:?} to {:?}", proc.target);
+
+            if let Err(err) = proc.target.signal(signal) {
+                self.error.get_or_insert(err);
+            }
+        }
+    }
+
+    /// Returns the output of the request, once all its pipes got
+    /// closed and all its processes got reaped.
+    fn finish(&mut self) -> Option<io::Result<ProcessOutput>> {
+        if !self.pipes.is_empty() || self.running() {
+            return None;
+        }
+
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+
+        if self.terminator.timed_out() {
+            return Some(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "process ran past its timeout",
+            )));
+        }
+
+        Some(Ok(self.output()))
+    }
+
+    /// Builds the output of the request, once its last process got
+    /// reaped.
+    fn output(&mut self) -> ProcessOutput {
+        let status = self.last().status.and_then(|status| status.code());
+        let status = ExitStatus::new(status);
+        let recorder = std::mem::replace(&mut self.recorder, Recorder::new(&self.capture));
+        let recording = recorder.finish(self.captured.0, self.captured.1);
+        let fds = std::mem::take(&mut self.fds);
+
+        match self.kind {
+            Kind::Spawn => ProcessOutput::Spawned { status },
+            Kind::SpawnIn => ProcessOutput::SpawnedIn { status },
+            Kind::SpawnOut => ProcessOutput::SpawnedOut {
+                status,
+                stdout: recording.stdout,
+                stderr: recording.stderr,
+                truncated: recording.truncated,
+                transcript: recording.transcript,
+                fds,
+            },
+            Kind::SpawnPipeline => ProcessOutput::SpawnedPipeline {
+                status,
+                stdout: recording.stdout,
+                stderr: recording.stderr,
+                truncated: recording.truncated,
+                transcript: recording.transcript,
+                fds,
+            },
+        }
+    }
+}
+
+/// Spawned process of a request.
+#[derive(Debug)]
+struct Proc {
+    child: Child,
+    target: kill::Target,
+    termination: Termination,
+    /// The exit status, once reaped.
+    status: Option<StdExitStatus>,
+}
+
+impl Proc {
+    /// Watches the given spawned child.
+    ///
+    /// The child is killed and reaped if it cannot be watched, since
+    /// it could not be terminated safely.
+    fn new(mut child: Child, termination: Termination) -> io::Result<Self> {
+        let target = match kill::Target::new(child.id(), termination.tree) {
+            Ok(target) => target,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err);
+            }
+        };
+
+        Ok(Self {
+            child,
+            target,
+            termination,
+            status: None,
+        })
+    }
+
+    /// Returns the pidfd of the process, if supported.
+    #[cfg(target_os = "linux")]
+    fn pidfd(&self) -> Option<RawFd> {
+        self.target.pidfd().map(AsRawFd::as_raw_fd)
+    }
+
+    /// Returns the pidfd of the process, which is not supported on
+    /// this platform.
+    #[cfg(not(target_os = "linux"))]
+    fn pidfd(&self) -> Option<RawFd> {
+        None
+    }
+}
+
+impl Drop for Proc {
+    fn drop(&mut self) {
+        if self.status.is_some() || self.termination.on_drop == OnDrop::Detach {
+            return;
+        }
+
+        debug!("kills and reaps dropped process {}", self.child.id());
+        let _ = self.target.signal(Signal::Kill);
+        let _ = self.child.wait();
+    }
+}
+
+/// Parent end of a pipe, or master side of a pseudo-terminal, served
+/// by the event loop.
+#[derive(Debug)]
+struct Pipe {
+    file: File,
+    role: Role,
+}
+
+impl Pipe {
+    /// Switches the given parent end to non-blocking mode, so that
+    /// serving it never blocks the other requests.
+    fn new(fd: impl Into<OwnedFd>, role: Role) -> io::Result<Self> {
+        let file = File::from(fd.into());
+        let fd = file.as_raw_fd();
+
+        // SAFETY: fcntl does not access memory, and the descriptor is
+        // owned by the file.
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+
+            if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(Self { file, role })
+    }
+}
+
+/// What a [`Pipe`] is used for.
+#[derive(Debug)]
+enum Role {
+    /// Captured standard stream, or captured terminal output as
+    /// stdout.
+    Output(Stream),
+    /// Captured extra file descriptor, by child descriptor number.
+    Capture(i32),
+    /// Uncaptured stream, drained and discarded so that the process
+    /// does not block on it.
+    Drain,
+    /// Input fed to the process, with the count of bytes already
+    /// written.
+    Input { bytes: Vec<u8>, written: usize },
+}
+
+impl Role {
+    fn is_input(&self) -> bool {
+        matches!(self, Self::Input { .. })
+    }
+}
+
+/// Returns `true` if the given error only means that the pipe is not
+/// ready anymore.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    )
+}
@@ -0,0 +1,460 @@
+//! Record/replay process runtime backed by on-disk cassettes.
+//!
+//! In record mode, a [`Cassette`] delegates requests to the
+//! [`std`](super::std) runtime, and stores each interaction in a
+//! human-readable cassette file. In replay mode, it serves the
+//! recorded outputs back without spawning any process, which makes
+//! tests deterministic.
+//!
+//! A cassette looks like this:
+//!
+//! ```text
+//! # io-process cassette
+//!
+//! SpawnIn
+//!   command: grep foo
+//!     env: LANG=C
+//!   stdin: "foo\nbar\n"
+//! => SpawnedIn
+//!   status: 0
+//!
+//! SpawnOut
+//!   command: echo hello
+//! => SpawnedOut
+//!   status: 0
+//!   stdout: "hello\n"
+//!   stderr: ""
+//! ```
+//!
+//! Each interaction starts with the description of the input, matched
+//! the same way the [`mock`](super::mock) runtime matches inputs,
+//! followed by the recorded output. Streams that were not captured
+//! are omitted, and transcripts are not recorded. Program names,
+//! arguments and environment variables that contain other characters
+//! than letters, digits and `-_./:@%+,` are double-quoted and escaped
+//! like the streams.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use thiserror::Error;
+
+use super::describe::{self, escape, unescape};
+use crate::{
+    capture::Truncated,
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+};
+
+/// Header written at the top of recorded cassettes.
+const HEADER: &str = "# io-process cassette\n";
+
+/// Error emitted by the [`Cassette`] runtime, wrapped into an
+/// [`io::Error`].
+#[derive(Debug, Error)]
+pub enum CassetteError {
+    /// The cassette file is not valid.
+    #[error("Invalid cassette at line {line}: {reason}")]
+    Parse { line: usize, reason: String },
+
+    /// The input does not match the next recorded interaction.
+    #[error("Unexpected process input (- recorded, + actual):\n{diff}")]
+    Mismatch { diff: String },
+
+    /// The input came after all recorded interactions got replayed.
+    #[error("Unexpected process input, no recorded interaction left:\n{input}")]
+    Exhausted { input: String },
+}
+
+/// Mode of a [`Cassette`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Mode {
+    /// Spawn real processes, and record the interactions, replacing
+    /// the existing cassette if any.
+    Record,
+    /// Serve the recorded interactions, without spawning any
+    /// process.
+    Replay,
+    /// Replay if the cassette exists, otherwise record it.
+    #[default]
+    Auto,
+}
+
+/// Recorded interaction of a cassette.
+#[derive(Debug)]
+struct Interaction {
+    input: String,
+    output: ProcessOutput,
+}
+
+/// Record/replay runtime.
+#[derive(Debug)]
+pub struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: VecDeque<Interaction>,
+}
+
+impl Cassette {
+    /// Opens the cassette at the given path, in the given mode.
+    ///
+    /// In record mode, the cassette is created, or truncated if it
+    /// already exists. In replay mode, it is read and parsed.
+    pub fn open(path: impl AsRef<Path>, mode: Mode) -> io::Result<Self> {
+        let path = path.as_ref().to_owned();
+
+        let mode = match mode {
+            Mode::Auto if path.exists() => Mode::Replay,
+            Mode::Auto => Mode::Record,
+            mode => mode,
+        };
+
+        debug!("opens cassette {} in {mode:?} mode", path.display());
+
+        let interactions = match mode {
+            Mode::Replay => parse(&fs::read_to_string(&path)?)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            _ => {
+                fs::write(&path, HEADER)?;
+                VecDeque::new()
+            }
+        };
+
+        Ok(Self {
+            path,
+            mode,
+            interactions,
+        })
+    }
+
+    /// Returns the effective mode of the cassette, either
+    /// [`Mode::Record`] or [`Mode::Replay`].
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Processes a [`ProcessInput`] request, either by spawning the
+    /// process and recording the interaction, or by replaying the
+    /// next recorded interaction.
+    ///
+    /// Failed requests are not recorded. When replaying, fails with a
+    /// [`CassetteError`] if the input does not match the recorded
+    /// one.
+    pub fn handle(&mut self, input: ProcessInput) -> io::Result<ProcessOutput> {
+        let description = describe::input(&input);
+
+        if self.mode == Mode::Replay {
+            return self.replay(description);
+        }
+
+        let output = super::std::handle(input)?;
+
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(render(&description, &output).as_bytes())?;
+
+        Ok(output)
+    }
+
+    fn replay(&mut self, input: String) -> io::Result<ProcessOutput> {
+        let Some(interaction) = self.interactions.pop_front() else {
+            return Err(io::Error::other(CassetteError::Exhausted { input }));
+        };
+
+        if interaction.input != input {
+            let diff = describe::diff(&interaction.input, &input);
+            self.interactions.push_front(interaction);
+            return Err(io::Error::other(CassetteError::Mismatch { diff }));
+        }
+
+        Ok(interaction.output)
+    }
+}
+
+/// Renders an interaction, followed by an empty line.
+fn render(input: &str, output: &ProcessOutput) -> String {
+    let mut rendered = format!("\n{input}\n=> ");
+
+    let status = match output {
+        ProcessOutput::Spawned { status } => {
+            rendered.push_str("Spawned");
+            status
+        }
+        ProcessOutput::SpawnedIn { status } => {
+            rendered.push_str("SpawnedIn");
+            status
+        }
+        ProcessOutput::SpawnedOut { status, .. } => {
+            rendered.push_str("SpawnedOut");
+            status
+        }
+        ProcessOutput::SpawnedPipeline { status, .. } => {
+            rendered.push_str("SpawnedPipeline");
+            status
+        }
+    };
+
+    match status.code() {
+        Some(code) => write!(rendered, "\n  status: {code}"),
+        None => write!(rendered, "\n  status: none"),
+    }
+    .unwrap();
+
+    if let ProcessOutput::SpawnedOut {
+        stdout,
+        stderr,
+        truncated,
+        fds,
+        ..
+    }
+    | ProcessOutput::SpawnedPipeline {
+        stdout,
+        stderr,
+        truncated,
+        fds,
+        ..
+    } = output
+    {
+        if let Some(stdout) = stdout {
+            write!(rendered, "\n  stdout: {}", escape(stdout)).unwrap();
+        }
+
+        if let Some(stderr) = stderr {
+            write!(rendered, "\n  stderr: {}", escape(stderr)).unwrap();
+        }
+
+        match (truncated.stdout, truncated.stderr) {
+            (false, false) => (),
+            (true, false) => rendered.push_str("\n  truncated: stdout"),
+            (false, true) => rendered.push_str("\n  truncated: stderr"),
+            (true, true) => rendered.push_str("\n  truncated: stdout, stderr"),
+        }
+
+        for (fd, bytes) in fds {
+            write!(rendered, "\n  fd {fd}: {}", escape(bytes)).unwrap();
+        }
+    }
+
+    rendered.push('\n');
+    rendered
+}
+
+/// Parses the interactions of a cassette.
+fn parse(cassette: &str) -> Result<VecDeque<Interaction>, CassetteError> {
+    let mut interactions = VecDeque::new();
+    let mut lines = cassette.lines().enumerate().map(|(i, line)| (i + 1, line));
+
+    let err = |line: usize, reason: &str| CassetteError::Parse {
+        line,
+        reason: reason.to_owned(),
+    };
+
+    while let Some((n, line)) = lines.next() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // the input goes up to the output marker
+        let mut input = line.to_owned();
+
+        let kind = loop {
+            let Some((n, line)) = lines.next() else {
+                return Err(err(n, "missing output of interaction"));
+            };
+
+            match line.strip_prefix("=> ") {
+                Some(kind) => break (n, kind),
+                None => {
+                    input.push('\n');
+                    input.push_str(line);
+                }
+            }
+        };
+
+        let mut status = None;
+        let mut stdout = None;
+        let mut stderr = None;
+        let mut truncated = Truncated::default();
+        let mut fds = BTreeMap::new();
+
+        for (n, line) in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+
+            let Some((key, val)) = line.strip_prefix("  ").and_then(|l| l.split_once(": ")) else {
+                return Err(err(n, "expected an output field"));
+            };
+
+            let bytes = || unescape(val).ok_or_else(|| err(n, "invalid quoted bytes"));
+
+            match key {
+                "status" if val == "none" => status = Some(ExitStatus::new(None)),
+                "status" => {
+                    let code = val.parse().map_err(|_| err(n, "invalid exit code"))?;
+                    status = Some(ExitStatus::new(Some(code)));
+                }
+                "stdout" => stdout = Some(bytes()?),
+                "stderr" => stderr = Some(bytes()?),
+                "truncated" => {
+                    for stream in val.split(", ") {
+                        match stream {
+                            "stdout" => truncated.stdout = true,
+                            "stderr" => truncated.stderr = true,
+                            _ => return Err(err(n, "invalid truncated stream")),
+                        }
+                    }
+                }
+                key => {
+                    let fd = key
+                        .strip_prefix("fd ")
+                        .and_then(|fd| fd.parse().ok())
+                        .ok_or_else(|| err(n, "unknown output field"))?;
+                    fds.insert(fd, bytes()?);
+                }
+            }
+        }
+
+        let (n, kind) = kind;
+        let status = status.ok_or_else(|| err(n, "missing exit status"))?;
+        let transcript = None;
+
+        let output = match kind {
+            "Spawned" => ProcessOutput::Spawned { status },
+            "SpawnedIn" => ProcessOutput::SpawnedIn { status },
+            "SpawnedOut" => ProcessOutput::SpawnedOut {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            },
+            "SpawnedPipeline" => ProcessOutput::SpawnedPipeline {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            },
+            _ => return Err(err(n, "unknown output kind")),
+        };
+
+        interactions.push_back(Interaction { input, output });
+    }
+
+    Ok(interactions)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        capture::Truncated,
+        command::Command,
+        io::{ProcessInput, ProcessOutput},
+        status::ExitStatus,
+    };
+
+    use super::{CassetteError, describe, parse, render};
+
+    #[test]
+    fn roundtrip() {
+        let input = "SpawnPipeline\n  command: ls\n    env: LANG=C\n  command: wc -l";
+        let output = ProcessOutput::SpawnedPipeline {
+            status: ExitStatus::new(None),
+            stdout: Some(b"3\n".to_vec()),
+            stderr: None,
+            truncated: Truncated {
+                stdout: true,
+                stderr: false,
+            },
+            transcript: None,
+            fds: BTreeMap::from([(3, b"\xff".to_vec())]),
+        };
+
+        let rendered = render(input, &output);
+        assert_eq!(
+            "\nSpawnPipeline\n  command: ls\n    env: LANG=C\n  command: wc -l\n\
+             => SpawnedPipeline\n  status: none\n  stdout: \"3\\n\"\n\
+             \x20 truncated: stdout\n  fd 3: \"\\xff\"\n",
+            rendered,
+        );
+
+        let mut interactions = parse(&rendered).unwrap();
+        let interaction = interactions.pop_front().unwrap();
+        assert_eq!(input, interaction.input);
+
+        let ProcessOutput::SpawnedPipeline {
+            status,
+            stdout,
+            stderr,
+            truncated,
+            fds,
+            ..
+        } = interaction.output
+        else {
+            panic!("unexpected output: {:?}", interaction.output);
+        };
+
+        assert_eq!(None, status.code());
+        assert_eq!(Some(b"3\n".to_vec()), stdout);
+        assert_eq!(None, stderr);
+        assert!(truncated.stdout);
+        assert_eq!(Some(&b"\xff".to_vec()), fds.get(&3));
+    }
+
+    #[test]
+    fn roundtrip_multiline() {
+        let mut cmd = Command::new("printf");
+        cmd.arg("%s\n").arg("first\n=> Spawned\n  status: 0\n");
+        cmd.env("MSG", "a\n=> SpawnedOut\n\nb");
+        let input = describe::input(&ProcessInput::SpawnOut { cmd });
+
+        assert_eq!(
+            "SpawnOut\n  command: printf \"%s\\n\" \"first\\n=> Spawned\\n  status: 0\\n\"\n\
+             \x20   env: MSG=\"a\\n=> SpawnedOut\\n\\nb\"",
+            input,
+        );
+
+        let output = ProcessOutput::SpawnedOut {
+            status: ExitStatus::new(Some(0)),
+            stdout: Some(b"ok".to_vec()),
+            stderr: None,
+            truncated: Truncated::default(),
+            transcript: None,
+            fds: BTreeMap::new(),
+        };
+
+        let mut interactions = parse(&render(&input, &output)).unwrap();
+        let interaction = interactions.pop_front().unwrap();
+        assert_eq!(input, interaction.input);
+        assert!(interactions.is_empty());
+
+        let ProcessOutput::SpawnedOut { status, stdout, .. } = interaction.output else {
+            panic!("unexpected output: {:?}", interaction.output);
+        };
+
+        assert_eq!(Some(0), status.code());
+        assert_eq!(Some(b"ok".to_vec()), stdout);
+    }
+
+    #[test]
+    fn invalid() {
+        let err = parse("Spawn\n  command: true\n=> Spawned\n  stdout: nope\n").unwrap_err();
+        let CassetteError::Parse { line, .. } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(4, line);
+
+        let err = parse("Spawn\n  command: true\n").unwrap_err();
+        assert!(matches!(err, CassetteError::Parse { .. }));
+    }
+}
@@ -0,0 +1,924 @@
+//! Deterministic simulated runtime, running fake programs instead of
+//! spawning processes.
+//!
+//! A [`Simulator`] holds a registry of programs implemented as Rust
+//! closures. Each [`ProcessInput`] is processed by calling the
+//! programs of its commands with their [`Invocation`], then by
+//! playing the returned [`Outcome`] the way a real process would: the
+//! [`Stdio`] and extra file descriptors of the commands are honored
+//! against an in-memory file system, pipeline stages are fed with the
+//! stdout of the previous stage, and captured streams are recorded
+//! according to the [`Capture`] configuration.
+//!
+//! Each simulated process has its own clock, which starts at zero
+//! when the process gets spawned and only moves forward with
+//! [`Outcome::sleep`]. Timeouts, output limits and transcripts are
+//! therefore deterministic, which makes the simulator suitable for
+//! fuzzing coroutines.
+//!
+//! The simulator performs no I/O at all, which makes it usable in
+//! `no_std` environments.
+//!
+//! [`Capture`]: crate::capture::Capture
+
+use alloc::{
+    borrow::Cow,
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt, time::Duration};
+
+use thiserror::Error;
+
+use crate::{
+    capture::{Capture, Recorder, Recording, Stream},
+    command::Command,
+    fd::ExtraFd,
+    io::{ProcessInput, ProcessOutput},
+    pty::Pty,
+    status::ExitStatus,
+    stdio::Stdio,
+};
+
+/// Error emitted by the [`Simulator`] runtime.
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+pub enum SimError {
+    /// No program is registered under the program of a command.
+    #[error("Program not found: {program}")]
+    NotFound { program: String },
+
+    /// A file read by a command does not exist.
+    #[error("Cannot open {stream} file {path}: file not found")]
+    FileNotFound { stream: String, path: String },
+
+    /// The input, or one of its commands, cannot be processed.
+    #[error("Invalid process input: {reason}")]
+    InvalidInput { reason: String },
+
+    /// The process ran past its timeout.
+    #[error("Process ran past its timeout")]
+    TimedOut,
+}
+
+#[cfg(feature = "std")]
+impl From<SimError> for std::io::Error {
+    fn from(err: SimError) -> Self {
+        use std::io::ErrorKind;
+
+        let kind = match err {
+            SimError::NotFound { .. } | SimError::FileNotFound { .. } => ErrorKind::NotFound,
+            SimError::InvalidInput { .. } => ErrorKind::InvalidInput,
+            SimError::TimedOut => ErrorKind::TimedOut,
+        };
+
+        std::io::Error::new(kind, err)
+    }
+}
+
+/// Simulated program, answering invocations with outcomes.
+type Program = Box<dyn FnMut(&Invocation) -> Outcome>;
+
+/// Invocation of a simulated program.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Invocation {
+    /// The program, as registered in the simulator.
+    pub program: String,
+    /// The arguments passed to the program.
+    pub args: Vec<String>,
+    /// The environment variables of the simulator, overridden by the
+    /// ones explicitly set on the command.
+    pub envs: BTreeMap<String, String>,
+    /// The working directory of the process, if set.
+    pub current_dir: Option<String>,
+    /// The whole stdin of the process.
+    pub stdin: Vec<u8>,
+    /// The bytes readable from the extra file descriptors, by
+    /// descriptor number.
+    pub fds: BTreeMap<i32, Vec<u8>>,
+    /// The pseudo-terminal of the process, if it runs in PTY mode.
+    pub pty: Option<Pty>,
+}
+
+/// Step of a simulated process.
+#[derive(Clone, Debug)]
+enum Event {
+    Write(Stream, Vec<u8>),
+    WriteFd(i32, Vec<u8>),
+    Sleep(Duration),
+}
+
+/// Outcome of a simulated process, returned by its program.
+///
+/// Describes what the process does, step by step, before exiting
+/// with its status. Exits with `0` and does nothing by default.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    status: ExitStatus,
+    events: Vec<Event>,
+}
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Self {
+            status: ExitStatus::new(Some(0)),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Outcome {
+    /// Creates a new outcome, exiting with `0`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the exit status.
+    ///
+    /// The status is replaced by `None` if the process gets killed,
+    /// because of a timeout or of an output limit.
+    pub fn status(&mut self, status: ExitStatus) -> &mut Self {
+        self.status = status;
+        self
+    }
+
+    /// Writes the given bytes to stdout.
+    pub fn stdout(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.events.push(Event::Write(Stream::Stdout, bytes.into()));
+        self
+    }
+
+    /// Writes the given bytes to stderr.
+    pub fn stderr(&mut self, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.events.push(Event::Write(Stream::Stderr, bytes.into()));
+        self
+    }
+
+    /// Writes the given bytes to an extra file descriptor.
+    ///
+    /// Bytes written to a descriptor that is not configured, or that
+    /// is configured for reading, are discarded.
+    pub fn fd(&mut self, fd: i32, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.events.push(Event::WriteFd(fd, bytes.into()));
+        self
+    }
+
+    /// Moves the clock of the process forward.
+    pub fn sleep(&mut self, duration: Duration) -> &mut Self {
+        self.events.push(Event::Sleep(duration));
+        self
+    }
+}
+
+/// Deterministic simulated runtime.
+///
+/// Processes [`ProcessInput`] requests by running the programs
+/// registered with [`Simulator::program`]. Files read and written by
+/// the commands live in memory, see [`Simulator::file`], and the
+/// streams inherited from the simulator are collected, see
+/// [`Simulator::parent_stdout`].
+#[derive(Default)]
+pub struct Simulator {
+    programs: BTreeMap<String, Program>,
+    envs: BTreeMap<String, String>,
+    files: BTreeMap<String, Vec<u8>>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl Simulator {
+    /// Creates a new simulator, without any program.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a program under the given name.
+    ///
+    /// Commands are matched against the registered names by their
+    /// exact program, after expansion. Registering a name again
+    /// replaces its program.
+    pub fn program(
+        &mut self,
+        name: impl ToString,
+        program: impl FnMut(&Invocation) -> Outcome + 'static,
+    ) -> &mut Self {
+        self.programs.insert(name.to_string(), Box::new(program));
+        self
+    }
+
+    /// Sets an environment variable inherited by all processes.
+    pub fn env(&mut self, key: impl ToString, val: impl ToString) -> &mut Self {
+        self.envs.insert(key.to_string(), val.to_string());
+        self
+    }
+
+    /// Creates or replaces a file of the in-memory file system.
+    pub fn file(&mut self, path: impl ToString, bytes: impl Into<Vec<u8>>) -> &mut Self {
+        self.files.insert(path.to_string(), bytes.into());
+        self
+    }
+
+    /// Returns the content of a file of the in-memory file system.
+    pub fn get_file(&self, path: &str) -> Option<&[u8]> {
+        self.files.get(path).map(Vec::as_slice)
+    }
+
+    /// Returns the bytes written to the stdout of the simulator by the
+    /// processes inheriting it or teeing their output to it.
+    pub fn parent_stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// Returns the bytes written to the stderr of the simulator by the
+    /// processes inheriting it or teeing their output to it.
+    pub fn parent_stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Processes a [`ProcessInput`] request, running the programs of
+    /// its commands.
+    ///
+    /// Fails the same way the std runtime does, for example with
+    /// [`SimError::TimedOut`] if a process ran past its timeout. The
+    /// side effects of the processes, like written files, are kept.
+    pub fn handle(&mut self, input: ProcessInput) -> Result<ProcessOutput, SimError> {
+        match input {
+            ProcessInput::Spawn { cmd } => {
+                let process = self.run(cmd, None, Role::Spawn)?;
+                let status = process.status;
+                Ok(ProcessOutput::Spawned { status })
+            }
+            ProcessInput::SpawnOut { cmd } => {
+                let process = self.run(cmd, None, Role::Capture)?;
+                let Recording {
+                    stdout,
+                    stderr,
+                    truncated,
+                    transcript,
+                } = process.recording;
+
+                Ok(ProcessOutput::SpawnedOut {
+                    status: process.status,
+                    stdout,
+                    stderr,
+                    truncated,
+                    transcript,
+                    fds: process.fds,
+                })
+            }
+            ProcessInput::SpawnIn { cmd, stdin } => {
+                if !matches!(cmd.stdin, None | Some(Stdio::Piped)) {
+                    let reason = "cannot feed stdin of a command with non-piped stdin";
+                    return Err(invalid(reason));
+                }
+
+                let process = self.run(cmd, Some(stdin), Role::Spawn)?;
+                let status = process.status;
+                Ok(ProcessOutput::SpawnedIn { status })
+            }
+            ProcessInput::SpawnPipeline { cmds } => self.spawn_pipeline(cmds),
+        }
+    }
+
+    /// Runs the stages of a pipeline one after the other, feeding
+    /// each stage with the stdout of the previous one.
+    fn spawn_pipeline(&mut self, mut cmds: Vec<Command>) -> Result<ProcessOutput, SimError> {
+        let Some(last) = cmds.pop() else {
+            return Err(invalid("empty pipeline"));
+        };
+
+        let mut stdin = None;
+        let mut fds = BTreeMap::new();
+
+        for cmd in cmds {
+            let mut process = self.run(cmd, stdin.take(), Role::Stage)?;
+            stdin = Some(process.pipe);
+            fds.append(&mut process.fds);
+        }
+
+        let mut process = self.run(last, stdin, Role::Capture)?;
+        fds.append(&mut process.fds);
+
+        let Recording {
+            stdout,
+            stderr,
+            truncated,
+            transcript,
+        } = process.recording;
+
+        Ok(ProcessOutput::SpawnedPipeline {
+            status: process.status,
+            stdout,
+            stderr,
+            truncated,
+            transcript,
+            fds,
+        })
+    }
+
+    /// Runs the program of the given command, fed with the given
+    /// stdin instead of the configured one, if any.
+    fn run(
+        &mut self,
+        mut cmd: Command,
+        stdin: Option<Vec<u8>>,
+        role: Role,
+    ) -> Result<Exited, SimError> {
+        let captured = role == Role::Capture;
+
+        if !captured && cmd.fds.values().any(|fd| *fd == ExtraFd::Capture) {
+            let reason = "cannot capture extra fds of a command without output capture";
+            return Err(invalid(reason));
+        }
+
+        #[cfg(unix)]
+        if cmd.process_group.is_some() && (cmd.setsid || cmd.pty.is_some()) {
+            let reason = "cannot run a command in a new session and in a process group";
+            return Err(invalid(reason));
+        }
+
+        if captured && cmd.pty.is_none() {
+            cmd.stdout.get_or_insert(Stdio::Piped);
+            cmd.stderr.get_or_insert(Stdio::Piped);
+        }
+
+        let pty = cmd.pty.is_some();
+        let configured = self.open_stdin(cmd.stdin.take())?;
+        let mut stdout = self.open_sink(cmd.stdout.take(), Stream::Stdout, pty, captured)?;
+
+        // the pipe to the next stage replaces the configured stdout,
+        // which still gets opened
+        if role == Role::Stage {
+            stdout = Sink::Pipe;
+        }
+
+        let stderr = match cmd.stderr.take() {
+            Some(Stdio::Stdout) => stdout.clone(),
+            stderr => self.open_sink(stderr, Stream::Stderr, pty, captured)?,
+        };
+
+        let mut readable = BTreeMap::new();
+        let mut sinks = BTreeMap::new();
+        let mut fds = BTreeMap::new();
+
+        for (fd, cfg) in core::mem::take(&mut cmd.fds) {
+            if fd <= 2 {
+                return Err(invalid(format!("cannot configure fd {fd} as an extra fd")));
+            }
+
+            let name = format!("fd {fd}");
+
+            match cfg {
+                ExtraFd::Input(bytes) => {
+                    readable.insert(fd, bytes);
+                }
+                ExtraFd::Capture => {
+                    fds.insert(fd, Vec::new());
+                    sinks.insert(fd, Sink::CaptureFd(fd));
+                }
+                ExtraFd::ReadFile(path) => {
+                    readable.insert(fd, self.read(&name, path)?);
+                }
+                ExtraFd::WriteFile(path) => {
+                    self.files.insert(path.clone(), Vec::new());
+                    sinks.insert(fd, Sink::File(path));
+                }
+                ExtraFd::AppendFile(path) => {
+                    self.files.entry(path.clone()).or_default();
+                    sinks.insert(fd, Sink::File(path));
+                }
+            }
+        }
+
+        let mut envs = self.envs.clone();
+        envs.extend(cmd.envs.take().unwrap_or_default());
+
+        let invocation = Invocation {
+            program: cmd.get_program().into_owned(),
+            args: cmd
+                .get_args()
+                .unwrap_or_default()
+                .into_iter()
+                .map(Cow::into_owned)
+                .collect(),
+            envs,
+            current_dir: cmd.current_dir.take(),
+            stdin: stdin.unwrap_or(configured),
+            fds: readable,
+            pty: cmd.pty.take(),
+        };
+
+        let Some(program) = self.programs.get_mut(&invocation.program) else {
+            let program = invocation.program;
+            return Err(SimError::NotFound { program });
+        };
+
+        let outcome = program(&invocation);
+
+        // pipeline stages other than the last one are not watched
+        let timeout = match role {
+            Role::Stage => None,
+            _ => cmd.timeout,
+        };
+
+        let mut output = Output {
+            recorder: Recorder::new(&cmd.capture),
+            capture: cmd.capture,
+            pipe: Vec::new(),
+            fds,
+        };
+
+        let mut elapsed = Duration::ZERO;
+        let mut killed = false;
+        let mut timed_out = false;
+
+        for event in outcome.events {
+            let kill = match event {
+                Event::Write(Stream::Stdout, bytes) => {
+                    self.write(&mut output, &stdout, elapsed, &bytes)
+                }
+                Event::Write(Stream::Stderr, bytes) => {
+                    self.write(&mut output, &stderr, elapsed, &bytes)
+                }
+                Event::WriteFd(fd, bytes) => match sinks.get(&fd) {
+                    Some(sink) => self.write(&mut output, sink, elapsed, &bytes),
+                    None => false,
+                },
+                Event::Sleep(duration) => {
+                    elapsed = elapsed.saturating_add(duration);
+                    // the real runtimes terminate the process at the deadline
+                    timed_out = timeout.is_some_and(|timeout| elapsed >= timeout);
+                    timed_out
+                }
+            };
+
+            if kill {
+                killed = true;
+                break;
+            }
+        }
+
+        if timed_out {
+            return Err(SimError::TimedOut);
+        }
+
+        let stdout = pty || matches!(stdout, Sink::Capture(_));
+        let stderr = matches!(stderr, Sink::Capture(Stream::Stderr));
+
+        let status = if killed {
+            ExitStatus::new(None)
+        } else {
+            outcome.status
+        };
+
+        Ok(Exited {
+            status,
+            recording: output.recorder.finish(captured && stdout, stderr),
+            pipe: output.pipe,
+            fds: output.fds,
+        })
+    }
+
+    /// Opens the configured stdin of a process, returning the bytes
+    /// it reads.
+    fn open_stdin(&mut self, stdio: Option<Stdio>) -> Result<Vec<u8>, SimError> {
+        match stdio {
+            None | Some(Stdio::Inherit | Stdio::Null | Stdio::Piped) => Ok(Vec::new()),
+            Some(Stdio::ReadFile(path)) => self.read("stdin", path),
+            Some(Stdio::WriteFile(path)) => {
+                self.files.insert(path, Vec::new());
+                Ok(Vec::new())
+            }
+            Some(Stdio::AppendFile(path)) => {
+                self.files.entry(path).or_default();
+                Ok(Vec::new())
+            }
+            Some(Stdio::Stdout) => Err(invalid("cannot redirect stdin to stdout")),
+        }
+    }
+
+    /// Opens the configured output stream of a process, returning
+    /// where its bytes go.
+    ///
+    /// Unset streams go to the terminal in PTY mode, which is only
+    /// read if the output of the process is captured.
+    fn open_sink(
+        &mut self,
+        stdio: Option<Stdio>,
+        stream: Stream,
+        pty: bool,
+        captured: bool,
+    ) -> Result<Sink, SimError> {
+        let name = match stream {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        };
+
+        match stdio {
+            None if pty && captured => Ok(Sink::Terminal),
+            None if pty => Ok(Sink::Discard),
+            None | Some(Stdio::Inherit) => Ok(Sink::Parent(stream)),
+            Some(Stdio::Null) => Ok(Sink::Discard),
+            Some(Stdio::Piped) if captured => Ok(Sink::Capture(stream)),
+            Some(Stdio::Piped) => Ok(Sink::Discard),
+            Some(Stdio::ReadFile(path)) => {
+                self.read(name, path)?;
+                Ok(Sink::Discard)
+            }
+            Some(Stdio::WriteFile(path)) => {
+                self.files.insert(path.clone(), Vec::new());
+                Ok(Sink::File(path))
+            }
+            Some(Stdio::AppendFile(path)) => {
+                self.files.entry(path.clone()).or_default();
+                Ok(Sink::File(path))
+            }
+            Some(Stdio::Stdout) => Err(invalid(format!("cannot redirect {name} to stdout"))),
+        }
+    }
+
+    /// Reads a file of the in-memory file system, opened as the given
+    /// stream.
+    fn read(&self, stream: &str, path: String) -> Result<Vec<u8>, SimError> {
+        match self.files.get(&path) {
+            Some(bytes) => Ok(bytes.clone()),
+            None => Err(SimError::FileNotFound {
+                stream: stream.to_string(),
+                path,
+            }),
+        }
+    }
+
+    /// Writes the given bytes to the given sink, `elapsed` time after
+    /// the process got spawned.
+    ///
+    /// Returns `true` if the process needs to be killed, because a
+    /// captured stream exceeded its limit.
+    fn write(&mut self, output: &mut Output, sink: &Sink, elapsed: Duration, bytes: &[u8]) -> bool {
+        let (stream, bytes) = match sink {
+            Sink::Discard => return false,
+            Sink::Parent(stream) => {
+                self.parent(*stream).extend_from_slice(bytes);
+                return false;
+            }
+            Sink::File(path) => {
+                self.files.entry(path.clone()).or_default().extend(bytes);
+                return false;
+            }
+            Sink::Pipe => {
+                output.pipe.extend_from_slice(bytes);
+                return false;
+            }
+            Sink::CaptureFd(fd) => {
+                output.fds.entry(*fd).or_default().extend(bytes);
+                return false;
+            }
+            Sink::Capture(stream) => (*stream, Cow::from(bytes)),
+            // the terminal translates newlines, like the default
+            // `onlcr` mode does
+            Sink::Terminal => {
+                let mut translated = Vec::with_capacity(bytes.len());
+
+                for byte in bytes {
+                    if *byte == b'\n' {
+                        translated.push(b'\r');
+                    }
+
+                    translated.push(*byte);
+                }
+
+                (Stream::Stdout, Cow::from(translated))
+            }
+        };
+
+        if output.capture.tees(stream) {
+            self.parent(stream).extend_from_slice(&bytes);
+        }
+
+        output.recorder.record(stream, elapsed, &bytes)
+    }
+
+    /// Returns the given stream of the simulator.
+    fn parent(&mut self, stream: Stream) -> &mut Vec<u8> {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+}
+
+impl fmt::Debug for Simulator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Simulator")
+            .field("programs", &self.programs.keys().collect::<Vec<_>>())
+            .field("envs", &self.envs)
+            .field("files", &self.files)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .finish()
+    }
+}
+
+/// Role of a process within the processed input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Role {
+    /// The output of the process is not captured.
+    Spawn,
+    /// The output of the process is captured.
+    Capture,
+    /// The stdout of the process is piped to the next pipeline stage.
+    Stage,
+}
+
+/// Destination of the bytes written by a simulated process.
+#[derive(Clone, Debug)]
+enum Sink {
+    Discard,
+    Parent(Stream),
+    File(String),
+    Pipe,
+    Capture(Stream),
+    CaptureFd(i32),
+    Terminal,
+}
+
+/// Outputs of a running simulated process.
+struct Output {
+    capture: Capture,
+    recorder: Recorder,
+    pipe: Vec<u8>,
+    fds: BTreeMap<i32, Vec<u8>>,
+}
+
+/// Simulated process that exited.
+struct Exited {
+    status: ExitStatus,
+    recording: Recording,
+    pipe: Vec<u8>,
+    fds: BTreeMap<i32, Vec<u8>>,
+}
+
+fn invalid(reason: impl ToString) -> SimError {
+    let reason = reason.to_string();
+    SimError::InvalidInput { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String, vec, vec::Vec};
+    use core::time::Duration;
+
+    use crate::{
+        capture::{LimitAction, Stream, TranscriptEvent},
+        command::Command,
+        coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
+        fd::ExtraFd,
+        io::{ProcessInput, ProcessOutput},
+        status::ExitStatus,
+        stdio::Stdio,
+    };
+
+    use super::{Outcome, SimError, Simulator};
+
+    fn cat(simulator: &mut Simulator) {
+        simulator.program("cat", |invocation| {
+            let mut outcome = Outcome::new();
+            outcome.stdout(invocation.stdin.clone());
+            outcome
+        });
+    }
+
+    #[test]
+    fn invocation() {
+        let mut simulator = Simulator::new();
+        simulator.env("HOME", "/home/me").env("LANG", "en");
+        simulator.program("greet", |invocation| {
+            let lang = &invocation.envs["LANG"];
+            let home = &invocation.envs["HOME"];
+            let name = &invocation.args[0];
+
+            let mut outcome = Outcome::new();
+            outcome
+                .stdout(format!("[{lang}] hello {name}\n"))
+                .stderr(format!("{home}\n"))
+                .status(ExitStatus::new(Some(2)));
+            outcome
+        });
+
+        let mut command = Command::new("greet");
+        command.arg("world").env("LANG", "C");
+        command.stderr(Stdio::Inherit);
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawnOut::new(command);
+
+        let (status, stdout, stderr) = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnOutResult::Ok {
+                    status,
+                    stdout,
+                    stderr,
+                    ..
+                } => break (status, stdout, stderr),
+                ProcessSpawnOutResult::Io { input } => arg = Some(simulator.handle(input).unwrap()),
+                ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(Some(2), status.code());
+        assert_eq!(Some(b"[C] hello world\n".to_vec()), stdout);
+        assert_eq!(None, stderr);
+        assert_eq!(b"/home/me\n", simulator.parent_stderr());
+    }
+
+    #[test]
+    fn pipeline() {
+        let mut simulator = Simulator::new();
+        cat(&mut simulator);
+        simulator.file("in.txt", "hello\n");
+        simulator.program("shout", |invocation| {
+            let mut outcome = Outcome::new();
+            outcome
+                .stdout(invocation.stdin.to_ascii_uppercase())
+                .stderr("shouted\n")
+                .fd(3, "logged\n");
+            outcome
+        });
+
+        let mut first = Command::new("cat");
+        first.stdin(Stdio::ReadFile("in.txt".into()));
+
+        // merged stderr goes through the pipe as well
+        let mut second = Command::new("shout");
+        second.stderr(Stdio::Stdout);
+
+        let mut last = Command::new("shout");
+        last.stderr(Stdio::AppendFile("err.log".into()));
+        last.fd(3, ExtraFd::Capture);
+
+        let input = ProcessInput::SpawnPipeline {
+            cmds: vec![first, second, last],
+        };
+
+        let Ok(ProcessOutput::SpawnedPipeline {
+            status,
+            stdout,
+            stderr,
+            fds,
+            ..
+        }) = simulator.handle(input)
+        else {
+            panic!("unexpected output");
+        };
+
+        assert!(status.success());
+        assert_eq!(Some(b"HELLO\nSHOUTED\n".to_vec()), stdout);
+        assert_eq!(None, stderr);
+        assert_eq!(Some(&b"logged\n".to_vec()), fds.get(&3));
+        assert_eq!(Some(&b"shouted\n"[..]), simulator.get_file("err.log"));
+
+        let input = ProcessInput::SpawnIn {
+            cmd: Command::new("cat"),
+            stdin: b"fed\n".to_vec(),
+        };
+
+        let Ok(ProcessOutput::SpawnedIn { status }) = simulator.handle(input) else {
+            panic!("unexpected output");
+        };
+
+        assert!(status.success());
+        assert_eq!(b"fed\n", simulator.parent_stdout());
+    }
+
+    #[test]
+    fn clock() {
+        let mut simulator = Simulator::new();
+        simulator.program("tick", |_| {
+            let mut outcome = Outcome::new();
+            outcome
+                .stdout("ab")
+                .sleep(Duration::from_secs(1))
+                .stderr("err")
+                .sleep(Duration::from_secs(1))
+                .stdout("cd");
+            outcome
+        });
+
+        let mut command = Command::new("tick");
+        command
+            .max_stdout(3)
+            .on_output_limit(LimitAction::Kill)
+            .transcript(true)
+            .tee_stderr(true);
+
+        let Ok(ProcessOutput::SpawnedOut {
+            status,
+            stdout,
+            truncated,
+            transcript,
+            ..
+        }) = simulator.handle(ProcessInput::SpawnOut { cmd: command })
+        else {
+            panic!("unexpected output");
+        };
+
+        assert_eq!(None, status.code());
+        assert_eq!(Some(b"abc".to_vec()), stdout);
+        assert!(truncated.stdout);
+        assert_eq!(b"err", simulator.parent_stderr());
+
+        let event = |stream, secs, bytes: &[u8]| TranscriptEvent {
+            stream,
+            elapsed: Duration::from_secs(secs),
+            bytes: bytes.to_vec(),
+        };
+
+        let expected = vec![
+            event(Stream::Stdout, 0, b"ab"),
+            event(Stream::Stderr, 1, b"err"),
+            event(Stream::Stdout, 2, b"c"),
+        ];
+        assert_eq!(Some(expected), transcript);
+
+        let mut command = Command::new("tick");
+        command
+            .stdout(Stdio::WriteFile("out.log".into()))
+            .timeout(Duration::from_millis(1500));
+
+        let err = simulator.handle(ProcessInput::Spawn { cmd: command });
+        assert_eq!(Err(SimError::TimedOut), err.map(|_| ()));
+        assert_eq!(Some(&b"ab"[..]), simulator.get_file("out.log"));
+    }
+
+    #[test]
+    fn timeout_boundary() {
+        let mut simulator = Simulator::new();
+        simulator.program("nap", |_| {
+            let mut outcome = Outcome::new();
+            outcome.sleep(Duration::from_secs(1));
+            outcome
+        });
+
+        let nap = |timeout| {
+            let mut command = Command::new("nap");
+            command.timeout(timeout);
+            ProcessInput::Spawn { cmd: command }
+        };
+
+        let err = simulator.handle(nap(Duration::from_secs(1)));
+        assert_eq!(Err(SimError::TimedOut), err.map(|_| ()));
+
+        let output = simulator.handle(nap(Duration::from_millis(1001)));
+        assert!(matches!(output, Ok(ProcessOutput::Spawned { status }) if status.success()));
+    }
+
+    #[test]
+    fn errors() {
+        let mut simulator = Simulator::new();
+        cat(&mut simulator);
+
+        let errors: Vec<_> = [
+            ProcessInput::Spawn {
+                cmd: Command::new("missing"),
+            },
+            ProcessInput::SpawnOut {
+                cmd: {
+                    let mut command = Command::new("cat");
+                    command.stdin(Stdio::ReadFile("missing.txt".into()));
+                    command
+                },
+            },
+            ProcessInput::SpawnIn {
+                cmd: {
+                    let mut command = Command::new("cat");
+                    command.stdin(Stdio::Null);
+                    command
+                },
+                stdin: Vec::new(),
+            },
+            ProcessInput::SpawnPipeline { cmds: Vec::new() },
+        ]
+        .into_iter()
+        .map(|input| simulator.handle(input).unwrap_err())
+        .collect();
+
+        let invalid = |reason: &str| SimError::InvalidInput {
+            reason: String::from(reason),
+        };
+
+        let expected = vec![
+            SimError::NotFound {
+                program: String::from("missing"),
+            },
+            SimError::FileNotFound {
+                stream: String::from("stdin"),
+                path: String::from("missing.txt"),
+            },
+            invalid("cannot feed stdin of a command with non-piped stdin"),
+            invalid("empty pipeline"),
+        ];
+        assert_eq!(expected, errors);
+    }
+}
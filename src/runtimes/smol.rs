@@ -0,0 +1,383 @@
+//! Async process runtime backed by [`async_process`], for the smol
+//! ecosystem.
+//!
+//! The runtime does not depend on a specific executor: the futures
+//! it returns can be driven by [`smol`], [`async_io::block_on`] or
+//! any other executor.
+//!
+//! [`smol`]: https://docs.rs/smol
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    pin::Pin,
+    process::{ExitStatus as StdExitStatus, Stdio as StdStdio},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use async_io::Async;
+use async_io::Timer;
+use async_process::{Child, ChildStderr, ChildStdin, ChildStdout, Command as AsyncCommand};
+use blocking::{Task, unblock};
+use futures_lite::{AsyncRead, AsyncWriteExt};
+
+#[cfg(target_os = "linux")]
+use super::pidfd::Pidfd;
+use super::{
+    convert::{self, ChildSetup, PtyMaster},
+    driver::{self, Reader, Runtime},
+};
+use crate::{
+    capture::Stream,
+    command::Command,
+    io::{ProcessInput, ProcessOutput},
+};
+
+/// Processes a [`ProcessInput`] request asynchronously using
+/// [`async_process`].
+///
+/// The returned future is cancellation-safe: when dropped before
+/// completion, spawned processes are handled according to their
+/// [`Termination::on_drop`] configuration, which kills and reaps them
+/// by default.
+///
+/// [`Termination::on_drop`]: crate::termination::Termination::on_drop
+pub async fn handle(input: ProcessInput) -> io::Result<ProcessOutput> {
+    match input {
+        ProcessInput::Spawn { cmd } => spawn(cmd).await,
+        ProcessInput::SpawnOut { cmd } => spawn_out(cmd).await,
+        ProcessInput::SpawnIn { cmd, stdin } => spawn_in(cmd, stdin).await,
+        ProcessInput::SpawnPipeline { cmds } => spawn_pipeline(cmds).await,
+    }
+}
+
+/// Spawns a process and waits for its exit status.
+///
+/// Fails with [`io::ErrorKind::TimedOut`] if the process ran past its
+/// timeout, after terminating it.
+pub async fn spawn(cmd: Command) -> io::Result<ProcessOutput> {
+    driver::spawn::<Smol>(cmd).await
+}
+
+/// Spawns a process, captures its stdout and stderr, and waits for
+/// its exit status.
+///
+/// Stdout and stderr are captured if their [`Stdio`] configuration
+/// is [`Stdio::Piped`] or unset, and returned as `None` otherwise.
+/// In PTY mode, the terminal output is captured as stdout instead.
+/// Bytes past the [`Capture`] limits of the command are drained but
+/// not stored.
+///
+/// Fails with [`io::ErrorKind::TimedOut`] if the process ran past its
+/// timeout, after terminating it.
+///
+/// [`Stdio`]: crate::stdio::Stdio
+/// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
+/// [`Capture`]: crate::capture::Capture
+pub async fn spawn_out(cmd: Command) -> io::Result<ProcessOutput> {
+    driver::spawn_out::<Smol>(cmd).await
+}
+
+/// Spawns a process, feeds bytes to its stdin, and waits for its exit
+/// status.
+///
+/// The bytes left unread when the process exits are discarded.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if the [`Stdio`]
+/// configuration of the command's stdin is neither [`Stdio::Piped`]
+/// nor unset, and with [`io::ErrorKind::TimedOut`] if the process ran
+/// past its timeout, after terminating it.
+///
+/// [`Stdio`]: crate::stdio::Stdio
+/// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
+pub async fn spawn_in(cmd: Command, stdin: Vec<u8>) -> io::Result<ProcessOutput> {
+    driver::spawn_in::<Smol>(cmd, stdin).await
+}
+
+/// Spawns a pipeline of processes, piping each process's stdout into
+/// the next process's stdin.
+///
+/// Returns the last process's exit status, stdout, stderr and extra
+/// file descriptors, captured the same way as [`spawn_out`]. The
+/// timeout and the termination configuration of the last process
+/// apply to every process of the pipeline.
+pub async fn spawn_pipeline(cmds: Vec<Command>) -> io::Result<ProcessOutput> {
+    driver::spawn_pipeline::<Smol>(cmds).await
+}
+
+/// Converts the stdout of a pipeline stage into the stdin of the next
+/// one, by handing its raw file descriptor over.
+///
+/// The descriptor is switched back to blocking mode, which the next
+/// process expects.
+#[cfg(unix)]
+async fn into_stdin(stdout: async_process::ChildStdout) -> io::Result<StdStdio> {
+    use std::os::fd::{AsRawFd, OwnedFd};
+
+    let fd = OwnedFd::try_from(stdout)?;
+
+    // SAFETY: the descriptor is owned, and alive.
+    unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFL);
+
+        if flags == -1
+            || libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags & !libc::O_NONBLOCK) == -1
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(fd.into())
+}
+
+/// Converts the stdout of a pipeline stage into the stdin of the next
+/// one.
+#[cfg(not(unix))]
+async fn into_stdin(stdout: async_process::ChildStdout) -> io::Result<StdStdio> {
+    stdout.into_stdio().await
+}
+
+/// Executor glue of the smol ecosystem.
+struct Smol;
+
+impl Runtime for Smol {
+    type Command = AsyncCommand;
+    type Child = Child;
+    type Stdin = ChildStdin;
+    type Stdout = Pipe<ChildStdout>;
+    type Stderr = Pipe<ChildStderr>;
+    #[cfg(unix)]
+    type Pty = Pipe<Async<PtyMaster>>;
+    #[cfg(not(unix))]
+    type Pty = driver::NoPty;
+    type FdTasks = FdTasks;
+
+    fn command(cmd: Command) -> io::Result<(AsyncCommand, ChildSetup)> {
+        command(cmd)
+    }
+
+    fn spawn(command: &mut AsyncCommand) -> io::Result<Child> {
+        command.spawn()
+    }
+
+    fn pipe_stdout(command: &mut AsyncCommand) {
+        command.stdout(StdStdio::piped());
+    }
+
+    async fn pipe_into(child: &mut Child, command: &mut AsyncCommand) -> io::Result<()> {
+        if let Some(stdout) = child.stdout.take() {
+            command.stdin(into_stdin(stdout).await?);
+        }
+
+        Ok(())
+    }
+
+    fn stdin(child: &mut Child) -> Option<ChildStdin> {
+        child.stdin.take()
+    }
+
+    async fn feed(mut stdin: ChildStdin, bytes: &[u8]) -> io::Result<()> {
+        stdin.write_all(bytes).await?;
+        stdin.close().await
+    }
+
+    fn start(setup: ChildSetup) -> io::Result<FdTasks> {
+        Ok(FdTasks::start(setup))
+    }
+
+    async fn join(tasks: FdTasks) -> io::Result<BTreeMap<i32, Vec<u8>>> {
+        tasks.join().await
+    }
+
+    fn id(child: &Child) -> Option<u32> {
+        Some(child.id())
+    }
+
+    fn pipes(child: &mut Child) -> (Option<Self::Stdout>, Option<Self::Stderr>) {
+        drop(child.stdin.take());
+        (child.stdout.take().map(Pipe), child.stderr.take().map(Pipe))
+    }
+
+    /// Switches the given master side to non-blocking mode, and
+    /// registers it in the reactor.
+    #[cfg(unix)]
+    fn pty(master: PtyMaster) -> io::Result<Self::Pty> {
+        Async::new(master).map(Pipe)
+    }
+
+    #[cfg(not(unix))]
+    fn pty(master: PtyMaster) -> io::Result<Self::Pty> {
+        driver::NoPty::new(master)
+    }
+
+    async fn wait(child: &mut Child) -> io::Result<StdExitStatus> {
+        child.status().await
+    }
+
+    /// Registers the pidfd in the reactor, which reports it readable
+    /// once the process exited.
+    #[cfg(target_os = "linux")]
+    async fn exited(pidfd: &Pidfd) -> io::Result<()> {
+        use std::os::fd::AsFd;
+
+        let fd = Async::new(pidfd.as_fd())?;
+
+        loop {
+            fd.readable().await?;
+
+            if pidfd.has_exited()? {
+                return Ok(());
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn kill(child: &mut Child) -> io::Result<()> {
+        child.kill()
+    }
+
+    async fn sleep(duration: Duration) {
+        Timer::after(duration).await;
+    }
+
+    /// Forwards the chunk from the thread pool of [`blocking`], so
+    /// that writing to the parent's streams does not block the
+    /// executor.
+    async fn tee(stream: Stream, chunk: &[u8]) -> io::Result<()> {
+        let chunk = chunk.to_vec();
+
+        unblock(move || match stream {
+            Stream::Stdout => {
+                let mut stdout = io::stdout();
+                stdout.write_all(&chunk)?;
+                stdout.flush()
+            }
+            Stream::Stderr => io::stderr().write_all(&chunk),
+        })
+        .await
+    }
+
+    /// Defers to the thread pool of [`blocking`], which runs whatever
+    /// the executor.
+    #[cfg(unix)]
+    fn defer(delay: Duration, f: impl FnOnce() + Send + 'static) {
+        unblock(move || {
+            std::thread::sleep(delay);
+            f();
+        })
+        .detach();
+    }
+}
+
+/// Reader of a futures stream.
+struct Pipe<R>(R);
+
+impl<R: AsyncRead + Unpin> Reader for Pipe<R> {
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+/// Tasks serving the extra file descriptors of a spawned child.
+///
+/// The pipes are served with blocking I/O from the thread pool of
+/// [`blocking`], so that they make progress whatever the executor.
+#[derive(Default)]
+struct FdTasks {
+    writers: Vec<Task<io::Result<()>>>,
+    readers: Vec<(i32, Task<io::Result<Vec<u8>>>)>,
+}
+
+impl FdTasks {
+    /// Closes the child ends of the given extra file descriptors,
+    /// then writes the input pipes and reads the captured pipes, each
+    /// from its own task.
+    ///
+    /// The terminal output, if not taken for capture, is drained and
+    /// discarded.
+    fn start(setup: ChildSetup) -> Self {
+        drop(setup.child_ends);
+
+        let mut tasks = Self::default();
+
+        if let Some(mut terminal) = setup.pty {
+            unblock(move || io::copy(&mut terminal, &mut io::sink())).detach();
+        }
+
+        for (mut pipe, bytes) in setup.inputs {
+            tasks
+                .writers
+                .push(unblock(move || match pipe.write_all(&bytes) {
+                    // the child is free not to read its input
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                    res => res,
+                }));
+        }
+
+        for (fd, mut pipe) in setup.captures {
+            let reader = unblock(move || {
+                let mut bytes = Vec::new();
+                pipe.read_to_end(&mut bytes)?;
+                Ok(bytes)
+            });
+
+            tasks.readers.push((fd, reader));
+        }
+
+        tasks
+    }
+
+    /// Waits for the tasks to finish, returning the captured bytes by
+    /// descriptor number.
+    async fn join(self) -> io::Result<BTreeMap<i32, Vec<u8>>> {
+        for writer in self.writers {
+            writer.await?;
+        }
+
+        let mut fds = BTreeMap::new();
+
+        for (fd, reader) in self.readers {
+            fds.insert(fd, reader.await?);
+        }
+
+        Ok(fds)
+    }
+}
+
+// SAFETY: reading does not close or replace the descriptor of the
+// master side.
+#[cfg(unix)]
+unsafe impl async_io::IoSafe for PtyMaster {}
+
+/// Converts a [`Command`] into an [`async_process::Command`], setting
+/// up its extra file descriptors.
+///
+/// Unless the command needs to be detached or gets terminated by the
+/// watchdog, see [`driver::kills_on_drop`], the child is killed when
+/// its handle gets dropped, for example when the future of the
+/// runtime got cancelled. It is reaped in the background by
+/// async-process.
+fn command(cmd: Command) -> io::Result<(AsyncCommand, ChildSetup)> {
+    let kills = driver::kills_on_drop(&cmd.termination);
+    let (command, stdio, setup) = convert::command_parts(cmd)?;
+    let mut command = AsyncCommand::from(command);
+
+    // the converted command does not keep its streams, they need to
+    // be set again
+    if let Some(stdin) = stdio.stdin {
+        command.stdin(stdin);
+    }
+
+    if let Some(stdout) = stdio.stdout {
+        command.stdout(stdout);
+    }
+
+    if let Some(stderr) = stdio.stderr {
+        command.stderr(stderr);
+    }
+
+    command.kill_on_drop(kills);
+    Ok((command, setup))
+}
@@ -0,0 +1,316 @@
+//! Out-of-process runtime, delegating requests to a server over a
+//! Unix domain socket.
+//!
+//! A [`Client`] sends each [`ProcessInput`] to a server, which
+//! processes it with the [`std`](super::std) runtime and sends back
+//! the [`ProcessOutput`]. This lets a sandboxed application delegate
+//! process spawning to a small privileged helper running [`serve`].
+//!
+//! # Protocol
+//!
+//! Messages are JSON documents following the [`serde`](crate::serde)
+//! schema, each prefixed with its length in bytes as a 32-bit
+//! big-endian integer. Frames larger than [`MAX_FRAME_LEN`] are
+//! rejected.
+//!
+//! The client opens the connection with `{"version": 1}`. The server
+//! answers with the same message if it speaks this [`VERSION`] of the
+//! protocol, otherwise with an error, and closes the connection.
+//!
+//! The client then sends process inputs, one per frame. The server
+//! answers each of them either with `{"output": ...}`, or with
+//! `{"error": {"kind": "not-found", "message": "..."}}`, where the
+//! kind is the kebab-cased [`io::ErrorKind`] of the failure. Clients
+//! turn errors back into [`io::Error`]s of the same kind.
+//!
+//! The server spawns whatever it is asked to: make sure only trusted
+//! clients can connect, for example by restricting the permissions of
+//! the socket file.
+
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    thread,
+};
+
+use log::debug;
+use serde::Serialize;
+use serde_json::{Value, json};
+use thiserror::Error;
+
+use crate::io::{ProcessInput, ProcessOutput};
+
+/// Version of the protocol spoken by clients and servers.
+pub const VERSION: u64 = 1;
+
+/// Maximum length of a frame, in bytes.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+/// Names of the error kinds propagated from servers to clients.
+///
+/// Other kinds are propagated as [`ErrorKind::Other`].
+const ERROR_KINDS: &[(ErrorKind, &str)] = &[
+    (ErrorKind::NotFound, "not-found"),
+    (ErrorKind::PermissionDenied, "permission-denied"),
+    (ErrorKind::AlreadyExists, "already-exists"),
+    (ErrorKind::BrokenPipe, "broken-pipe"),
+    (ErrorKind::WouldBlock, "would-block"),
+    (ErrorKind::InvalidInput, "invalid-input"),
+    (ErrorKind::InvalidData, "invalid-data"),
+    (ErrorKind::TimedOut, "timed-out"),
+    (ErrorKind::Interrupted, "interrupted"),
+    (ErrorKind::Unsupported, "unsupported"),
+    (ErrorKind::UnexpectedEof, "unexpected-eof"),
+    (ErrorKind::OutOfMemory, "out-of-memory"),
+    (ErrorKind::Other, "other"),
+];
+
+/// Error emitted by the [`Client`] and the server when the protocol
+/// is not followed, wrapped into an [`io::Error`].
+#[derive(Debug, Error)]
+pub enum SocketError {
+    /// The frame is larger than [`MAX_FRAME_LEN`].
+    #[error("Frame of {len} bytes exceeds the maximum of {MAX_FRAME_LEN} bytes")]
+    FrameTooLarge { len: usize },
+
+    /// The message is not valid.
+    #[error("Invalid message: {reason}")]
+    InvalidMessage { reason: String },
+
+    /// The peer does not speak the same version of the protocol.
+    #[error("Unsupported protocol version {version:?}, expected {VERSION}")]
+    UnsupportedVersion { version: Option<u64> },
+
+    /// The peer closed the connection before answering.
+    #[error("Connection closed by peer")]
+    Closed,
+}
+
+impl From<SocketError> for io::Error {
+    fn from(err: SocketError) -> Self {
+        let kind = match err {
+            SocketError::UnsupportedVersion { .. } => ErrorKind::Unsupported,
+            SocketError::Closed => ErrorKind::UnexpectedEof,
+            _ => ErrorKind::InvalidData,
+        };
+
+        io::Error::new(kind, err)
+    }
+}
+
+/// Client runtime, connected to a server.
+#[derive(Debug)]
+pub struct Client {
+    stream: UnixStream,
+}
+
+impl Client {
+    /// Connects to the server listening at the given socket path.
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_stream(UnixStream::connect(path)?)
+    }
+
+    /// Opens a connection over the given stream, already connected to
+    /// a server.
+    ///
+    /// Fails with [`ErrorKind::Unsupported`] if the server does not
+    /// speak the same protocol version.
+    pub fn from_stream(mut stream: UnixStream) -> io::Result<Self> {
+        write_frame(&mut stream, &json!({ "version": VERSION }))?;
+        let reply = read_frame(&mut stream)?.ok_or(SocketError::Closed)?;
+        let version = unwrap_reply(reply, "version")?.as_u64();
+
+        if version != Some(VERSION) {
+            return Err(SocketError::UnsupportedVersion { version }.into());
+        }
+
+        debug!("connected to process server using protocol v{VERSION}");
+        Ok(Self { stream })
+    }
+
+    /// Processes a [`ProcessInput`] request by sending it to the
+    /// server, and waiting for its [`ProcessOutput`].
+    ///
+    /// Errors of the server are returned with their original
+    /// [`io::ErrorKind`].
+    pub fn handle(&mut self, input: ProcessInput) -> io::Result<ProcessOutput> {
+        write_frame(&mut self.stream, &input)?;
+        let reply = read_frame(&mut self.stream)?.ok_or(SocketError::Closed)?;
+        let output = unwrap_reply(reply, "output")?;
+        serde_json::from_value(output).map_err(invalid_message)
+    }
+}
+
+/// Accepts connections from the given listener, serving each of them
+/// in its own thread.
+///
+/// Only returns when accepting a connection fails.
+pub fn serve(listener: &UnixListener) -> io::Result<()> {
+    loop {
+        let (stream, _) = listener.accept()?;
+
+        thread::spawn(move || {
+            if let Err(err) = serve_connection(stream) {
+                debug!("process server connection failed: {err}");
+            }
+        });
+    }
+}
+
+/// Serves a single connection, processing its requests with the
+/// [`std`](super::std) runtime until the client disconnects.
+pub fn serve_connection(mut stream: UnixStream) -> io::Result<()> {
+    let Some(hello) = read_frame(&mut stream)? else {
+        return Ok(());
+    };
+
+    let version = hello.get("version").and_then(Value::as_u64);
+
+    if version != Some(VERSION) {
+        let err = io::Error::from(SocketError::UnsupportedVersion { version });
+        write_frame(&mut stream, &error_reply(&err))?;
+        return Err(err);
+    }
+
+    write_frame(&mut stream, &json!({ "version": VERSION }))?;
+
+    while let Some(request) = read_frame(&mut stream)? {
+        let output = serde_json::from_value::<ProcessInput>(request)
+            .map_err(invalid_message)
+            .and_then(super::std::handle);
+
+        let reply = match output {
+            Ok(output) => json!({ "output": output }),
+            Err(err) => error_reply(&err),
+        };
+
+        write_frame(&mut stream, &reply)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the given message as a length-prefixed frame.
+fn write_frame(stream: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    let payload = serde_json::to_vec(message).map_err(invalid_message)?;
+    let len = payload.len();
+
+    if len > MAX_FRAME_LEN {
+        return Err(SocketError::FrameTooLarge { len }.into());
+    }
+
+    stream.write_all(&(len as u32).to_be_bytes())?;
+    stream.write_all(&payload)?;
+    stream.flush()
+}
+
+/// Reads the next length-prefixed frame as a message.
+///
+/// Returns `None` if the peer closed the connection between two
+/// frames.
+fn read_frame(stream: &mut impl Read) -> io::Result<Option<Value>> {
+    let mut len = [0; 4];
+    let mut read = 0;
+
+    while read < len.len() {
+        match stream.read(&mut len[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    let len = u32::from_be_bytes(len) as usize;
+
+    if len > MAX_FRAME_LEN {
+        return Err(SocketError::FrameTooLarge { len }.into());
+    }
+
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload)?;
+
+    let message = serde_json::from_slice(&payload).map_err(invalid_message)?;
+    Ok(Some(message))
+}
+
+/// Extracts the value at the given key of a reply, or the error it
+/// carries.
+fn unwrap_reply(mut reply: Value, key: &str) -> io::Result<Value> {
+    if let Some(err) = reply.get("error") {
+        let kind = err.get("kind").and_then(Value::as_str).unwrap_or_default();
+        let kind = ERROR_KINDS
+            .iter()
+            .find(|(_, name)| *name == kind)
+            .map_or(ErrorKind::Other, |(kind, _)| *kind);
+
+        let message = err.get("message").and_then(Value::as_str);
+        return Err(io::Error::new(kind, message.unwrap_or_default()));
+    }
+
+    match reply.get_mut(key) {
+        Some(value) => Ok(value.take()),
+        None => Err(invalid_message(format!("missing field `{key}`"))),
+    }
+}
+
+/// Builds the reply carrying the given error.
+fn error_reply(err: &io::Error) -> Value {
+    let kind = ERROR_KINDS
+        .iter()
+        .find(|(kind, _)| *kind == err.kind())
+        .map_or("other", |(_, name)| name);
+
+    json!({ "error": { "kind": kind, "message": err.to_string() } })
+}
+
+fn invalid_message(reason: impl ToString) -> io::Error {
+    let reason = reason.to_string();
+    SocketError::InvalidMessage { reason }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::ErrorKind, os::unix::net::UnixStream, thread};
+
+    use serde_json::json;
+
+    use super::{Client, VERSION, read_frame, serve_connection, write_frame};
+
+    #[test]
+    fn frames() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+
+        let message = json!({ "version": VERSION });
+        write_frame(&mut client, &message).unwrap();
+        drop(client);
+
+        assert_eq!(Some(message), read_frame(&mut server).unwrap());
+        assert_eq!(None, read_frame(&mut server).unwrap());
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let (mut client, server) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || serve_connection(server));
+
+        write_frame(&mut client, &json!({ "version": VERSION + 1 })).unwrap();
+        let reply = read_frame(&mut client).unwrap().unwrap();
+        assert_eq!("unsupported", reply["error"]["kind"]);
+
+        let err = server.join().unwrap().unwrap_err();
+        assert_eq!(ErrorKind::Unsupported, err.kind());
+
+        // the client checks the version as well
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let client = thread::spawn(move || Client::from_stream(client));
+
+        read_frame(&mut server).unwrap();
+        write_frame(&mut server, &json!({ "version": 0 })).unwrap();
+
+        let err = client.join().unwrap().unwrap_err();
+        assert_eq!(ErrorKind::Unsupported, err.kind());
+    }
+}
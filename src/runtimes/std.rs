@@ -1,15 +1,27 @@
 //! Synchronous process runtime backed by [`std::process`].
 
 use std::{
-    io::{self, Write},
-    process::{Command as StdCommand, Stdio as StdStdio},
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    process::{Child, ExitStatus as StdExitStatus, Stdio as StdStdio},
+    slice,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use log::debug;
+
+use super::convert::{self, ChildSetup, PtyMaster};
+#[cfg(unix)]
+use super::kill;
 use crate::{
+    capture::{Capture, Recorder, Recording, Stream},
     command::Command,
     io::{ProcessInput, ProcessOutput},
     status::ExitStatus,
     stdio::Stdio,
+    termination::{Signal, Terminator},
 };
 
 /// Processes a [`ProcessInput`] request synchronously using
@@ -24,9 +36,22 @@ pub fn handle(input: ProcessInput) -> io::Result<ProcessOutput> {
 }
 
 /// Spawns a process and waits for its exit status.
+///
+/// Fails with [`io::ErrorKind::TimedOut`] if the process ran past its
+/// timeout, after terminating it.
 pub fn spawn(cmd: Command) -> io::Result<ProcessOutput> {
-    let mut command = StdCommand::from(cmd);
-    let status = command.status()?;
+    convert::reject_captured_fds(&cmd)?;
+    let mut watchdog = Watchdog::new(&cmd);
+    let tree = cmd.termination.tree;
+    let (mut command, mut setup) = convert::command(cmd)?;
+
+    let mut child = command.spawn().map_err(|err| setup.spawn_error(err))?;
+    watchdog.watch(&mut child, tree)?;
+    // closes the parent copies of the child stdio, like the terminal
+    drop(command);
+    let fds = FdThreads::start(setup);
+    let status = watchdog.wait(slice::from_mut(&mut child))?;
+    fds.join()?;
 
     Ok(ProcessOutput::Spawned {
         status: ExitStatus::new(status.code()),
@@ -36,39 +61,102 @@ pub fn spawn(cmd: Command) -> io::Result<ProcessOutput> {
 /// Spawns a process, captures its stdout and stderr, and waits for
 /// its exit status.
 ///
-/// Overrides the command's stdout and stderr to [`StdStdio::piped`]
-/// regardless of the [`Stdio`] configuration on the command.
-pub fn spawn_out(cmd: Command) -> io::Result<ProcessOutput> {
-    let mut command = StdCommand::from(cmd);
-    command.stdout(StdStdio::piped());
-    command.stderr(StdStdio::piped());
+/// Stdout and stderr are captured if their [`Stdio`] configuration
+/// is [`Stdio::Piped`] or unset, and returned as `None` otherwise.
+/// In PTY mode, the terminal output is captured as stdout instead.
+/// Bytes past the [`Capture`] limits of the command are drained but
+/// not stored.
+///
+/// Fails with [`io::ErrorKind::TimedOut`] if the process ran past its
+/// timeout, after terminating it.
+pub fn spawn_out(mut cmd: Command) -> io::Result<ProcessOutput> {
+    if cmd.pty.is_none() {
+        cmd.stdout.get_or_insert(Stdio::Piped);
+        cmd.stderr.get_or_insert(Stdio::Piped);
+    }
+
+    let capture = cmd.capture.clone();
+    let mut watchdog = Watchdog::new(&cmd);
+    let tree = cmd.termination.tree;
+    let (mut command, mut setup) = convert::command(cmd)?;
 
-    let child = command.spawn()?;
-    let output = child.wait_with_output()?;
+    let mut child = command.spawn().map_err(|err| setup.spawn_error(err))?;
+    watchdog.watch(&mut child, tree)?;
+    // closes the parent copies of the child stdio, like the terminal
+    drop(command);
+    let terminal = setup.pty.take();
+    let fds = FdThreads::start(setup);
+    let Recording {
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+    } = read_output(
+        slice::from_mut(&mut child),
+        terminal,
+        &capture,
+        &mut watchdog,
+    )?;
+    let status = watchdog.wait(slice::from_mut(&mut child))?;
+    let fds = fds.join()?;
 
     Ok(ProcessOutput::SpawnedOut {
-        status: ExitStatus::new(output.status.code()),
-        stdout: output.stdout,
-        stderr: output.stderr,
+        status: ExitStatus::new(status.code()),
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+        fds,
     })
 }
 
 /// Spawns a process, feeds bytes to its stdin, and waits for its exit
 /// status.
 ///
-/// Overrides the command's stdin to [`StdStdio::piped`] regardless of
-/// the [`Stdio`] configuration on the command.
-pub fn spawn_in(cmd: Command, stdin: Vec<u8>) -> io::Result<ProcessOutput> {
-    let mut command = StdCommand::from(cmd);
-    command.stdin(StdStdio::piped());
+/// The bytes left unread when the process exits are discarded.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if the [`Stdio`]
+/// configuration of the command's stdin is neither [`Stdio::Piped`]
+/// nor unset, and with [`io::ErrorKind::TimedOut`] if the process ran
+/// past its timeout, after terminating it.
+pub fn spawn_in(mut cmd: Command, stdin: Vec<u8>) -> io::Result<ProcessOutput> {
+    if *cmd.stdin.get_or_insert(Stdio::Piped) != Stdio::Piped {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot feed stdin of a command with non-piped stdin",
+        ));
+    }
+
+    convert::reject_captured_fds(&cmd)?;
+    let mut watchdog = Watchdog::new(&cmd);
+    let tree = cmd.termination.tree;
+    let (mut command, mut setup) = convert::command(cmd)?;
+
+    let mut child = command.spawn().map_err(|err| setup.spawn_error(err))?;
+    watchdog.watch(&mut child, tree)?;
+    // closes the parent copies of the child stdio, like the terminal
+    drop(command);
+    let fds = FdThreads::start(setup);
+
+    // stdin is fed from its own thread, so that a process that does
+    // not read it still gets terminated at its deadline
+    let feeder = child.stdin.take().map(|mut handle| {
+        thread::spawn(move || match handle.write_all(&stdin) {
+            // the process may exit without reading its whole stdin
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            res => res,
+        })
+    });
 
-    let mut child = command.spawn()?;
+    let status = watchdog.wait(slice::from_mut(&mut child))?;
 
-    if let Some(mut handle) = child.stdin.take() {
-        handle.write_all(&stdin)?;
+    if let Some(feeder) = feeder {
+        feeder
+            .join()
+            .map_err(|_| io::Error::other("stdin thread panicked"))??;
     }
 
-    let status = child.wait()?;
+    fds.join()?;
 
     Ok(ProcessOutput::SpawnedIn {
         status: ExitStatus::new(status.code()),
@@ -78,7 +166,10 @@ pub fn spawn_in(cmd: Command, stdin: Vec<u8>) -> io::Result<ProcessOutput> {
 /// Spawns a pipeline of processes, piping each process's stdout into
 /// the next process's stdin.
 ///
-/// Returns the last process's exit status, stdout, and stderr.
+/// Returns the last process's exit status, stdout, stderr and extra
+/// file descriptors, captured the same way as [`spawn_out`]. The
+/// timeout and the termination configuration of the last process
+/// apply to every process of the pipeline.
 pub fn spawn_pipeline(cmds: Vec<Command>) -> io::Result<ProcessOutput> {
     let n = cmds.len();
     if n == 0 {
@@ -89,104 +180,421 @@ pub fn spawn_pipeline(cmds: Vec<Command>) -> io::Result<ProcessOutput> {
     }
 
     let mut prev_stdout: Option<std::process::ChildStdout> = None;
-    let mut early_children: Vec<std::process::Child> = Vec::new();
-    let mut last_child = None;
+    let mut children: Vec<std::process::Child> = Vec::new();
+    let mut threads = Vec::new();
+    let mut terminal = None;
+    let mut capture = Capture::default();
+    // the whole pipeline is watched, with the configuration of the
+    // last process
+    let mut watchdog = Watchdog::new(&cmds[n - 1]);
 
-    for (i, cmd) in cmds.into_iter().enumerate() {
+    for (i, mut cmd) in cmds.into_iter().enumerate() {
         let is_last = i == n - 1;
-        let mut command = StdCommand::from(cmd);
+        if is_last {
+            if cmd.pty.is_none() {
+                cmd.stdout.get_or_insert(Stdio::Piped);
+                cmd.stderr.get_or_insert(Stdio::Piped);
+            }
+            capture = cmd.capture.clone();
+        } else {
+            convert::reject_captured_fds(&cmd)?;
+        }
+        let tree = cmd.termination.tree;
+        let (mut command, mut setup) = convert::command(cmd)?;
 
         if let Some(stdout) = prev_stdout.take() {
             command.stdin(stdout);
         }
 
-        command.stdout(StdStdio::piped());
-        if is_last {
-            command.stderr(StdStdio::piped());
+        if !is_last {
+            command.stdout(StdStdio::piped());
         }
 
-        let mut child = command.spawn()?;
+        let mut child = command.spawn().map_err(|err| setup.spawn_error(err))?;
+        watchdog.watch(&mut child, tree)?;
+        // closes the parent copies of the child stdio, like the terminal
+        drop(command);
 
         if is_last {
-            last_child = Some(child);
+            terminal = setup.pty.take();
         } else {
             prev_stdout = child.stdout.take();
-            early_children.push(child);
         }
+
+        threads.push(FdThreads::start(setup));
+        children.push(child);
     }
 
-    let output = last_child.unwrap().wait_with_output()?;
+    let Recording {
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+    } = read_output(&mut children, terminal, &capture, &mut watchdog)?;
+    let status = watchdog.wait(&mut children)?;
+
+    let mut fds = BTreeMap::new();
 
-    for mut child in early_children {
-        let _ = child.wait();
+    for threads in threads {
+        fds.append(&mut threads.join()?);
     }
 
     Ok(ProcessOutput::SpawnedPipeline {
-        status: ExitStatus::new(output.status.code()),
-        stdout: output.stdout,
-        stderr: output.stderr,
+        status: ExitStatus::new(status.code()),
+        stdout,
+        stderr,
+        truncated,
+        transcript,
+        fds,
     })
 }
 
-/// Converts a [`Command`] builder into a [`std::process::Command`].
-impl From<Command> for StdCommand {
-    fn from(builder: Command) -> Self {
-        let mut command = StdCommand::new(&*builder.get_program());
+/// Reads the piped stdout and stderr of the last of the given
+/// children concurrently, until both reach EOF.
+///
+/// Streams that are not piped are returned as `None`. The output of
+/// the given terminal, if any, is read as stdout.
+///
+/// Each stream is read from its own thread, so that a child filling
+/// up one pipe while the other one is not drained cannot deadlock.
+/// Chunks are recorded according to the capture configuration,
+/// timestamped relatively to the spawn of the child, and forwarded to
+/// the parent's streams in tee mode. The children get terminated by
+/// the watchdog if they time out or if a stream exceeds its limit.
+fn read_output(
+    children: &mut [Child],
+    terminal: Option<PtyMaster>,
+    capture: &Capture,
+    watchdog: &mut Watchdog,
+) -> io::Result<Recording> {
+    // closes stdin so that the child does not wait for more input
+    let (stdout, stderr) = match children.last_mut() {
+        Some(child) => {
+            drop(child.stdin.take());
+            (child.stdout.take(), child.stderr.take())
+        }
+        None => (None, None),
+    };
 
-        if let Some(args) = builder.get_args() {
-            for arg in args {
-                command.arg(&*arg);
-            }
+    let captured = (stdout.is_some() || terminal.is_some(), stderr.is_some());
+    let (tx, rx) = mpsc::channel();
+
+    if let Some(terminal) = terminal {
+        spawn_reader(Stream::Stdout, terminal, tx.clone());
+    }
+
+    if let Some(stdout) = stdout {
+        spawn_reader(Stream::Stdout, stdout, tx.clone());
+    }
+
+    if let Some(stderr) = stderr {
+        spawn_reader(Stream::Stderr, stderr, tx.clone());
+    }
+
+    drop(tx);
+
+    let mut recorder = Recorder::new(capture);
+
+    loop {
+        let (stream, instant, chunk) = match watchdog.remaining() {
+            None => match rx.recv() {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+            Some(timeout) => match rx.recv_timeout(timeout) {
+                Ok(msg) => msg,
+                Err(RecvTimeoutError::Timeout) => {
+                    watchdog.poll(children)?;
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+        };
+
+        let chunk = chunk?;
+        let elapsed = instant.saturating_duration_since(watchdog.start);
+
+        if capture.tees(stream) {
+            tee(stream, &chunk)?;
         }
 
-        if let Some(envs) = builder.envs {
-            for (key, val) in envs {
-                command.env(key, val);
-            }
+        if recorder.record(stream, elapsed, &chunk) {
+            debug!("{stream:?} exceeded its limit, terminating process");
+            watchdog.terminate(children)?;
         }
+    }
 
-        if let Some(dir) = builder.current_dir {
-            command.current_dir(&dir);
+    Ok(recorder.finish(captured.0, captured.1))
+}
+
+/// Terminator of spawned children, sending the signals of their
+/// termination sequence.
+///
+/// The children of a pipeline share the same termination sequence.
+struct Watchdog {
+    terminator: Terminator,
+    start: Instant,
+    /// Targets of the watched children, by spawn order, taken once
+    /// the child got reaped.
+    #[cfg(unix)]
+    targets: Vec<Option<kill::Target>>,
+}
+
+impl Watchdog {
+    /// Interval at which a child with a deadline is polled while
+    /// waiting for its exit.
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    /// Creates a new watchdog for the given command, about to be
+    /// spawned.
+    fn new(cmd: &Command) -> Self {
+        Self {
+            terminator: Terminator::new(cmd.timeout, &cmd.termination),
+            start: Instant::now(),
+            #[cfg(unix)]
+            targets: Vec::new(),
         }
-        match builder.stdin {
-            Some(Stdio::Inherit) => {
-                command.stdin(StdStdio::inherit());
-            }
-            Some(Stdio::Null) => {
-                command.stdin(StdStdio::null());
+    }
+
+    /// Watches the given spawned child, and its whole process tree if
+    /// `tree` is `true`.
+    ///
+    /// The child is killed and reaped if it cannot be watched, since
+    /// it could not be terminated safely.
+    #[cfg(unix)]
+    fn watch(&mut self, child: &mut Child, tree: bool) -> io::Result<()> {
+        match kill::Target::new(child.id(), tree) {
+            Ok(target) => {
+                self.targets.push(Some(target));
+                Ok(())
             }
-            Some(Stdio::Piped) => {
-                command.stdin(StdStdio::piped());
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(err)
             }
-            None => (),
-        };
+        }
+    }
+
+    /// Watches the given spawned child, which has nothing to prepare
+    /// on this platform.
+    #[cfg(not(unix))]
+    fn watch(&mut self, _child: &mut Child, _tree: bool) -> io::Result<()> {
+        Ok(())
+    }
 
-        match builder.stdout {
-            Some(Stdio::Inherit) => {
-                command.stdout(StdStdio::inherit());
+    /// Returns the time remaining until the next deadline, if any.
+    fn remaining(&self) -> Option<Duration> {
+        let deadline = self.terminator.deadline()?;
+        Some(deadline.saturating_sub(self.start.elapsed()))
+    }
+
+    /// Starts the termination sequence of the given children.
+    fn terminate(&mut self, children: &mut [Child]) -> io::Result<()> {
+        match self.terminator.terminate(self.start.elapsed()) {
+            Some(signal) => self.send(children, signal),
+            None => Ok(()),
+        }
+    }
+
+    /// Makes the termination sequence of the given children progress.
+    fn poll(&mut self, children: &mut [Child]) -> io::Result<()> {
+        match self.terminator.poll(self.start.elapsed()) {
+            Some(signal) => self.send(children, signal),
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for the exit of the given children, making their
+    /// termination sequence progress meanwhile.
+    ///
+    /// Returns the exit status of the last child. Fails with
+    /// [`io::ErrorKind::TimedOut`] if the children timed out.
+    fn wait(&mut self, children: &mut [Child]) -> io::Result<StdExitStatus> {
+        let mut status = None;
+
+        for i in 0..children.len() {
+            status = Some(self.reap(children, i)?);
+        }
+
+        if self.terminator.timed_out() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "process ran past its timeout",
+            ));
+        }
+
+        status.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no process to wait"))
+    }
+
+    /// Waits for the exit of the child at the given index, making the
+    /// termination sequence of all the children progress meanwhile,
+    /// then reaps it.
+    fn reap(&mut self, children: &mut [Child], i: usize) -> io::Result<StdExitStatus> {
+        let status = loop {
+            // the pidfd of the child is polled until the deadline
+            #[cfg(target_os = "linux")]
+            if let Some(pidfd) = self.targets[i].as_ref().and_then(kill::Target::pidfd) {
+                if pidfd.wait_timeout(self.remaining())? {
+                    break children[i].wait()?;
+                }
+
+                self.poll(children)?;
+                continue;
             }
-            Some(Stdio::Null) => {
-                command.stdout(StdStdio::null());
+
+            let Some(remaining) = self.remaining() else {
+                break children[i].wait()?;
+            };
+
+            if let Some(status) = children[i].try_wait()? {
+                break status;
             }
-            Some(Stdio::Piped) => {
-                command.stdout(StdStdio::piped());
+
+            if remaining.is_zero() {
+                self.poll(children)?;
+            } else {
+                thread::sleep(remaining.min(Self::POLL_INTERVAL));
             }
-            None => (),
         };
 
-        match builder.stderr {
-            Some(Stdio::Inherit) => {
-                command.stderr(StdStdio::inherit());
-            }
-            Some(Stdio::Null) => {
-                command.stderr(StdStdio::null());
-            }
-            Some(Stdio::Piped) => {
-                command.stderr(StdStdio::piped());
+        // the child got reaped, its ID may be recycled
+        #[cfg(unix)]
+        {
+            self.targets[i] = None;
+        }
+
+        Ok(status)
+    }
+
+    /// Sends the given signal to the children not reaped yet, or to
+    /// their whole process tree.
+    #[cfg(unix)]
+    fn send(&self, _children: &mut [Child], signal: Signal) -> io::Result<()> {
+        for target in self.targets.iter().flatten() {
+            debug!("sends {signal:?} to {target:?}");
+            target.signal(signal)?;
+        }
+
+        Ok(())
+    }
+
+    /// Kills the children, which is the only termination supported on
+    /// this platform.
+    #[cfg(not(unix))]
+    fn send(&self, children: &mut [Child], _signal: Signal) -> io::Result<()> {
+        for child in children {
+            child.kill()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Forwards the given chunk to the matching stream of the parent
+/// process.
+pub(super) fn tee(stream: Stream, chunk: &[u8]) -> io::Result<()> {
+    match stream {
+        Stream::Stdout => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(chunk)?;
+            stdout.flush()
+        }
+        Stream::Stderr => io::stderr().lock().write_all(chunk),
+    }
+}
+
+/// Spawns a thread reading chunks from the given pipe and sending
+/// them to `tx`, until EOF or error.
+fn spawn_reader(
+    stream: Stream,
+    mut pipe: impl Read + Send + 'static,
+    tx: Sender<(Stream, Instant, io::Result<Vec<u8>>)>,
+) {
+    thread::spawn(move || {
+        let mut buf = [0; 8192];
+
+        loop {
+            let chunk = match pipe.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => Ok(buf[..n].to_vec()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => Err(err),
+            };
+
+            let is_err = chunk.is_err();
+
+            if tx.send((stream, Instant::now(), chunk)).is_err() || is_err {
+                break;
             }
-            None => (),
-        };
+        }
+    });
+}
+
+/// Threads serving the extra file descriptors of a spawned child.
+struct FdThreads {
+    writers: Vec<JoinHandle<io::Result<()>>>,
+    readers: Vec<(i32, JoinHandle<io::Result<Vec<u8>>>)>,
+}
+
+impl FdThreads {
+    /// Closes the child ends of the given extra file descriptors,
+    /// then writes the input pipes and reads the captured pipes, each
+    /// from its own thread.
+    ///
+    /// The terminal output, if not taken for capture, is drained and
+    /// discarded.
+    fn start(setup: ChildSetup) -> Self {
+        drop(setup.child_ends);
+
+        let writers = setup
+            .inputs
+            .into_iter()
+            .map(|(mut pipe, bytes)| {
+                thread::spawn(move || match pipe.write_all(&bytes) {
+                    // the child is free not to read its input
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                    res => res,
+                })
+            })
+            .collect();
+
+        if let Some(mut terminal) = setup.pty {
+            thread::spawn(move || io::copy(&mut terminal, &mut io::sink()));
+        }
+
+        let readers = setup
+            .captures
+            .into_iter()
+            .map(|(fd, mut pipe)| {
+                let reader = thread::spawn(move || {
+                    let mut bytes = Vec::new();
+                    pipe.read_to_end(&mut bytes)?;
+                    Ok(bytes)
+                });
+
+                (fd, reader)
+            })
+            .collect();
+
+        Self { writers, readers }
+    }
+
+    /// Waits for the threads to finish, returning the captured bytes
+    /// by descriptor number.
+    fn join(self) -> io::Result<BTreeMap<i32, Vec<u8>>> {
+        let panicked = |_| io::Error::other("extra fd thread panicked");
+
+        for writer in self.writers {
+            writer.join().map_err(panicked)??;
+        }
+
+        let mut fds = BTreeMap::new();
+
+        for (fd, reader) in self.readers {
+            fds.insert(fd, reader.join().map_err(panicked)??);
+        }
 
-        command
+        Ok(fds)
     }
 }
@@ -0,0 +1,83 @@
+//! Synthetic process outputs, shared by the runtimes that do not
+//! spawn processes.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    capture::Truncated,
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+    stdio::Stdio,
+};
+
+/// Synthetic output of a process.
+///
+/// Exits with `0` and writes nothing by default.
+#[derive(Clone, Debug)]
+pub(crate) struct Synthetic {
+    pub(crate) status: ExitStatus,
+    pub(crate) stdout: Vec<u8>,
+    pub(crate) stderr: Vec<u8>,
+    pub(crate) fds: BTreeMap<i32, Vec<u8>>,
+}
+
+impl Default for Synthetic {
+    fn default() -> Self {
+        Self {
+            status: ExitStatus::new(Some(0)),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            fds: BTreeMap::new(),
+        }
+    }
+}
+
+impl Synthetic {
+    /// Builds the output answering the given input.
+    ///
+    /// Streams are returned as captured the same way runtimes
+    /// capture them, according to the [`Stdio`] configuration of the
+    /// input.
+    pub(crate) fn into_output(self, input: &ProcessInput) -> ProcessOutput {
+        let status = self.status;
+
+        let last = match input {
+            ProcessInput::Spawn { .. } => return ProcessOutput::Spawned { status },
+            ProcessInput::SpawnIn { .. } => return ProcessOutput::SpawnedIn { status },
+            ProcessInput::SpawnOut { cmd } => Some(cmd),
+            ProcessInput::SpawnPipeline { cmds } => cmds.last(),
+        };
+
+        // streams are captured if piped or unset, and the terminal
+        // is captured as stdout in PTY mode
+        let pty = last.is_some_and(|cmd| cmd.pty.is_some());
+        let piped = |stdio: Option<&Stdio>| matches!(stdio, None | Some(Stdio::Piped));
+        let stdout = pty || piped(last.and_then(|cmd| cmd.stdout.as_ref()));
+        let stderr = !pty && piped(last.and_then(|cmd| cmd.stderr.as_ref()));
+
+        let stdout = stdout.then_some(self.stdout);
+        let stderr = stderr.then_some(self.stderr);
+        let truncated = Truncated::default();
+        let transcript = None;
+        let fds = self.fds;
+
+        match input {
+            ProcessInput::SpawnPipeline { .. } => ProcessOutput::SpawnedPipeline {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            },
+            _ => ProcessOutput::SpawnedOut {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            },
+        }
+    }
+}
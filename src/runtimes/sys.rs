@@ -0,0 +1,36 @@
+//! Thin wrappers around Unix system calls, shared by the std-based
+//! runtimes.
+
+use std::{io, os::fd::RawFd, time::Duration};
+
+/// Returns a descriptor to wait for the given events on with
+/// [`poll`].
+pub(crate) fn pollfd(fd: RawFd, events: libc::c_short) -> libc::pollfd {
+    libc::pollfd {
+        fd,
+        events,
+        revents: 0,
+    }
+}
+
+/// Waits for events on the given descriptors with `poll(2)`, for at
+/// most the given timeout, if any.
+///
+/// Returns the number of descriptors with events. The timeout is
+/// rounded up to the millisecond, so that a deadline is not missed.
+pub(crate) fn poll(fds: &mut [libc::pollfd], timeout: Option<Duration>) -> io::Result<usize> {
+    let timeout = match timeout {
+        None => -1,
+        Some(timeout) => timeout
+            .as_nanos()
+            .div_ceil(1_000_000)
+            .try_into()
+            .unwrap_or(libc::c_int::MAX),
+    };
+
+    // SAFETY: fds points to a slice of valid pollfds.
+    match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } {
+        -1 => Err(io::Error::last_os_error()),
+        n => Ok(n as usize),
+    }
+}
@@ -0,0 +1,416 @@
+//! Conformance test-kit for runtimes.
+//!
+//! The kit exercises every [`ProcessInput`] variant against the
+//! `handle` function of a runtime, and checks that the runtime
+//! behaves like the [`std`](super::std) one: exit statuses, captured
+//! streams, large outputs, environment, working directory, stdin,
+//! pipelines, timeouts and errors.
+//!
+//! ```rust,ignore
+//! #[test]
+//! fn conformance() {
+//!     io_process::runtimes::testkit::run(my_runtime::handle).unwrap();
+//! }
+//! ```
+//!
+//! Async runtimes can be checked by blocking on their futures, for
+//! example with `|input| rt.block_on(handle(input))`.
+//!
+//! The checks spawn POSIX utilities (`sh`, `cat`, `head`, `pwd`,
+//! `sleep` and `wc`), so they need a Unix-like environment.
+
+use std::{
+    fmt::Debug,
+    io::{self, ErrorKind},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::{
+    command::Command,
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+    stdio::Stdio,
+};
+
+/// Handle function of the runtime under test.
+pub type Handle<'a> = dyn FnMut(ProcessInput) -> io::Result<ProcessOutput> + 'a;
+
+/// Size of the large outputs and inputs, in bytes.
+const LARGE: usize = 1024 * 1024;
+
+/// Conformance checks run by [`run`], in order.
+pub const CHECKS: &[Check] = &[
+    Check::new("spawn-status", spawn_status),
+    Check::new("spawn-out-streams", spawn_out_streams),
+    Check::new("spawn-out-uncaptured", spawn_out_uncaptured),
+    Check::new("spawn-out-large", spawn_out_large),
+    Check::new("spawn-in-stdin", spawn_in_stdin),
+    Check::new("spawn-in-large", spawn_in_large),
+    Check::new("env", env),
+    Check::new("current-dir", current_dir),
+    Check::new("pipeline", pipeline),
+    Check::new("pipeline-status", pipeline_status),
+    Check::new("pipeline-empty", pipeline_empty),
+    Check::new("not-found", not_found),
+    Check::new("timeout", timeout),
+];
+
+/// Failure of a single conformance [`Check`].
+#[derive(Debug, Error)]
+#[error("Check {check} failed: {reason}")]
+pub struct Failure {
+    /// The name of the check.
+    pub check: &'static str,
+    /// Why the runtime did not conform.
+    pub reason: String,
+}
+
+/// Error emitted by [`run`], listing all the failed checks.
+#[derive(Debug, Error)]
+#[error(
+    "{} conformance check(s) failed:\n{}",
+    .failures.len(),
+    .failures.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"),
+)]
+pub struct ConformanceError {
+    /// The failures, in the order of [`CHECKS`].
+    pub failures: Vec<Failure>,
+}
+
+/// Conformance check, exercising one behavior of a runtime.
+#[derive(Clone, Copy)]
+pub struct Check {
+    name: &'static str,
+    run: fn(&mut Handle<'_>) -> Result<(), String>,
+}
+
+impl Check {
+    const fn new(name: &'static str, run: fn(&mut Handle<'_>) -> Result<(), String>) -> Self {
+        Self { name, run }
+    }
+
+    /// Returns the name of the check.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Runs the check against the given handle function.
+    pub fn run(&self, handle: &mut Handle<'_>) -> Result<(), Failure> {
+        (self.run)(handle).map_err(|reason| Failure {
+            check: self.name,
+            reason,
+        })
+    }
+}
+
+impl Debug for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Check").field("name", &self.name).finish()
+    }
+}
+
+/// Runs all the [`CHECKS`] against the given handle function.
+///
+/// All checks are run, even after a failure, so that the error lists
+/// every non-conformance at once.
+pub fn run(
+    mut handle: impl FnMut(ProcessInput) -> io::Result<ProcessOutput>,
+) -> Result<(), ConformanceError> {
+    let failures: Vec<_> = CHECKS
+        .iter()
+        .filter_map(|check| check.run(&mut handle).err())
+        .collect();
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(ConformanceError { failures })
+    }
+}
+
+/// Returns `Err` with the given formatted reason if the condition
+/// does not hold.
+macro_rules! ensure {
+    ($cond:expr, $($reason:tt)+) => {
+        if !$cond {
+            return Err(format!($($reason)+));
+        }
+    };
+}
+
+fn sh(script: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script);
+    command
+}
+
+fn expect_status(output: io::Result<ProcessOutput>) -> Result<ExitStatus, String> {
+    match output.map_err(|err| format!("unexpected error: {err}"))? {
+        ProcessOutput::Spawned { status } | ProcessOutput::SpawnedIn { status } => Ok(status),
+        output => Err(format!("unexpected output: {output:?}")),
+    }
+}
+
+type Streams = (ExitStatus, Option<Vec<u8>>, Option<Vec<u8>>);
+
+fn expect_streams(output: io::Result<ProcessOutput>) -> Result<Streams, String> {
+    match output.map_err(|err| format!("unexpected error: {err}"))? {
+        ProcessOutput::SpawnedOut {
+            status,
+            stdout,
+            stderr,
+            ..
+        }
+        | ProcessOutput::SpawnedPipeline {
+            status,
+            stdout,
+            stderr,
+            ..
+        } => Ok((status, stdout, stderr)),
+        output => Err(format!("unexpected output: {output:?}")),
+    }
+}
+
+fn expect_error(output: io::Result<ProcessOutput>, kind: ErrorKind) -> Result<(), String> {
+    match output {
+        Err(err) if err.kind() == kind => Ok(()),
+        Err(err) => Err(format!(
+            "expected {kind:?} error, got {:?}: {err}",
+            err.kind()
+        )),
+        Ok(output) => Err(format!("expected {kind:?} error, got {output:?}")),
+    }
+}
+
+fn spawn_status(handle: &mut Handle<'_>) -> Result<(), String> {
+    let cmd = sh("exit 7");
+    let status = expect_status(handle(ProcessInput::Spawn { cmd }))?;
+    ensure!(
+        status.code() == Some(7),
+        "expected exit code 7, got {status:?}"
+    );
+
+    let cmd = sh("exit 0");
+    let status = expect_status(handle(ProcessInput::Spawn { cmd }))?;
+    ensure!(status.success(), "expected success, got {status:?}");
+
+    Ok(())
+}
+
+fn spawn_out_streams(handle: &mut Handle<'_>) -> Result<(), String> {
+    let cmd = sh("printf out; printf err >&2; exit 3");
+    let (status, stdout, stderr) = expect_streams(handle(ProcessInput::SpawnOut { cmd }))?;
+
+    ensure!(
+        status.code() == Some(3),
+        "expected exit code 3, got {status:?}"
+    );
+    ensure!(
+        stdout.as_deref() == Some(b"out"),
+        "unexpected stdout {stdout:?}"
+    );
+    ensure!(
+        stderr.as_deref() == Some(b"err"),
+        "unexpected stderr {stderr:?}"
+    );
+
+    Ok(())
+}
+
+fn spawn_out_uncaptured(handle: &mut Handle<'_>) -> Result<(), String> {
+    let mut cmd = sh("printf out; printf err >&2");
+    cmd.stdout(Stdio::Null);
+    let (_, stdout, stderr) = expect_streams(handle(ProcessInput::SpawnOut { cmd }))?;
+
+    ensure!(
+        stdout.is_none(),
+        "expected uncaptured stdout, got {stdout:?}"
+    );
+    ensure!(
+        stderr.as_deref() == Some(b"err"),
+        "unexpected stderr {stderr:?}"
+    );
+
+    Ok(())
+}
+
+fn spawn_out_large(handle: &mut Handle<'_>) -> Result<(), String> {
+    // fills stderr while stdout is still open, which deadlocks
+    // runtimes reading the streams one after the other
+    let script = format!("head -c {LARGE} /dev/zero; head -c {LARGE} /dev/zero >&2");
+    let cmd = sh(&script);
+    let (status, stdout, stderr) = expect_streams(handle(ProcessInput::SpawnOut { cmd }))?;
+
+    ensure!(status.success(), "expected success, got {status:?}");
+
+    for (name, stream) in [("stdout", stdout), ("stderr", stderr)] {
+        let len = stream.map(|bytes| bytes.len());
+        ensure!(
+            len == Some(LARGE),
+            "expected {LARGE} {name} bytes, got {len:?}"
+        );
+    }
+
+    Ok(())
+}
+
+fn spawn_in_stdin(handle: &mut Handle<'_>) -> Result<(), String> {
+    let cmd = sh("test \"$(cat)\" = hello");
+    let stdin = b"hello".to_vec();
+    let status = expect_status(handle(ProcessInput::SpawnIn { cmd, stdin }))?;
+    ensure!(status.success(), "stdin not fed, got {status:?}");
+
+    // empty stdin is closed right away
+    let cmd = sh("test -z \"$(cat)\"");
+    let stdin = Vec::new();
+    let status = expect_status(handle(ProcessInput::SpawnIn { cmd, stdin }))?;
+    ensure!(status.success(), "stdin not empty, got {status:?}");
+
+    Ok(())
+}
+
+fn spawn_in_large(handle: &mut Handle<'_>) -> Result<(), String> {
+    let cmd = sh(&format!("test $(wc -c) -eq {LARGE}"));
+    let stdin = vec![b'x'; LARGE];
+    let status = expect_status(handle(ProcessInput::SpawnIn { cmd, stdin }))?;
+    ensure!(
+        status.success(),
+        "expected {LARGE} stdin bytes, got {status:?}"
+    );
+
+    // the process may exit without reading its whole stdin
+    let cmd = sh("exit 0");
+    let stdin = vec![b'x'; LARGE];
+    let status = expect_status(handle(ProcessInput::SpawnIn { cmd, stdin }))?;
+    ensure!(status.success(), "expected success, got {status:?}");
+
+    Ok(())
+}
+
+fn env(handle: &mut Handle<'_>) -> Result<(), String> {
+    let mut cmd = sh("printf '%s:' \"$IO_PROCESS_TESTKIT\"; test -n \"$PATH\"");
+    cmd.env("IO_PROCESS_TESTKIT", "hello world");
+    let (status, stdout, _) = expect_streams(handle(ProcessInput::SpawnOut { cmd }))?;
+
+    let expected: &[u8] = b"hello world:";
+    ensure!(
+        stdout.as_deref() == Some(expected),
+        "unexpected stdout {stdout:?}"
+    );
+    ensure!(
+        status.success(),
+        "inherited environment lost, got {status:?}"
+    );
+
+    Ok(())
+}
+
+fn current_dir(handle: &mut Handle<'_>) -> Result<(), String> {
+    let mut cmd = Command::new("pwd");
+    cmd.current_dir("/");
+    let (_, stdout, _) = expect_streams(handle(ProcessInput::SpawnOut { cmd }))?;
+
+    let expected: &[u8] = b"/\n";
+    ensure!(
+        stdout.as_deref() == Some(expected),
+        "unexpected stdout {stdout:?}"
+    );
+
+    Ok(())
+}
+
+fn pipeline(handle: &mut Handle<'_>) -> Result<(), String> {
+    let cmds = vec![
+        sh("printf 'a\\nb\\n'"),
+        Command::new("cat"),
+        sh("cat; printf err3 >&2"),
+    ];
+    let (status, stdout, stderr) = expect_streams(handle(ProcessInput::SpawnPipeline { cmds }))?;
+
+    let expected: &[u8] = b"a\nb\n";
+    ensure!(status.success(), "expected success, got {status:?}");
+    ensure!(
+        stdout.as_deref() == Some(expected),
+        "unexpected stdout {stdout:?}"
+    );
+    ensure!(
+        stderr.as_deref() == Some(b"err3"),
+        "unexpected stderr {stderr:?}"
+    );
+
+    // a single command behaves like a spawn out
+    let cmds = vec![sh("printf out")];
+    let (_, stdout, _) = expect_streams(handle(ProcessInput::SpawnPipeline { cmds }))?;
+    ensure!(
+        stdout.as_deref() == Some(b"out"),
+        "unexpected stdout {stdout:?}"
+    );
+
+    Ok(())
+}
+
+fn pipeline_status(handle: &mut Handle<'_>) -> Result<(), String> {
+    let cmds = vec![sh("exit 0"), sh("cat >/dev/null; exit 5")];
+    let (status, _, _) = expect_streams(handle(ProcessInput::SpawnPipeline { cmds }))?;
+    ensure!(
+        status.code() == Some(5),
+        "expected last exit code 5, got {status:?}"
+    );
+
+    let cmds = vec![sh("exit 5"), sh("cat >/dev/null")];
+    let (status, _, _) = expect_streams(handle(ProcessInput::SpawnPipeline { cmds }))?;
+    ensure!(
+        status.success(),
+        "expected last process success, got {status:?}"
+    );
+
+    Ok(())
+}
+
+fn pipeline_empty(handle: &mut Handle<'_>) -> Result<(), String> {
+    let cmds = Vec::new();
+    expect_error(
+        handle(ProcessInput::SpawnPipeline { cmds }),
+        ErrorKind::InvalidInput,
+    )
+}
+
+fn not_found(handle: &mut Handle<'_>) -> Result<(), String> {
+    let missing = || Command::new("/nonexistent/io-process-testkit");
+
+    let inputs = [
+        ProcessInput::Spawn { cmd: missing() },
+        ProcessInput::SpawnOut { cmd: missing() },
+        ProcessInput::SpawnIn {
+            cmd: missing(),
+            stdin: b"hello".to_vec(),
+        },
+        ProcessInput::SpawnPipeline {
+            cmds: vec![Command::new("true"), missing()],
+        },
+    ];
+
+    for input in inputs {
+        expect_error(handle(input), ErrorKind::NotFound)?;
+    }
+
+    Ok(())
+}
+
+fn timeout(handle: &mut Handle<'_>) -> Result<(), String> {
+    let mut cmd = Command::new("sleep");
+    cmd.arg("10").timeout(Duration::from_millis(100));
+
+    let start = Instant::now();
+    expect_error(handle(ProcessInput::SpawnOut { cmd }), ErrorKind::TimedOut)?;
+
+    let elapsed = start.elapsed();
+    ensure!(
+        elapsed < Duration::from_secs(5),
+        "process not terminated on timeout, took {elapsed:?}",
+    );
+
+    Ok(())
+}
@@ -1,18 +1,44 @@
 //! Async process runtime backed by [`tokio::process`].
 
-use std::{io, process::Stdio as StdStdio};
+use std::{
+    collections::BTreeMap,
+    io,
+    pin::Pin,
+    process::{ExitStatus as StdExitStatus, Stdio as StdStdio},
+    task::{Context, Poll},
+    time::Duration,
+};
 
-use tokio::{io::AsyncWriteExt, process::Command as TokioCommand};
+#[cfg(unix)]
+use tokio::io::unix::AsyncFd;
+use tokio::{
+    io::{AsyncRead, AsyncWriteExt, Interest, ReadBuf},
+    process::{Child, ChildStderr, ChildStdin, ChildStdout, Command as TokioCommand},
+    task::JoinHandle,
+};
 
+#[cfg(target_os = "linux")]
+use super::pidfd::Pidfd;
+use super::{
+    convert::{self, ChildSetup, PtyMaster},
+    driver::{self, Reader, Runtime},
+};
 use crate::{
+    capture::Stream,
     command::Command,
     io::{ProcessInput, ProcessOutput},
-    status::ExitStatus,
-    stdio::Stdio,
+    termination::OnDrop,
 };
 
 /// Processes a [`ProcessInput`] request asynchronously using
 /// [`tokio::process`].
+///
+/// The returned future is cancellation-safe: when dropped before
+/// completion, spawned processes are handled according to their
+/// [`Termination::on_drop`] configuration, which kills and reaps them
+/// by default.
+///
+/// [`Termination::on_drop`]: crate::termination::Termination::on_drop
 pub async fn handle(input: ProcessInput) -> io::Result<ProcessOutput> {
     match input {
         ProcessInput::Spawn { cmd } => spawn(cmd).await,
@@ -23,179 +49,393 @@ pub async fn handle(input: ProcessInput) -> io::Result<ProcessOutput> {
 }
 
 /// Spawns a process and waits for its exit status.
+///
+/// Fails with [`io::ErrorKind::TimedOut`] if the process ran past its
+/// timeout, after terminating it.
 pub async fn spawn(cmd: Command) -> io::Result<ProcessOutput> {
-    let mut command = TokioCommand::from(cmd);
-    let status = command.status().await?;
-
-    Ok(ProcessOutput::Spawned {
-        status: ExitStatus::new(status.code()),
-    })
+    driver::spawn::<Tokio>(cmd).await
 }
 
 /// Spawns a process, captures its stdout and stderr, and waits for
 /// its exit status.
 ///
-/// Overrides the command's stdout and stderr to [`StdStdio::piped`]
-/// regardless of the [`Stdio`] configuration on the command.
+/// Stdout and stderr are captured if their [`Stdio`] configuration
+/// is [`Stdio::Piped`] or unset, and returned as `None` otherwise.
+/// In PTY mode, the terminal output is captured as stdout instead.
+/// Bytes past the [`Capture`] limits of the command are drained but
+/// not stored.
+///
+/// Fails with [`io::ErrorKind::TimedOut`] if the process ran past its
+/// timeout, after terminating it.
+///
+/// [`Stdio`]: crate::stdio::Stdio
+/// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
+/// [`Capture`]: crate::capture::Capture
 pub async fn spawn_out(cmd: Command) -> io::Result<ProcessOutput> {
-    let mut command = TokioCommand::from(cmd);
-    command.stdout(StdStdio::piped());
-    command.stderr(StdStdio::piped());
-
-    let child = command.spawn()?;
-    let output = child.wait_with_output().await?;
-
-    Ok(ProcessOutput::SpawnedOut {
-        status: ExitStatus::new(output.status.code()),
-        stdout: output.stdout,
-        stderr: output.stderr,
-    })
+    driver::spawn_out::<Tokio>(cmd).await
 }
 
 /// Spawns a process, feeds bytes to its stdin, and waits for its exit
 /// status.
 ///
-/// Overrides the command's stdin to [`StdStdio::piped`] regardless of
-/// the [`Stdio`] configuration on the command.
+/// The bytes left unread when the process exits are discarded.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if the [`Stdio`]
+/// configuration of the command's stdin is neither [`Stdio::Piped`]
+/// nor unset, and with [`io::ErrorKind::TimedOut`] if the process ran
+/// past its timeout, after terminating it.
+///
+/// [`Stdio`]: crate::stdio::Stdio
+/// [`Stdio::Piped`]: crate::stdio::Stdio::Piped
 pub async fn spawn_in(cmd: Command, stdin: Vec<u8>) -> io::Result<ProcessOutput> {
-    let mut command = TokioCommand::from(cmd);
-    command.stdin(StdStdio::piped());
-
-    let mut child = command.spawn()?;
-
-    if let Some(mut handle) = child.stdin.take() {
-        handle.write_all(&stdin).await?;
-        handle.shutdown().await?;
-    }
-
-    let status = child.wait().await?;
-
-    Ok(ProcessOutput::SpawnedIn {
-        status: ExitStatus::new(status.code()),
-    })
+    driver::spawn_in::<Tokio>(cmd, stdin).await
 }
 
 /// Spawns a pipeline of processes, piping each process's stdout into
 /// the next process's stdin.
 ///
-/// Returns the last process's exit status, stdout, and stderr.
+/// Returns the last process's exit status, stdout, stderr and extra
+/// file descriptors, captured the same way as [`spawn_out`]. The
+/// timeout and the termination configuration of the last process
+/// apply to every process of the pipeline.
 pub async fn spawn_pipeline(cmds: Vec<Command>) -> io::Result<ProcessOutput> {
-    let n = cmds.len();
-    if n == 0 {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "empty pipeline",
-        ));
-    }
-
-    let mut prev_stdout: Option<tokio::process::ChildStdout> = None;
-    let mut early_children: Vec<tokio::process::Child> = Vec::new();
-    let mut last_child = None;
-
-    for (i, cmd) in cmds.into_iter().enumerate() {
-        let is_last = i == n - 1;
-        let mut command = TokioCommand::from(cmd);
-
-        if let Some(stdout) = prev_stdout.take() {
-            #[cfg(unix)]
-            if let Ok(fd) = stdout.into_owned_fd() {
-                command.stdin(fd);
-            }
+    driver::spawn_pipeline::<Tokio>(cmds).await
+}
 
-            #[cfg(windows)]
-            if let Ok(handle) = stdout.into_owned_handle() {
-                command.stdin(handle);
-            }
-        }
+/// Executor glue of the tokio runtime.
+struct Tokio;
+
+impl Runtime for Tokio {
+    type Command = TokioCommand;
+    type Child = Child;
+    type Stdin = ChildStdin;
+    type Stdout = Pipe<ChildStdout>;
+    type Stderr = Pipe<ChildStderr>;
+    #[cfg(unix)]
+    type Pty = Pipe<AsyncPty>;
+    #[cfg(not(unix))]
+    type Pty = driver::NoPty;
+    type FdTasks = FdTasks;
+
+    fn command(cmd: Command) -> io::Result<(TokioCommand, ChildSetup)> {
+        command(cmd)
+    }
+
+    fn spawn(command: &mut TokioCommand) -> io::Result<Child> {
+        command.spawn()
+    }
 
+    fn pipe_stdout(command: &mut TokioCommand) {
         command.stdout(StdStdio::piped());
-        if is_last {
-            command.stderr(StdStdio::piped());
+    }
+
+    async fn pipe_into(child: &mut Child, command: &mut TokioCommand) -> io::Result<()> {
+        let Some(stdout) = child.stdout.take() else {
+            return Ok(());
+        };
+
+        #[cfg(unix)]
+        command.stdin(stdout.into_owned_fd()?);
+
+        #[cfg(windows)]
+        command.stdin(stdout.into_owned_handle()?);
+
+        Ok(())
+    }
+
+    fn stdin(child: &mut Child) -> Option<ChildStdin> {
+        child.stdin.take()
+    }
+
+    async fn feed(mut stdin: ChildStdin, bytes: &[u8]) -> io::Result<()> {
+        stdin.write_all(bytes).await?;
+        stdin.shutdown().await
+    }
+
+    fn start(setup: ChildSetup) -> io::Result<FdTasks> {
+        FdTasks::start(setup)
+    }
+
+    async fn join(tasks: FdTasks) -> io::Result<BTreeMap<i32, Vec<u8>>> {
+        tasks.join().await
+    }
+
+    fn id(child: &Child) -> Option<u32> {
+        child.id()
+    }
+
+    fn pipes(child: &mut Child) -> (Option<Self::Stdout>, Option<Self::Stderr>) {
+        drop(child.stdin.take());
+        (child.stdout.take().map(Pipe), child.stderr.take().map(Pipe))
+    }
+
+    #[cfg(unix)]
+    fn pty(master: PtyMaster) -> io::Result<Self::Pty> {
+        AsyncPty::new(master).map(Pipe)
+    }
+
+    #[cfg(not(unix))]
+    fn pty(master: PtyMaster) -> io::Result<Self::Pty> {
+        driver::NoPty::new(master)
+    }
+
+    async fn wait(child: &mut Child) -> io::Result<StdExitStatus> {
+        child.wait().await
+    }
+
+    /// Registers the pidfd in the reactor, which reports it readable
+    /// once the process exited.
+    #[cfg(target_os = "linux")]
+    async fn exited(pidfd: &Pidfd) -> io::Result<()> {
+        use std::os::fd::AsFd;
+
+        let fd = AsyncFd::with_interest(pidfd.as_fd(), Interest::READABLE)?;
+
+        loop {
+            let mut guard = fd.readable().await?;
+
+            if pidfd.has_exited()? {
+                return Ok(());
+            }
+
+            guard.clear_ready();
         }
+    }
+
+    #[cfg(not(unix))]
+    fn kill(child: &mut Child) -> io::Result<()> {
+        child.start_kill()
+    }
 
-        let mut child = command.spawn()?;
+    async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    async fn tee(stream: Stream, chunk: &[u8]) -> io::Result<()> {
+        match stream {
+            Stream::Stdout => {
+                let mut stdout = tokio::io::stdout();
+                stdout.write_all(chunk).await?;
+                stdout.flush().await
+            }
+            Stream::Stderr => tokio::io::stderr().write_all(chunk).await,
+        }
+    }
 
-        if is_last {
-            last_child = Some(child);
-        } else {
-            prev_stdout = child.stdout.take();
-            early_children.push(child);
+    /// Defers to a task of the current tokio runtime, or runs the
+    /// function right away outside of any runtime.
+    #[cfg(unix)]
+    fn defer(delay: Duration, f: impl FnOnce() + Send + 'static) {
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    f();
+                });
+            }
+            Err(_) => f(),
         }
     }
+}
+
+/// Reader of a tokio stream.
+struct Pipe<R>(R);
 
-    let output = last_child.unwrap().wait_with_output().await?;
+impl<R: AsyncRead + Unpin> Reader for Pipe<R> {
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        let mut buf = ReadBuf::new(buf);
 
-    for mut child in early_children {
-        let _ = child.wait().await;
+        match Pin::new(&mut self.0).poll_read(cx, &mut buf) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(buf.filled().len())),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
     }
+}
 
-    Ok(ProcessOutput::SpawnedPipeline {
-        status: ExitStatus::new(output.status.code()),
-        stdout: output.stdout,
-        stderr: output.stderr,
-    })
+/// Tasks serving the extra file descriptors of a spawned child.
+#[derive(Default)]
+struct FdTasks {
+    writers: Vec<JoinHandle<io::Result<()>>>,
+    readers: Vec<(i32, JoinHandle<io::Result<Vec<u8>>>)>,
 }
 
-/// Converts a [`Command`] builder into a [`tokio::process::Command`].
-impl From<Command> for TokioCommand {
-    fn from(builder: Command) -> Self {
-        let mut command = TokioCommand::new(&*builder.get_program());
+impl FdTasks {
+    /// Closes the child ends of the given extra file descriptors,
+    /// then writes the input pipes and reads the captured pipes, each
+    /// from its own task.
+    ///
+    /// The terminal output, if not taken for capture, is drained and
+    /// discarded.
+    #[cfg(unix)]
+    fn start(setup: ChildSetup) -> io::Result<Self> {
+        use tokio::{io::AsyncReadExt, net::unix::pipe};
+
+        drop(setup.child_ends);
+
+        let mut tasks = Self::default();
+
+        if let Some(terminal) = setup.pty {
+            let mut terminal = AsyncPty::new(terminal)?;
+            tokio::spawn(
+                async move { tokio::io::copy(&mut terminal, &mut tokio::io::sink()).await },
+            );
+        }
 
-        if let Some(args) = builder.get_args() {
-            for arg in args {
-                command.arg(&*arg);
-            }
+        for (pipe, bytes) in setup.inputs {
+            let mut pipe = pipe::Sender::from_file(pipe)?;
+
+            tasks.writers.push(tokio::spawn(async move {
+                match pipe.write_all(&bytes).await {
+                    // the child is free not to read its input
+                    Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                    res => res,
+                }
+            }));
         }
 
-        if let Some(envs) = builder.envs {
-            for (key, val) in envs {
-                command.env(key, val);
-            }
+        for (fd, pipe) in setup.captures {
+            let mut pipe = pipe::Receiver::from_file(pipe)?;
+
+            let reader = tokio::spawn(async move {
+                let mut bytes = Vec::new();
+                pipe.read_to_end(&mut bytes).await?;
+                Ok(bytes)
+            });
+
+            tasks.readers.push((fd, reader));
         }
 
-        if let Some(dir) = builder.current_dir {
-            command.current_dir(&dir);
+        Ok(tasks)
+    }
+
+    /// Extra file descriptors are not supported on this platform, and
+    /// rejected when converting the command.
+    #[cfg(not(unix))]
+    fn start(_setup: ChildSetup) -> io::Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Waits for the tasks to finish, returning the captured bytes by
+    /// descriptor number.
+    async fn join(self) -> io::Result<BTreeMap<i32, Vec<u8>>> {
+        for writer in self.writers {
+            writer.await??;
         }
 
-        match builder.stdin {
-            Some(Stdio::Inherit) => {
-                command.stdin(StdStdio::inherit());
-            }
-            Some(Stdio::Null) => {
-                command.stdin(StdStdio::null());
-            }
-            Some(Stdio::Piped) => {
-                command.stdin(StdStdio::piped());
-            }
-            None => (),
-        };
+        let mut fds = BTreeMap::new();
 
-        match builder.stdout {
-            Some(Stdio::Inherit) => {
-                command.stdout(StdStdio::inherit());
-            }
-            Some(Stdio::Null) => {
-                command.stdout(StdStdio::null());
-            }
-            Some(Stdio::Piped) => {
-                command.stdout(StdStdio::piped());
-            }
-            None => (),
-        };
+        for (fd, reader) in self.readers {
+            fds.insert(fd, reader.await??);
+        }
 
-        match builder.stderr {
-            Some(Stdio::Inherit) => {
-                command.stderr(StdStdio::inherit());
-            }
-            Some(Stdio::Null) => {
-                command.stderr(StdStdio::null());
+        Ok(fds)
+    }
+}
+
+/// Async reader of the master side of a pseudo-terminal.
+#[cfg(unix)]
+struct AsyncPty(AsyncFd<PtyMaster>);
+
+#[cfg(unix)]
+impl AsyncPty {
+    /// Switches the given master side to non-blocking mode, and
+    /// registers it in the reactor.
+    fn new(master: PtyMaster) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let fd = master.0.as_raw_fd();
+
+        // SAFETY: the descriptor is owned by the master side, which
+        // is alive.
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+
+            if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+                return Err(io::Error::last_os_error());
             }
-            Some(Stdio::Piped) => {
-                command.stderr(StdStdio::piped());
+        }
+
+        Ok(Self(AsyncFd::new(master)?))
+    }
+}
+
+#[cfg(unix)]
+impl AsyncRead for AsyncPty {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        use std::io::Read;
+
+        let this = self.get_mut();
+
+        loop {
+            let mut guard = match this.0.poll_read_ready_mut(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let unfilled = buf.initialize_unfilled();
+
+            match guard.try_io(|master| master.get_mut().read(unfilled)) {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                }
+                Ok(Err(err)) => return Poll::Ready(Err(err)),
+                Err(_would_block) => continue,
             }
-            None => (),
-        };
+        }
+    }
+}
+
+/// Converts a [`Command`] into a [`tokio::process::Command`], setting
+/// up its extra file descriptors.
+///
+/// Unless the command needs to be detached or gets terminated by the
+/// watchdog, see [`driver::kills_on_drop`], the child is killed when
+/// its handle gets dropped, for example when the future of the
+/// runtime got cancelled. It is reaped in the background by tokio.
+fn command(cmd: Command) -> io::Result<(TokioCommand, ChildSetup)> {
+    let kills = driver::kills_on_drop(&cmd.termination);
+    let (command, setup) = convert::command(cmd)?;
+    let mut command = TokioCommand::from(command);
+    command.kill_on_drop(kills);
+    Ok((command, setup))
+}
+
+/// Converts a [`Command`] builder into a [`tokio::process::Command`].
+///
+/// The conversion opens the file-backed [`Stdio`] of the builder, so
+/// it fails for the same reasons as [`Command::into_std_command`].
+///
+/// [`Stdio`]: crate::stdio::Stdio
+impl TryFrom<Command> for TokioCommand {
+    type Error = io::Error;
+
+    fn try_from(builder: Command) -> io::Result<Self> {
+        builder.into_tokio_command()
+    }
+}
 
-        command
+impl Command {
+    /// Converts the builder into a [`tokio::process::Command`],
+    /// opening its file-backed [`Stdio`].
+    ///
+    /// The child is killed on drop according to the
+    /// [`Termination::on_drop`] configuration of the builder.
+    ///
+    /// Fails for the same reasons as [`Command::into_std_command`].
+    ///
+    /// [`Stdio`]: crate::stdio::Stdio
+    /// [`Termination::on_drop`]: crate::termination::Termination::on_drop
+    pub fn into_tokio_command(self) -> io::Result<TokioCommand> {
+        let on_drop = self.termination.on_drop;
+        let mut command = TokioCommand::from(self.into_std_command()?);
+        command.kill_on_drop(on_drop == OnDrop::Kill);
+        Ok(command)
     }
 }
@@ -0,0 +1,259 @@
+//! Serialization of the [`Command`] attributes that are not plain
+//! strings nor numbers.
+//!
+//! [`Command`]: crate::command::Command
+
+use core::fmt;
+
+use alloc::string::String;
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{Error, MapAccess, Visitor},
+    ser::SerializeMap,
+};
+
+use super::bytes::{ByteBuf, Bytes};
+#[cfg(unix)]
+use crate::limit::{Resource, Rlimit};
+use crate::{capture::LimitAction, fd::ExtraFd, pty::Pty, termination::OnDrop};
+
+const EXTRA_FD_VARIANTS: &[&str] = &["capture", "input", "read", "write", "append"];
+
+const PTY_FIELDS: &[&str] = &["rows", "cols"];
+
+const LIMIT_ACTION_VARIANTS: &[&str] = &["truncate", "kill"];
+
+const ON_DROP_VARIANTS: &[&str] = &["kill", "detach"];
+
+#[cfg(unix)]
+const RESOURCE_VARIANTS: &[&str] = &[
+    "address-space",
+    "cpu",
+    "open-files",
+    "core-size",
+    "file-size",
+];
+
+#[cfg(unix)]
+const RLIMIT_FIELDS: &[&str] = &["soft", "hard"];
+
+impl Serialize for ExtraFd {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            ExtraFd::Capture => serializer.serialize_str("capture"),
+            ExtraFd::Input(bytes) => serialize_entry(serializer, "input", &Bytes(bytes)),
+            ExtraFd::ReadFile(path) => serialize_entry(serializer, "read", path),
+            ExtraFd::WriteFile(path) => serialize_entry(serializer, "write", path),
+            ExtraFd::AppendFile(path) => serialize_entry(serializer, "append", path),
+        }
+    }
+}
+
+/// Serializes a single-entry table.
+fn serialize_entry<S: Serializer>(
+    serializer: S,
+    key: &str,
+    val: &impl Serialize,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(key, val)?;
+    map.end()
+}
+
+impl<'de> Deserialize<'de> for ExtraFd {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ExtraFd, D::Error> {
+        deserializer.deserialize_any(ExtraFdVisitor)
+    }
+}
+
+struct ExtraFdVisitor;
+
+impl<'de> Visitor<'de> for ExtraFdVisitor {
+    type Value = ExtraFd;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(
+            "\"capture\" or a table with input bytes, or a read, write or append file path",
+        )
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        match v {
+            "capture" => Ok(ExtraFd::Capture),
+            v => Err(E::unknown_variant(v, EXTRA_FD_VARIANTS)),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let Some(key) = map.next_key::<String>()? else {
+            return Err(Error::custom("fd table cannot be empty"));
+        };
+
+        let fd = match key.as_str() {
+            "input" => ExtraFd::Input(map.next_value::<ByteBuf>()?.0),
+            "read" => ExtraFd::ReadFile(map.next_value()?),
+            "write" => ExtraFd::WriteFile(map.next_value()?),
+            "append" => ExtraFd::AppendFile(map.next_value()?),
+            key => return Err(Error::unknown_variant(key, EXTRA_FD_VARIANTS)),
+        };
+
+        if map.next_key::<String>()?.is_some() {
+            return Err(Error::custom("fd table must contain a single entry"));
+        }
+
+        Ok(fd)
+    }
+}
+
+impl Serialize for Pty {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("rows", &self.rows)?;
+        map.serialize_entry("cols", &self.cols)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Pty {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Pty, D::Error> {
+        deserializer.deserialize_map(PtyVisitor)
+    }
+}
+
+struct PtyVisitor;
+
+impl<'de> Visitor<'de> for PtyVisitor {
+    type Value = Pty;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a table with terminal rows and cols")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut pty = Pty::default();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "rows" => pty.rows = map.next_value()?,
+                "cols" => pty.cols = map.next_value()?,
+                key => return Err(Error::unknown_field(key, PTY_FIELDS)),
+            }
+        }
+
+        Ok(pty)
+    }
+}
+
+impl Serialize for LimitAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            LimitAction::Truncate => serializer.serialize_str("truncate"),
+            LimitAction::Kill => serializer.serialize_str("kill"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LimitAction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<LimitAction, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "truncate" => Ok(LimitAction::Truncate),
+            "kill" => Ok(LimitAction::Kill),
+            action => Err(Error::unknown_variant(action, LIMIT_ACTION_VARIANTS)),
+        }
+    }
+}
+
+impl Serialize for OnDrop {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            OnDrop::Kill => serializer.serialize_str("kill"),
+            OnDrop::Detach => serializer.serialize_str("detach"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OnDrop {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<OnDrop, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "kill" => Ok(OnDrop::Kill),
+            "detach" => Ok(OnDrop::Detach),
+            on_drop => Err(Error::unknown_variant(on_drop, ON_DROP_VARIANTS)),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Serialize for Resource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let resource = match self {
+            Resource::AddressSpace => "address-space",
+            Resource::Cpu => "cpu",
+            Resource::OpenFiles => "open-files",
+            Resource::CoreSize => "core-size",
+            Resource::FileSize => "file-size",
+        };
+
+        serializer.serialize_str(resource)
+    }
+}
+
+#[cfg(unix)]
+impl<'de> Deserialize<'de> for Resource {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Resource, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "address-space" => Ok(Resource::AddressSpace),
+            "cpu" => Ok(Resource::Cpu),
+            "open-files" => Ok(Resource::OpenFiles),
+            "core-size" => Ok(Resource::CoreSize),
+            "file-size" => Ok(Resource::FileSize),
+            resource => Err(Error::unknown_variant(resource, RESOURCE_VARIANTS)),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Serialize for Rlimit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("soft", &self.soft)?;
+        map.serialize_entry("hard", &self.hard)?;
+        map.end()
+    }
+}
+
+#[cfg(unix)]
+impl<'de> Deserialize<'de> for Rlimit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Rlimit, D::Error> {
+        deserializer.deserialize_map(RlimitVisitor)
+    }
+}
+
+#[cfg(unix)]
+struct RlimitVisitor;
+
+#[cfg(unix)]
+impl<'de> Visitor<'de> for RlimitVisitor {
+    type Value = Rlimit;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a table with soft and hard limits")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut soft = None;
+        let mut hard = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "soft" => soft = Some(map.next_value()?),
+                "hard" => hard = Some(map.next_value()?),
+                key => return Err(Error::unknown_field(key, RLIMIT_FIELDS)),
+            }
+        }
+
+        Ok(Rlimit {
+            soft: soft.ok_or(Error::missing_field("soft"))?,
+            hard: hard.ok_or(Error::missing_field("hard"))?,
+        })
+    }
+}
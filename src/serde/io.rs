@@ -0,0 +1,465 @@
+//! Serialization of [`ProcessInput`], [`ProcessOutput`] and
+//! [`ExitStatus`].
+
+use core::fmt;
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{Error, MapAccess, Visitor},
+    ser::SerializeMap,
+};
+
+use super::{
+    bytes::{ByteBuf, Bytes},
+    duration,
+};
+use crate::{
+    capture::{Stream, TranscriptEvent, Truncated},
+    command::Command,
+    io::{ProcessInput, ProcessOutput},
+    status::ExitStatus,
+};
+
+const INPUT_FIELDS: &[&str] = &["type", "cmd", "cmds", "stdin"];
+
+const INPUT_TYPES: &[&str] = &["spawn", "spawn-out", "spawn-in", "spawn-pipeline"];
+
+const OUTPUT_FIELDS: &[&str] = &[
+    "type",
+    "status",
+    "stdout",
+    "stderr",
+    "truncated",
+    "transcript",
+    "fds",
+];
+
+const OUTPUT_TYPES: &[&str] = &["spawned", "spawned-out", "spawned-in", "spawned-pipeline"];
+
+const TRANSCRIPT_EVENT_FIELDS: &[&str] = &["stream", "elapsed", "bytes"];
+
+const STREAMS: &[&str] = &["stdout", "stderr"];
+
+impl Serialize for ExitStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.code().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExitStatus {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ExitStatus, D::Error> {
+        Ok(ExitStatus::new(Option::deserialize(deserializer)?))
+    }
+}
+
+impl Serialize for ProcessInput {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        match self {
+            ProcessInput::Spawn { cmd } => {
+                map.serialize_entry("type", "spawn")?;
+                map.serialize_entry("cmd", cmd)?;
+            }
+            ProcessInput::SpawnOut { cmd } => {
+                map.serialize_entry("type", "spawn-out")?;
+                map.serialize_entry("cmd", cmd)?;
+            }
+            ProcessInput::SpawnIn { cmd, stdin } => {
+                map.serialize_entry("type", "spawn-in")?;
+                map.serialize_entry("cmd", cmd)?;
+                map.serialize_entry("stdin", &Bytes(stdin))?;
+            }
+            ProcessInput::SpawnPipeline { cmds } => {
+                map.serialize_entry("type", "spawn-pipeline")?;
+                map.serialize_entry("cmds", cmds)?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ProcessInput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ProcessInput, D::Error> {
+        deserializer.deserialize_map(ProcessInputVisitor)
+    }
+}
+
+struct ProcessInputVisitor;
+
+impl<'de> Visitor<'de> for ProcessInputVisitor {
+    type Value = ProcessInput;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a process input table")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut kind = None;
+        let mut cmd = None;
+        let mut cmds = None;
+        let mut stdin = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "type" => kind = Some(map.next_value::<String>()?),
+                "cmd" => cmd = Some(map.next_value::<Command>()?),
+                "cmds" => cmds = Some(map.next_value::<Vec<Command>>()?),
+                "stdin" => stdin = Some(map.next_value::<ByteBuf>()?.0),
+                key => return Err(Error::unknown_field(key, INPUT_FIELDS)),
+            }
+        }
+
+        let kind = kind.ok_or(Error::missing_field("type"))?;
+        let cmd = || cmd.ok_or(Error::missing_field("cmd"));
+
+        let input = match kind.as_str() {
+            "spawn" => ProcessInput::Spawn { cmd: cmd()? },
+            "spawn-out" => ProcessInput::SpawnOut { cmd: cmd()? },
+            "spawn-in" => ProcessInput::SpawnIn {
+                cmd: cmd()?,
+                stdin: stdin.ok_or(Error::missing_field("stdin"))?,
+            },
+            "spawn-pipeline" => ProcessInput::SpawnPipeline {
+                cmds: cmds.ok_or(Error::missing_field("cmds"))?,
+            },
+            kind => return Err(Error::unknown_variant(kind, INPUT_TYPES)),
+        };
+
+        Ok(input)
+    }
+}
+
+impl Serialize for ProcessOutput {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        let (stdout, stderr, truncated, transcript, fds) = match self {
+            ProcessOutput::Spawned { status } => {
+                map.serialize_entry("type", "spawned")?;
+                map.serialize_entry("status", status)?;
+                return map.end();
+            }
+            ProcessOutput::SpawnedIn { status } => {
+                map.serialize_entry("type", "spawned-in")?;
+                map.serialize_entry("status", status)?;
+                return map.end();
+            }
+            ProcessOutput::SpawnedOut {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            } => {
+                map.serialize_entry("type", "spawned-out")?;
+                map.serialize_entry("status", status)?;
+                (stdout, stderr, truncated, transcript, fds)
+            }
+            ProcessOutput::SpawnedPipeline {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            } => {
+                map.serialize_entry("type", "spawned-pipeline")?;
+                map.serialize_entry("status", status)?;
+                (stdout, stderr, truncated, transcript, fds)
+            }
+        };
+
+        if let Some(stdout) = stdout {
+            map.serialize_entry("stdout", &Bytes(stdout))?;
+        }
+
+        if let Some(stderr) = stderr {
+            map.serialize_entry("stderr", &Bytes(stderr))?;
+        }
+
+        if truncated.any() {
+            map.serialize_entry("truncated", truncated)?;
+        }
+
+        if let Some(transcript) = transcript {
+            map.serialize_entry("transcript", transcript)?;
+        }
+
+        if !fds.is_empty() {
+            let fds: BTreeMap<_, _> = fds.iter().map(|(fd, bytes)| (fd, Bytes(bytes))).collect();
+            map.serialize_entry("fds", &fds)?;
+        }
+
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ProcessOutput {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ProcessOutput, D::Error> {
+        deserializer.deserialize_map(ProcessOutputVisitor)
+    }
+}
+
+struct ProcessOutputVisitor;
+
+impl<'de> Visitor<'de> for ProcessOutputVisitor {
+    type Value = ProcessOutput;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a process output table")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut kind = None;
+        let mut status = None;
+        let mut stdout = None;
+        let mut stderr = None;
+        let mut truncated = Truncated::default();
+        let mut transcript = None;
+        let mut fds = BTreeMap::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "type" => kind = Some(map.next_value::<String>()?),
+                "status" => status = Some(map.next_value::<ExitStatus>()?),
+                "stdout" => stdout = map.next_value::<Option<ByteBuf>>()?.map(|b| b.0),
+                "stderr" => stderr = map.next_value::<Option<ByteBuf>>()?.map(|b| b.0),
+                "truncated" => truncated = map.next_value()?,
+                "transcript" => transcript = Some(map.next_value()?),
+                "fds" => {
+                    let bufs = map.next_value::<BTreeMap<i32, ByteBuf>>()?;
+                    fds = bufs.into_iter().map(|(fd, buf)| (fd, buf.0)).collect();
+                }
+                key => return Err(Error::unknown_field(key, OUTPUT_FIELDS)),
+            }
+        }
+
+        let kind = kind.ok_or(Error::missing_field("type"))?;
+        let status = status.ok_or(Error::missing_field("status"))?;
+
+        let output = match kind.as_str() {
+            "spawned" => ProcessOutput::Spawned { status },
+            "spawned-in" => ProcessOutput::SpawnedIn { status },
+            "spawned-out" => ProcessOutput::SpawnedOut {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            },
+            "spawned-pipeline" => ProcessOutput::SpawnedPipeline {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                transcript,
+                fds,
+            },
+            kind => return Err(Error::unknown_variant(kind, OUTPUT_TYPES)),
+        };
+
+        Ok(output)
+    }
+}
+
+impl Serialize for Truncated {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("stdout", &self.stdout)?;
+        map.serialize_entry("stderr", &self.stderr)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Truncated {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Truncated, D::Error> {
+        deserializer.deserialize_map(TruncatedVisitor)
+    }
+}
+
+struct TruncatedVisitor;
+
+impl<'de> Visitor<'de> for TruncatedVisitor {
+    type Value = Truncated;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a table of truncated streams")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut truncated = Truncated::default();
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "stdout" => truncated.stdout = map.next_value()?,
+                "stderr" => truncated.stderr = map.next_value()?,
+                key => return Err(Error::unknown_field(key, STREAMS)),
+            }
+        }
+
+        Ok(truncated)
+    }
+}
+
+impl Serialize for Stream {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Stream::Stdout => serializer.serialize_str("stdout"),
+            Stream::Stderr => serializer.serialize_str("stderr"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Stream {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Stream, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "stdout" => Ok(Stream::Stdout),
+            "stderr" => Ok(Stream::Stderr),
+            stream => Err(Error::unknown_variant(stream, STREAMS)),
+        }
+    }
+}
+
+impl Serialize for TranscriptEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("stream", &self.stream)?;
+        map.serialize_entry("elapsed", &self.elapsed.as_secs_f64())?;
+        map.serialize_entry("bytes", &Bytes(&self.bytes))?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for TranscriptEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TranscriptEvent, D::Error> {
+        deserializer.deserialize_map(TranscriptEventVisitor)
+    }
+}
+
+struct TranscriptEventVisitor;
+
+impl<'de> Visitor<'de> for TranscriptEventVisitor {
+    type Value = TranscriptEvent;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a transcript event table")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut stream = None;
+        let mut elapsed = None;
+        let mut bytes = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "stream" => stream = Some(map.next_value()?),
+                "elapsed" => elapsed = Some(duration(map.next_value()?)?),
+                "bytes" => bytes = Some(map.next_value::<ByteBuf>()?.0),
+                key => return Err(Error::unknown_field(key, TRANSCRIPT_EVENT_FIELDS)),
+            }
+        }
+
+        Ok(TranscriptEvent {
+            stream: stream.ok_or(Error::missing_field("stream"))?,
+            elapsed: elapsed.ok_or(Error::missing_field("elapsed"))?,
+            bytes: bytes.ok_or(Error::missing_field("bytes"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeMap, string::ToString, vec};
+    use core::time::Duration;
+
+    use crate::{
+        capture::{Stream, TranscriptEvent, Truncated},
+        command::Command,
+        io::{ProcessInput, ProcessOutput},
+        status::ExitStatus,
+    };
+
+    #[test]
+    fn input() {
+        let input = ProcessInput::SpawnIn {
+            cmd: Command::new("cat"),
+            stdin: b"hello\n".to_vec(),
+        };
+
+        let json = serde_json::to_string(&input).unwrap();
+        assert_eq!(
+            r#"{"type":"spawn-in","cmd":["cat"],"stdin":"aGVsbG8K"}"#,
+            json
+        );
+
+        let ProcessInput::SpawnIn { cmd, stdin } = serde_json::from_str(&json).unwrap() else {
+            panic!("unexpected input");
+        };
+        assert_eq!(Command::new("cat"), cmd);
+        assert_eq!(b"hello\n", stdin.as_slice());
+
+        let json = r#"{"type":"spawn-pipeline","cmds":["ls",["wc","-l"]]}"#;
+        let ProcessInput::SpawnPipeline { cmds } = serde_json::from_str(json).unwrap() else {
+            panic!("unexpected input");
+        };
+        assert_eq!(2, cmds.len());
+
+        let err = serde_json::from_str::<ProcessInput>(r#"{"type":"spawn"}"#).unwrap_err();
+        assert!(err.to_string().starts_with("missing field `cmd`"));
+    }
+
+    #[test]
+    fn output() {
+        let output = ProcessOutput::SpawnedOut {
+            status: ExitStatus::new(None),
+            stdout: Some(b"out".to_vec()),
+            stderr: None,
+            truncated: Truncated {
+                stdout: true,
+                stderr: false,
+            },
+            transcript: Some(vec![TranscriptEvent {
+                stream: Stream::Stdout,
+                elapsed: Duration::from_millis(1500),
+                bytes: b"out".to_vec(),
+            }]),
+            fds: BTreeMap::from([(3, b"\xff".to_vec())]),
+        };
+
+        let json = serde_json::to_string(&output).unwrap();
+        assert_eq!(
+            r#"{"type":"spawned-out","status":null,"stdout":"b3V0","truncated":{"stdout":true,"stderr":false},"transcript":[{"stream":"stdout","elapsed":1.5,"bytes":"b3V0"}],"fds":{"3":"/w=="}}"#,
+            json
+        );
+
+        let ProcessOutput::SpawnedOut {
+            status,
+            stdout,
+            stderr,
+            truncated,
+            transcript,
+            fds,
+        } = serde_json::from_str(&json).unwrap()
+        else {
+            panic!("unexpected output");
+        };
+
+        assert_eq!(None, status.code());
+        assert_eq!(Some(b"out".to_vec()), stdout);
+        assert_eq!(None, stderr);
+        assert!(truncated.stdout);
+        assert_eq!(Duration::from_millis(1500), transcript.unwrap()[0].elapsed);
+        assert_eq!(Some(&b"\xff".to_vec()), fds.get(&3));
+
+        let json = r#"{"type":"spawned","status":0}"#;
+        let ProcessOutput::Spawned { status } = serde_json::from_str(json).unwrap() else {
+            panic!("unexpected output");
+        };
+        assert!(status.success());
+    }
+}
@@ -0,0 +1,786 @@
+//! Module dedicated to [`serde`] de/serialization of [`Command`],
+//! [`Stdio`], and of the [`ProcessInput`] requests and
+//! [`ProcessOutput`] responses exchanged with runtimes.
+//!
+//! A [`Command`] can be written in three forms:
+//!
+//! - a string, split on whitespaces: `"gpg --decrypt"`
+//! - a list of strings: `["gpg", "--decrypt"]`
+//! - a table, for commands that need more than a program and its
+//!   arguments:
+//!
+//! ```toml
+//! [cmd]
+//! program = "gpg"
+//! args = ["--decrypt"]
+//! env = { GNUPGHOME = "~/.gnupg" }
+//! current-dir = "/tmp"
+//! stdin = { read = "/tmp/mail.eml" }
+//! stdout = "piped"
+//! stderr = { append = "/tmp/gpg.log" }
+//! ```
+//!
+//! The table also accepts:
+//!
+//! - `fds`: extra file descriptors by number, each either `"capture"`
+//!   or a single-entry table whose key is `input` (bytes), `read`,
+//!   `write` or `append` (file path)
+//! - `pty`: a table with the terminal `rows` and `cols`
+//! - `max-stdout`, `max-stderr`, `on-output-limit` (`"truncate"` or
+//!   `"kill"`), `transcript`, `tee-stdout` and `tee-stderr`
+//! - `timeout` and `grace-period`, in seconds, `kill-tree`, and
+//!   `on-drop` (`"kill"` or `"detach"`)
+//!
+//! On Unix, the table also accepts the process attributes `uid`,
+//! `gid`, `groups`, `process-group`, `setsid` and `umask`, the
+//! resource limits `rlimits`, by resource name (`address-space`,
+//! `cpu`, `open-files`, `core-size` or `file-size`) with their `soft`
+//! and `hard` values, and the `nice` value.
+//!
+//! A [`Stdio`] is written either as one of the strings `"inherit"`,
+//! `"null"`, `"piped"` and `"stdout"`, or as a single-entry table whose key is
+//! `read`, `write` or `append` and whose value is a file path.
+//!
+//! A [`ProcessInput`] is written as a table whose `type` is one of
+//! `"spawn"`, `"spawn-out"` and `"spawn-in"`, with the command under
+//! `cmd`, or `"spawn-pipeline"`, with the commands under `cmds`. The
+//! bytes fed to `"spawn-in"` go under `stdin`:
+//!
+//! ```json
+//! { "type": "spawn-in", "cmd": ["cat"], "stdin": "aGVsbG8K" }
+//! ```
+//!
+//! A [`ProcessOutput`] is written as a table whose `type` is one of
+//! `"spawned"`, `"spawned-in"`, `"spawned-out"` and
+//! `"spawned-pipeline"`, with the exit `status`. The last two also
+//! carry the captured `stdout` and `stderr` if any, the `truncated`
+//! streams, the `transcript` events and the captured `fds`, omitted
+//! when empty:
+//!
+//! ```json
+//! {
+//!   "type": "spawned-out",
+//!   "status": 0,
+//!   "stdout": "aGVsbG8K",
+//!   "truncated": { "stdout": true, "stderr": false },
+//!   "transcript": [{ "stream": "stdout", "elapsed": 0.002, "bytes": "aGVsbG8K" }],
+//!   "fds": { "3": "b2sK" }
+//! }
+//! ```
+//!
+//! An [`ExitStatus`] is written as its exit code, or `null` if the
+//! process got terminated by a signal.
+//!
+//! Byte buffers are written as standard base64 strings by
+//! human-readable formats, and as raw bytes by binary formats.
+//!
+//! [`ProcessInput`]: crate::io::ProcessInput
+//! [`ProcessOutput`]: crate::io::ProcessOutput
+//! [`ExitStatus`]: crate::status::ExitStatus
+
+mod attrs;
+mod bytes;
+mod io;
+
+use core::{fmt, time::Duration};
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{Error, MapAccess, SeqAccess, Visitor},
+    ser::{SerializeMap, SerializeSeq},
+};
+
+#[cfg(unix)]
+use crate::limit::{Resource, Rlimit};
+use crate::{
+    capture::{Capture, LimitAction},
+    command::Command,
+    fd::ExtraFd,
+    pty::Pty,
+    stdio::Stdio,
+    termination::{OnDrop, Termination},
+};
+
+const COMMAND_FIELDS: &[&str] = &[
+    "program",
+    "args",
+    "env",
+    "current-dir",
+    "stdin",
+    "stdout",
+    "stderr",
+    "fds",
+    "pty",
+    "max-stdout",
+    "max-stderr",
+    "on-output-limit",
+    "transcript",
+    "tee-stdout",
+    "tee-stderr",
+    "timeout",
+    "kill-tree",
+    "grace-period",
+    "on-drop",
+    #[cfg(unix)]
+    "uid",
+    #[cfg(unix)]
+    "gid",
+    #[cfg(unix)]
+    "groups",
+    #[cfg(unix)]
+    "process-group",
+    #[cfg(unix)]
+    "setsid",
+    #[cfg(unix)]
+    "umask",
+    #[cfg(unix)]
+    "rlimits",
+    #[cfg(unix)]
+    "nice",
+    #[cfg(feature = "expand")]
+    "expand",
+];
+
+const STDIO_VARIANTS: &[&str] = &[
+    "inherit", "null", "piped", "stdout", "read", "write", "append",
+];
+
+impl Command {
+    /// Returns `true` if the command only has a program and
+    /// arguments, in which case it can be serialized as a list.
+    fn is_plain(&self) -> bool {
+        #[cfg(unix)]
+        if !self.is_unix_plain() {
+            return false;
+        }
+
+        #[cfg(feature = "expand")]
+        if self.expand {
+            return false;
+        }
+
+        self.envs.is_none()
+            && self.current_dir.is_none()
+            && self.stdin.is_none()
+            && self.stdout.is_none()
+            && self.stderr.is_none()
+            && self.fds.is_empty()
+            && self.pty.is_none()
+            && self.capture == Capture::default()
+            && self.timeout.is_none()
+            && self.termination == Termination::default()
+    }
+}
+
+#[cfg(unix)]
+impl Command {
+    /// Returns `true` if the command has no Unix process attribute
+    /// nor limit.
+    fn is_unix_plain(&self) -> bool {
+        self.uid.is_none()
+            && self.gid.is_none()
+            && self.groups.is_none()
+            && self.process_group.is_none()
+            && !self.setsid
+            && self.umask.is_none()
+            && self.rlimits.is_empty()
+            && self.nice.is_none()
+    }
+}
+
+impl Serialize for Command {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !self.is_plain() {
+            return serialize_table(self, serializer);
+        }
+
+        let args_len = match &self.args {
+            Some(args) => args.len() + 1,
+            None => 1,
+        };
+
+        let mut seq = serializer.serialize_seq(Some(args_len))?;
+
+        seq.serialize_element(&self.program)?;
+
+        if let Some(args) = &self.args {
+            for arg in args {
+                seq.serialize_element(&arg)?;
+            }
+        }
+
+        seq.end()
+    }
+}
+
+fn serialize_table<S: Serializer>(cmd: &Command, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(None)?;
+
+    // the raw program and arguments are serialized, since they get
+    // expanded again once deserialized
+    map.serialize_entry("program", &cmd.program)?;
+
+    if let Some(args) = &cmd.args {
+        map.serialize_entry("args", args)?;
+    }
+
+    if let Some(envs) = &cmd.envs {
+        map.serialize_entry("env", envs)?;
+    }
+
+    if let Some(dir) = &cmd.current_dir {
+        map.serialize_entry("current-dir", dir)?;
+    }
+
+    if let Some(stdin) = &cmd.stdin {
+        map.serialize_entry("stdin", stdin)?;
+    }
+
+    if let Some(stdout) = &cmd.stdout {
+        map.serialize_entry("stdout", stdout)?;
+    }
+
+    if let Some(stderr) = &cmd.stderr {
+        map.serialize_entry("stderr", stderr)?;
+    }
+
+    if !cmd.fds.is_empty() {
+        map.serialize_entry("fds", &cmd.fds)?;
+    }
+
+    if let Some(pty) = &cmd.pty {
+        map.serialize_entry("pty", pty)?;
+    }
+
+    let capture = &cmd.capture;
+
+    if let Some(max) = &capture.max_stdout {
+        map.serialize_entry("max-stdout", max)?;
+    }
+
+    if let Some(max) = &capture.max_stderr {
+        map.serialize_entry("max-stderr", max)?;
+    }
+
+    if capture.on_limit != LimitAction::default() {
+        map.serialize_entry("on-output-limit", &capture.on_limit)?;
+    }
+
+    if capture.transcript {
+        map.serialize_entry("transcript", &true)?;
+    }
+
+    if capture.tee_stdout {
+        map.serialize_entry("tee-stdout", &true)?;
+    }
+
+    if capture.tee_stderr {
+        map.serialize_entry("tee-stderr", &true)?;
+    }
+
+    if let Some(timeout) = &cmd.timeout {
+        map.serialize_entry("timeout", &timeout.as_secs_f64())?;
+    }
+
+    let termination = &cmd.termination;
+
+    if termination.tree {
+        map.serialize_entry("kill-tree", &true)?;
+    }
+
+    if let Some(grace) = &termination.grace {
+        map.serialize_entry("grace-period", &grace.as_secs_f64())?;
+    }
+
+    if termination.on_drop != OnDrop::default() {
+        map.serialize_entry("on-drop", &termination.on_drop)?;
+    }
+
+    #[cfg(unix)]
+    {
+        if let Some(uid) = &cmd.uid {
+            map.serialize_entry("uid", uid)?;
+        }
+
+        if let Some(gid) = &cmd.gid {
+            map.serialize_entry("gid", gid)?;
+        }
+
+        if let Some(groups) = &cmd.groups {
+            map.serialize_entry("groups", groups)?;
+        }
+
+        if let Some(pgroup) = &cmd.process_group {
+            map.serialize_entry("process-group", pgroup)?;
+        }
+
+        if cmd.setsid {
+            map.serialize_entry("setsid", &true)?;
+        }
+
+        if let Some(umask) = &cmd.umask {
+            map.serialize_entry("umask", umask)?;
+        }
+
+        if !cmd.rlimits.is_empty() {
+            map.serialize_entry("rlimits", &cmd.rlimits)?;
+        }
+
+        if let Some(nice) = &cmd.nice {
+            map.serialize_entry("nice", nice)?;
+        }
+    }
+
+    #[cfg(feature = "expand")]
+    if cmd.expand {
+        map.serialize_entry("expand", &true)?;
+    }
+
+    map.end()
+}
+
+impl<'de> Deserialize<'de> for Command {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Command, D::Error> {
+        deserializer.deserialize_any(CommandVisitor)
+    }
+}
+
+struct CommandVisitor;
+
+impl<'de> Visitor<'de> for CommandVisitor {
+    type Value = Command;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .write_str("a string (full command), a list of string (command arguments) or a table")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        let mut args = v.split_whitespace();
+        let program = args.next().ok_or(E::custom("command cannot be empty"))?;
+        let mut command = Command::new(program);
+        command.args(args);
+        Ok(command)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let program = seq
+            .next_element::<String>()?
+            .ok_or(<A::Error as Error>::custom("command cannot be empty"))?;
+
+        let mut command = Command::new(program);
+
+        while let Some(arg) = seq.next_element::<String>()? {
+            command.arg(arg);
+        }
+
+        Ok(command)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut program = None;
+        let mut args = None;
+        let mut envs = None;
+        let mut current_dir = None;
+        let mut stdin = None;
+        let mut stdout = None;
+        let mut stderr = None;
+        let mut fds = BTreeMap::new();
+        let mut pty = None;
+        let mut capture = Capture::default();
+        let mut timeout = None;
+        let mut termination = Termination::default();
+        #[cfg(unix)]
+        let mut uid = None;
+        #[cfg(unix)]
+        let mut gid = None;
+        #[cfg(unix)]
+        let mut groups = None;
+        #[cfg(unix)]
+        let mut process_group = None;
+        #[cfg(unix)]
+        let mut setsid = false;
+        #[cfg(unix)]
+        let mut umask = None;
+        #[cfg(unix)]
+        let mut rlimits = BTreeMap::new();
+        #[cfg(unix)]
+        let mut nice = None;
+        #[cfg(feature = "expand")]
+        let mut expand = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "program" => program = Some(map.next_value::<String>()?),
+                "args" => args = Some(map.next_value::<Vec<String>>()?),
+                "env" => envs = Some(map.next_value::<BTreeMap<String, String>>()?),
+                "current-dir" => current_dir = Some(map.next_value::<String>()?),
+                "stdin" => stdin = Some(map.next_value::<Stdio>()?),
+                "stdout" => stdout = Some(map.next_value::<Stdio>()?),
+                "stderr" => stderr = Some(map.next_value::<Stdio>()?),
+                "fds" => fds = map.next_value::<BTreeMap<i32, ExtraFd>>()?,
+                "pty" => pty = Some(map.next_value::<Pty>()?),
+                "max-stdout" => capture.max_stdout = Some(map.next_value::<usize>()?),
+                "max-stderr" => capture.max_stderr = Some(map.next_value::<usize>()?),
+                "on-output-limit" => capture.on_limit = map.next_value::<LimitAction>()?,
+                "transcript" => capture.transcript = map.next_value::<bool>()?,
+                "tee-stdout" => capture.tee_stdout = map.next_value::<bool>()?,
+                "tee-stderr" => capture.tee_stderr = map.next_value::<bool>()?,
+                "timeout" => timeout = Some(duration(map.next_value::<f64>()?)?),
+                "kill-tree" => termination.tree = map.next_value::<bool>()?,
+                "grace-period" => termination.grace = Some(duration(map.next_value::<f64>()?)?),
+                "on-drop" => termination.on_drop = map.next_value::<OnDrop>()?,
+                #[cfg(unix)]
+                "uid" => uid = Some(map.next_value::<u32>()?),
+                #[cfg(unix)]
+                "gid" => gid = Some(map.next_value::<u32>()?),
+                #[cfg(unix)]
+                "groups" => groups = Some(map.next_value::<Vec<u32>>()?),
+                #[cfg(unix)]
+                "process-group" => process_group = Some(map.next_value::<i32>()?),
+                #[cfg(unix)]
+                "setsid" => setsid = map.next_value::<bool>()?,
+                #[cfg(unix)]
+                "umask" => umask = Some(map.next_value::<u32>()?),
+                #[cfg(unix)]
+                "rlimits" => rlimits = map.next_value::<BTreeMap<Resource, Rlimit>>()?,
+                #[cfg(unix)]
+                "nice" => nice = Some(map.next_value::<i32>()?),
+                #[cfg(feature = "expand")]
+                "expand" => expand = map.next_value::<bool>()?,
+                key => return Err(Error::unknown_field(key, COMMAND_FIELDS)),
+            }
+        }
+
+        let program = program.ok_or(Error::missing_field("program"))?;
+
+        if program.is_empty() {
+            return Err(Error::custom("command cannot be empty"));
+        }
+
+        let mut command = Command::new(program);
+
+        if let Some(args) = args {
+            command.args(args);
+        }
+
+        command.envs = envs;
+        command.current_dir = current_dir;
+        command.stdin = stdin;
+        command.stdout = stdout;
+        command.stderr = stderr;
+        command.fds = fds;
+        command.pty = pty;
+        command.capture = capture;
+        command.timeout = timeout;
+        command.termination = termination;
+
+        #[cfg(unix)]
+        {
+            command.uid = uid;
+            command.gid = gid;
+            command.groups = groups;
+            command.process_group = process_group;
+            command.setsid = setsid;
+            command.umask = umask;
+            command.rlimits = rlimits;
+            command.nice = nice;
+        }
+
+        #[cfg(feature = "expand")]
+        {
+            command.expand = expand;
+        }
+
+        Ok(command)
+    }
+}
+
+/// Converts the given number of seconds into a duration.
+fn duration<E: Error>(secs: f64) -> Result<Duration, E> {
+    Duration::try_from_secs_f64(secs)
+        .map_err(|err| E::custom(format_args!("invalid duration: {err}")))
+}
+
+impl Serialize for Stdio {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (key, path) = match self {
+            Stdio::Inherit => return serializer.serialize_str("inherit"),
+            Stdio::Null => return serializer.serialize_str("null"),
+            Stdio::Piped => return serializer.serialize_str("piped"),
+            Stdio::Stdout => return serializer.serialize_str("stdout"),
+            Stdio::ReadFile(path) => ("read", path),
+            Stdio::WriteFile(path) => ("write", path),
+            Stdio::AppendFile(path) => ("append", path),
+        };
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(key, path)?;
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Stdio {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Stdio, D::Error> {
+        deserializer.deserialize_any(StdioVisitor)
+    }
+}
+
+struct StdioVisitor;
+
+impl<'de> Visitor<'de> for StdioVisitor {
+    type Value = Stdio;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(
+            "\"inherit\", \"null\", \"piped\", \"stdout\" or a table with a read, write or append file path",
+        )
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        match v {
+            "inherit" => Ok(Stdio::Inherit),
+            "null" => Ok(Stdio::Null),
+            "piped" => Ok(Stdio::Piped),
+            "stdout" => Ok(Stdio::Stdout),
+            v => Err(E::unknown_variant(v, STDIO_VARIANTS)),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let Some(key) = map.next_key::<String>()? else {
+            return Err(Error::custom("stdio table cannot be empty"));
+        };
+
+        let stdio = match key.as_str() {
+            "read" => Stdio::ReadFile(map.next_value()?),
+            "write" => Stdio::WriteFile(map.next_value()?),
+            "append" => Stdio::AppendFile(map.next_value()?),
+            key => return Err(Error::unknown_variant(key, STDIO_VARIANTS)),
+        };
+
+        if map.next_key::<String>()?.is_some() {
+            return Err(Error::custom("stdio table must contain a single entry"));
+        }
+
+        Ok(stdio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+    use core::time::Duration;
+
+    use serde::{
+        Deserialize,
+        de::value::{Error, SeqDeserializer, StringDeserializer},
+    };
+
+    #[cfg(unix)]
+    use crate::limit::{Resource, Rlimit};
+    use crate::{
+        capture::LimitAction, command::Command, fd::ExtraFd, pty::Pty, stdio::Stdio,
+        termination::OnDrop,
+    };
+
+    #[test]
+    fn deserialize_string() {
+        let mut expected = Command::new("program");
+        expected.arg("arg1").arg("arg2");
+
+        let s = String::from("program arg1 arg2");
+        let s = StringDeserializer::<Error>::new(s);
+        let got = Command::deserialize(s).unwrap();
+        assert_eq!(expected, got);
+
+        let s = String::from("program   \narg1 \t arg2");
+        let s = StringDeserializer::<Error>::new(s);
+        let got = Command::deserialize(s).unwrap();
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn deserialize_empty_string() {
+        let s = StringDeserializer::<Error>::new(String::new());
+        let err = Command::deserialize(s).unwrap_err();
+        assert_eq!("command cannot be empty", err.to_string());
+
+        let s = StringDeserializer::<Error>::new(String::from(" \n\t"));
+        let err = Command::deserialize(s).unwrap_err();
+        assert_eq!("command cannot be empty", err.to_string());
+    }
+
+    #[test]
+    fn deserialize_seq() {
+        let mut expected = Command::new("program");
+        expected.arg("arg1").arg("arg2");
+
+        let s = ["program", "arg1", "arg2"];
+        let s = SeqDeserializer::<_, Error>::new(s.into_iter());
+        let got = Command::deserialize(s).unwrap();
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn deserialize_empty_seq() {
+        let s: [&str; 0] = [];
+        let s = SeqDeserializer::new(s.into_iter());
+        let err: Error = Command::deserialize(s).unwrap_err();
+        assert_eq!("command cannot be empty", err.to_string());
+    }
+
+    #[test]
+    fn deserialize_table() {
+        let mut expected = Command::new("gpg");
+        expected.arg("--decrypt");
+        expected.env("GNUPGHOME", "/tmp/gnupg");
+        expected.current_dir("/tmp");
+        expected.stdin(Stdio::ReadFile("mail.eml".into()));
+        expected.stdout(Stdio::Piped);
+        expected.stderr(Stdio::AppendFile("gpg.log".into()));
+
+        let json = r#"{
+            "program": "gpg",
+            "args": ["--decrypt"],
+            "env": { "GNUPGHOME": "/tmp/gnupg" },
+            "current-dir": "/tmp",
+            "stdin": { "read": "mail.eml" },
+            "stdout": "piped",
+            "stderr": { "append": "gpg.log" }
+        }"#;
+
+        let got: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(expected, got);
+    }
+
+    #[test]
+    fn deserialize_invalid_table() {
+        let err = serde_json::from_str::<Command>(r#"{ "args": ["a"] }"#).unwrap_err();
+        assert!(err.to_string().starts_with("missing field `program`"));
+
+        let json = r#"{ "program": "a", "stdout": { "truncate": "f" } }"#;
+        let err = serde_json::from_str::<Command>(json).unwrap_err();
+        assert!(err.to_string().starts_with("unknown variant `truncate`"));
+
+        let json = r#"{ "program": "a", "stdout": { "write": "f", "append": "f" } }"#;
+        let err = serde_json::from_str::<Command>(json).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("stdio table must contain a single entry")
+        );
+    }
+
+    #[test]
+    fn serialize() {
+        let mut command = Command::new("echo");
+        let json = serde_json::to_string(&command).unwrap();
+        assert_eq!(r#"["echo"]"#, json);
+
+        command.arg("hello");
+        let json = serde_json::to_string(&command).unwrap();
+        assert_eq!(r#"["echo","hello"]"#, json);
+
+        command.stdin(Stdio::Null);
+        command.stdout(Stdio::WriteFile("out.log".into()));
+        command.stderr(Stdio::Stdout);
+        let json = serde_json::to_string(&command).unwrap();
+        assert_eq!(
+            r#"{"program":"echo","args":["hello"],"stdin":"null","stdout":{"write":"out.log"},"stderr":"stdout"}"#,
+            json
+        );
+
+        let got: Command = serde_json::from_str(&json).unwrap();
+        assert_eq!(command, got);
+    }
+
+    #[cfg(feature = "expand")]
+    #[test]
+    fn serialize_expand() {
+        let mut command = Command::new("~/bin/tool");
+        command.arg("$HOME/file").arg("$$literal");
+        command.expand = true;
+
+        let json = serde_json::to_string(&command).unwrap();
+        assert_eq!(
+            r#"{"program":"~/bin/tool","args":["$HOME/file","$$literal"],"expand":true}"#,
+            json
+        );
+
+        let got: Command = serde_json::from_str(&json).unwrap();
+        assert_eq!(command, got);
+    }
+
+    #[test]
+    fn runtime_attrs() {
+        let mut expected = Command::new("gpg");
+        expected.fd(3, ExtraFd::Input(b"secret\n".to_vec()));
+        expected.fd(4, ExtraFd::Capture);
+        expected.pty(Pty {
+            rows: 40,
+            cols: 120,
+        });
+        expected.max_stdout(1024).on_output_limit(LimitAction::Kill);
+        expected.transcript(true).tee_stderr(true);
+        expected.timeout(Duration::from_millis(2500));
+        expected
+            .kill_tree(true)
+            .grace_period(Duration::from_secs(1));
+        expected.on_drop(OnDrop::Detach);
+
+        let json = r#"{
+            "program": "gpg",
+            "fds": { "3": { "input": "c2VjcmV0Cg==" }, "4": "capture" },
+            "pty": { "rows": 40, "cols": 120 },
+            "max-stdout": 1024,
+            "on-output-limit": "kill",
+            "transcript": true,
+            "tee-stderr": true,
+            "timeout": 2.5,
+            "kill-tree": true,
+            "grace-period": 1,
+            "on-drop": "detach"
+        }"#;
+
+        let got: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(expected, got);
+
+        let json = serde_json::to_string(&expected).unwrap();
+        let got: Command = serde_json::from_str(&json).unwrap();
+        assert_eq!(expected, got);
+
+        let json = r#"{ "program": "a", "timeout": -1 }"#;
+        let err = serde_json::from_str::<Command>(json).unwrap_err();
+        assert!(err.to_string().starts_with("invalid duration"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_attrs() {
+        let mut expected = Command::new("pass");
+        expected.uid(1000).gid(100).groups([10, 20]);
+        expected.process_group(0).umask(0o077);
+        expected
+            .rlimit(Resource::OpenFiles, Rlimit::new(64))
+            .nice(10);
+
+        let json = r#"{
+            "program": "pass",
+            "rlimits": { "open-files": { "soft": 64, "hard": 64 } },
+            "nice": 10,
+            "uid": 1000,
+            "gid": 100,
+            "groups": [10, 20],
+            "process-group": 0,
+            "umask": 63
+        }"#;
+
+        let got: Command = serde_json::from_str(json).unwrap();
+        assert_eq!(expected, got);
+
+        let json = serde_json::to_string(&expected).unwrap();
+        let got: Command = serde_json::from_str(&json).unwrap();
+        assert_eq!(expected, got);
+    }
+}
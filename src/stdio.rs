@@ -1,10 +1,14 @@
 //! Process standard I/O stream configuration.
 
+use alloc::string::String;
+
 /// Configuration for a child process's standard I/O stream.
 ///
 /// Used in [`Command`] to specify how stdin, stdout, and stderr
 /// should behave when the process is spawned.
 ///
+/// File-backed variants are opened by runtimes at spawn time.
+///
 /// [`Command`]: crate::command::Command
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub enum Stdio {
@@ -15,4 +19,21 @@ pub enum Stdio {
     Null,
     /// Create a new pipe for the stream.
     Piped,
+    /// Read the stream from the file at the given path (`< path`).
+    ///
+    /// Only makes sense for stdin.
+    ReadFile(String),
+    /// Write the stream to the file at the given path, creating it
+    /// or truncating it (`> path`).
+    WriteFile(String),
+    /// Append the stream to the file at the given path, creating it
+    /// if needed (`>> path`).
+    AppendFile(String),
+    /// Redirect the stream to the same destination as stdout
+    /// (`2>&1`).
+    ///
+    /// Only makes sense for stderr. When stdout is captured, both
+    /// streams are captured as a single stdout buffer in their real
+    /// write order.
+    Stdout,
 }
@@ -0,0 +1,182 @@
+//! Process termination configuration.
+
+use core::time::Duration;
+
+/// Termination configuration of a child process.
+///
+/// Applies when runtimes need to terminate a process, for example
+/// when it runs past its [`Command::timeout`] or when its captured
+/// output exceeds its limit with [`LimitAction::Kill`].
+///
+/// By default, only the direct child is killed, right away.
+///
+/// [`Command::timeout`]: crate::command::Command::timeout
+/// [`LimitAction::Kill`]: crate::capture::LimitAction::Kill
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Termination {
+    /// Whether to signal the whole process tree of the child.
+    ///
+    /// The child is placed in its own process group, and signals are
+    /// sent to the whole group, so that grandchildren (for example
+    /// spawned by `sh -c`) do not keep running. Only supported on
+    /// Unix, other platforms only kill the direct child. Runtimes
+    /// reject commands joining an existing process group in this
+    /// mode.
+    pub tree: bool,
+
+    /// Grace period given to the process between the termination
+    /// request (`SIGTERM`) and the kill (`SIGKILL`).
+    ///
+    /// Without grace period, the process is killed right away. Only
+    /// supported on Unix, other platforms always kill right away.
+    pub grace: Option<Duration>,
+
+    /// What runtimes do with the process when its handling gets
+    /// cancelled, for example when the future of an async runtime is
+    /// dropped.
+    pub on_drop: OnDrop,
+}
+
+/// Behavior of runtimes towards a running process whose handling got
+/// cancelled.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OnDrop {
+    /// Kill the process and reap it in the background.
+    ///
+    /// In tree mode, the whole process tree is terminated instead,
+    /// following the grace period if any: the process is asked to
+    /// terminate, then killed once the grace period elapsed.
+    #[default]
+    Kill,
+    /// Let the process run on its own.
+    Detach,
+}
+
+/// Signal sent by runtimes to terminate a process.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Signal {
+    /// Asks the process to terminate (`SIGTERM`).
+    Terminate,
+    /// Kills the process (`SIGKILL`).
+    Kill,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    Running,
+    Terminating { kill_at: Duration },
+    Killed,
+}
+
+/// State machine of the termination sequence of a process.
+///
+/// Used by runtimes to know which [`Signal`] to send and when. Times
+/// are expressed as durations elapsed since the spawn of the process.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Terminator {
+    timeout: Option<Duration>,
+    grace: Option<Duration>,
+    state: State,
+    timed_out: bool,
+}
+
+impl Terminator {
+    /// Creates a new terminator for a process with the given timeout
+    /// and termination configuration.
+    pub fn new(timeout: Option<Duration>, termination: &Termination) -> Self {
+        Self {
+            timeout,
+            grace: termination.grace,
+            state: State::Running,
+            timed_out: false,
+        }
+    }
+
+    /// Starts the termination sequence, returning the signal to send.
+    ///
+    /// Returns `None` if the sequence already started.
+    pub fn terminate(&mut self, elapsed: Duration) -> Option<Signal> {
+        if self.state != State::Running {
+            return None;
+        }
+
+        match self.grace {
+            Some(grace) => {
+                let kill_at = elapsed.saturating_add(grace);
+                self.state = State::Terminating { kill_at };
+                Some(Signal::Terminate)
+            }
+            None => {
+                self.state = State::Killed;
+                Some(Signal::Kill)
+            }
+        }
+    }
+
+    /// Makes the sequence progress, returning the signal to send if
+    /// the process timed out or if its grace period expired.
+    pub fn poll(&mut self, elapsed: Duration) -> Option<Signal> {
+        match self.state {
+            State::Running => match self.timeout {
+                Some(timeout) if elapsed >= timeout => {
+                    self.timed_out = true;
+                    self.terminate(elapsed)
+                }
+                _ => None,
+            },
+            State::Terminating { kill_at } if elapsed >= kill_at => {
+                self.state = State::Killed;
+                Some(Signal::Kill)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the elapsed time at which [`Terminator::poll`] needs
+    /// to be called next, if any.
+    pub fn deadline(&self) -> Option<Duration> {
+        match self.state {
+            State::Running => self.timeout,
+            State::Terminating { kill_at } => Some(kill_at),
+            State::Killed => None,
+        }
+    }
+
+    /// Returns `true` if the process ran past its timeout.
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{Signal, Termination, Terminator};
+
+    #[test]
+    fn sequence() {
+        let s = Duration::from_secs;
+
+        let termination = Termination {
+            tree: true,
+            grace: Some(s(2)),
+            ..Default::default()
+        };
+
+        let mut terminator = Terminator::new(Some(s(5)), &termination);
+        assert_eq!(Some(s(5)), terminator.deadline());
+        assert_eq!(None, terminator.poll(s(4)));
+        assert_eq!(Some(Signal::Terminate), terminator.poll(s(5)));
+        assert!(terminator.timed_out());
+        assert_eq!(Some(s(7)), terminator.deadline());
+        assert_eq!(None, terminator.terminate(s(6)));
+        assert_eq!(Some(Signal::Kill), terminator.poll(s(7)));
+        assert_eq!(None, terminator.deadline());
+
+        let mut terminator = Terminator::new(None, &Termination::default());
+        assert_eq!(None, terminator.deadline());
+        assert_eq!(Some(Signal::Kill), terminator.terminate(s(1)));
+        assert!(!terminator.timed_out());
+    }
+}
@@ -2,13 +2,15 @@
 
 use io_process::{
     command::Command,
-    coroutines::spawn_out::{SpawnOut, SpawnOutResult},
+    coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
     runtimes::std::handle,
 };
 
+const HELPER: &str = env!("CARGO_BIN_EXE_io-process-test-helper");
+
 fn echo() -> Command {
-    let mut command = Command::new("echo");
-    command.arg("-n").arg("$TEST").env("TEST", "expanded");
+    let mut command = Command::new(HELPER);
+    command.arg("stdout").arg("$TEST").env("TEST", "expanded");
     command
 }
 
@@ -19,42 +21,44 @@ pub fn expand() {
     let mut command = echo();
     command.expand = true;
 
-    let mut spawn = SpawnOut::new(command);
+    let mut spawn = ProcessSpawnOut::new(command);
     let mut arg = None;
 
     let (_, stdout, _) = loop {
         match spawn.resume(arg.take()) {
-            SpawnOutResult::Ok {
+            ProcessSpawnOutResult::Ok {
                 status,
                 stdout,
                 stderr,
+                ..
             } => break (status, stdout, stderr),
-            SpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
-            SpawnOutResult::Err { err } => panic!("{err}"),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
         }
     };
 
-    assert_eq!("expanded", String::from_utf8_lossy(&stdout));
+    assert_eq!("expanded", String::from_utf8_lossy(&stdout.unwrap()));
 }
 
 #[test]
 pub fn no_expand() {
     let _ = env_logger::try_init();
 
-    let mut spawn = SpawnOut::new(echo());
+    let mut spawn = ProcessSpawnOut::new(echo());
     let mut arg = None;
 
     let (_, stdout, _) = loop {
         match spawn.resume(arg.take()) {
-            SpawnOutResult::Ok {
+            ProcessSpawnOutResult::Ok {
                 status,
                 stdout,
                 stderr,
+                ..
             } => break (status, stdout, stderr),
-            SpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
-            SpawnOutResult::Err { err } => panic!("{err}"),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
         }
     };
 
-    assert_eq!("$TEST", String::from_utf8_lossy(&stdout));
+    assert_eq!("$TEST", String::from_utf8_lossy(&stdout.unwrap()));
 }
@@ -0,0 +1,187 @@
+#![cfg(all(unix, feature = "poll"))]
+
+use std::time::{Duration, Instant};
+
+use io_process::{
+    command::Command,
+    io::{ProcessInput, ProcessOutput},
+    runtimes::poll::Poller,
+    stdio::Stdio,
+};
+
+const HELPER: &str = env!("CARGO_BIN_EXE_io-process-test-helper");
+
+/// Returns a command running the test helper with the given script.
+fn helper(script: &[&str]) -> Command {
+    let mut command = Command::new(HELPER);
+    command.args(script);
+    command
+}
+
+#[cfg(feature = "testkit")]
+#[test]
+fn conformance() {
+    let _ = env_logger::try_init();
+
+    io_process::runtimes::testkit::run(io_process::runtimes::poll::handle).unwrap();
+}
+
+#[test]
+fn many_in_flight() {
+    let _ = env_logger::try_init();
+
+    let mut poller = Poller::new();
+    let mut ids = Vec::new();
+
+    for i in 0..100 {
+        let text = format!("out {i}");
+        let cmd = helper(&["sleep", "50", "stdout", &text, "stderr", "err"]);
+        ids.push(poller.submit(ProcessInput::SpawnOut { cmd }));
+    }
+
+    assert_eq!(100, poller.len());
+
+    // the requests run concurrently, not one after the other
+    let start = Instant::now();
+    let mut outputs = Vec::new();
+
+    for (id, output) in &mut poller {
+        match output.unwrap() {
+            ProcessOutput::SpawnedOut { status, stdout, .. } => {
+                assert!(status.success());
+                outputs.push((id, stdout.unwrap()));
+            }
+            output => panic!("unexpected output: {output:?}"),
+        }
+    }
+
+    assert!(start.elapsed() < Duration::from_secs(4));
+    assert!(poller.is_empty());
+
+    outputs.sort();
+
+    for ((id, stdout), (i, expected)) in outputs.into_iter().zip(ids.into_iter().enumerate()) {
+        assert_eq!(expected, id);
+        assert_eq!(format!("out {i}").into_bytes(), stdout);
+    }
+}
+
+#[test]
+fn completion_order() {
+    let _ = env_logger::try_init();
+
+    let mut poller = Poller::new();
+
+    let slow = poller.submit(ProcessInput::Spawn {
+        cmd: helper(&["sleep", "300"]),
+    });
+    let mut cmd = helper(&["cat"]);
+    cmd.stdout(Stdio::Null);
+    let input = ProcessInput::SpawnIn {
+        cmd,
+        stdin: vec![b'x'; 1024 * 1024],
+    };
+    let fast = poller.submit(input);
+    let failed = poller.submit(ProcessInput::Spawn {
+        cmd: Command::new("/nonexistent"),
+    });
+
+    // spawn errors complete right away
+    let (id, output) = poller.wait(Some(Duration::ZERO)).unwrap();
+    assert_eq!(failed, id);
+    assert_eq!(std::io::ErrorKind::NotFound, output.unwrap_err().kind());
+
+    let (id, output) = poller.next().unwrap();
+    assert_eq!(fast, id);
+    assert!(matches!(output, Ok(ProcessOutput::SpawnedIn { .. })));
+
+    // the slow request does not complete within the timeout
+    assert!(poller.wait(Some(Duration::from_millis(10))).is_none());
+
+    let (id, output) = poller.next().unwrap();
+    assert_eq!(slow, id);
+    assert!(matches!(output, Ok(ProcessOutput::Spawned { .. })));
+
+    assert!(poller.next().is_none());
+}
+
+#[test]
+fn timeout_per_request() {
+    let _ = env_logger::try_init();
+
+    let mut poller = Poller::new();
+
+    let mut cmd = helper(&["sleep", "10000"]);
+    cmd.timeout(Duration::from_millis(200))
+        .grace_period(Duration::from_millis(100));
+    let slow = poller.submit(ProcessInput::SpawnOut { cmd });
+    let fast = poller.submit(ProcessInput::SpawnOut {
+        cmd: helper(&["sleep", "500", "stdout", "done"]),
+    });
+
+    // the timeout of one request does not affect the others
+    let start = Instant::now();
+
+    let (id, output) = poller.next().unwrap();
+    assert_eq!(slow, id);
+    assert_eq!(std::io::ErrorKind::TimedOut, output.unwrap_err().kind());
+    assert!(start.elapsed() < Duration::from_millis(500));
+
+    let (id, output) = poller.next().unwrap();
+    assert_eq!(fast, id);
+
+    match output.unwrap() {
+        ProcessOutput::SpawnedOut { status, stdout, .. } => {
+            assert!(status.success());
+            assert_eq!(b"done".to_vec(), stdout.unwrap());
+        }
+        output => panic!("unexpected output: {output:?}"),
+    }
+
+    assert!(poller.next().is_none());
+}
+
+#[test]
+fn pipeline_timeout() {
+    let _ = env_logger::try_init();
+
+    // the first stage outlives the last one, and gets terminated at
+    // the deadline of the request
+    for tree in [false, true] {
+        let mut poller = Poller::new();
+
+        let mut last = helper(&[]);
+        last.timeout(Duration::from_millis(200)).kill_tree(tree);
+        let cmds = vec![helper(&["sleep", "10000"]), last];
+
+        let start = Instant::now();
+        poller.submit(ProcessInput::SpawnPipeline { cmds });
+
+        let (_, output) = poller.next().unwrap();
+        assert_eq!(std::io::ErrorKind::TimedOut, output.unwrap_err().kind());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn drop_in_flight() {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("pid");
+
+    let mut poller = Poller::new();
+    let cmd = helper(&["pid", &path.to_string_lossy(), "sleep", "10000"]);
+    poller.submit(ProcessInput::Spawn { cmd });
+
+    while std::fs::read_to_string(&path).map_or(true, |pid| !pid.ends_with('\n')) {
+        assert!(poller.wait(Some(Duration::from_millis(10))).is_none());
+    }
+
+    let pid = std::fs::read_to_string(&path).unwrap();
+
+    // the process gets killed and reaped before the poller is gone
+    drop(poller);
+    assert!(!std::path::Path::new(&format!("/proc/{}", pid.trim())).exists());
+}
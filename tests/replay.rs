@@ -0,0 +1,101 @@
+#![cfg(feature = "replay")]
+
+use io_process::{
+    command::Command,
+    coroutines::{
+        spawn_in::{SpawnIn, SpawnInResult},
+        spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
+    },
+    runtimes::replay::{Cassette, CassetteError, Mode},
+};
+
+const HELPER: &str = env!("CARGO_BIN_EXE_io-process-test-helper");
+
+/// Returns a command running the test helper with the given script.
+fn helper(script: &[&str]) -> Command {
+    let mut command = Command::new(HELPER);
+    command.args(script);
+    command
+}
+
+fn spawn_out(cassette: &mut Cassette, command: Command) -> std::io::Result<Vec<u8>> {
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, .. } => break Ok(stdout.unwrap()),
+            ProcessSpawnOutResult::Io { input } => arg = Some(cassette.handle(input)?),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    }
+}
+
+#[test]
+fn record_replay() {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cassette");
+
+    let mut echo = helper(&["stdout", "hello"]);
+    echo.env("LANG", "C");
+
+    let mut cat = helper(&["cat"]);
+    cat.stdout(io_process::stdio::Stdio::Null);
+
+    // records real processes
+    let mut cassette = Cassette::open(&path, Mode::Auto).unwrap();
+    assert_eq!(Mode::Record, cassette.mode());
+
+    let stdout = spawn_out(&mut cassette, echo.clone()).unwrap();
+    assert_eq!(b"hello", stdout.as_slice());
+
+    let mut arg = None;
+    let mut spawn = SpawnIn::new(cat.clone(), b"in\n".to_vec());
+
+    loop {
+        match spawn.resume(arg.take()) {
+            SpawnInResult::Ok { status } => break assert!(status.success()),
+            SpawnInResult::Io { input } => arg = Some(cassette.handle(input).unwrap()),
+            SpawnInResult::Err { err } => panic!("{err}"),
+        }
+    }
+
+    let recorded = std::fs::read_to_string(&path).unwrap();
+    let command = format!("  command: {HELPER} stdout hello\n    env: LANG=C\n");
+    assert!(recorded.contains(&command));
+    assert!(recorded.contains("  stdout: \"hello\"\n"));
+    assert!(recorded.contains("  stdin: \"in\\n\"\n"));
+
+    // replays the recorded outputs, which can be edited by hand
+    std::fs::write(&path, recorded.replace("\"hello\"", "\"replayed\"")).unwrap();
+
+    let mut cassette = Cassette::open(&path, Mode::Auto).unwrap();
+    assert_eq!(Mode::Replay, cassette.mode());
+
+    let stdout = spawn_out(&mut cassette, echo).unwrap();
+    assert_eq!(b"replayed", stdout.as_slice());
+
+    // fails on mismatched commands
+    let other = helper(&["stdout", "bye"]);
+
+    let err = spawn_out(&mut cassette, other).unwrap_err();
+    let err = err
+        .into_inner()
+        .unwrap()
+        .downcast::<CassetteError>()
+        .unwrap();
+    let CassetteError::Mismatch { diff } = *err else {
+        panic!("unexpected error: {err}");
+    };
+
+    let expected = [
+        "- SpawnIn".to_string(),
+        "+ SpawnOut".to_string(),
+        format!("-   command: {HELPER} cat"),
+        format!("+   command: {HELPER} stdout bye"),
+        "-   stdin: \"in\\n\"".to_string(),
+    ];
+    assert_eq!(expected.join("\n"), diff);
+}
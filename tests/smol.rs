@@ -0,0 +1,375 @@
+#![cfg(feature = "smol")]
+
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
+
+use io_process::{
+    command::Command,
+    coroutines::{
+        spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
+        spawn_pipeline::{SpawnPipeline, SpawnPipelineResult},
+    },
+    runtimes::smol::handle,
+};
+#[cfg(unix)]
+use io_process::{
+    coroutines::spawn::{ProcessSpawn, ProcessSpawnResult},
+    fd::ExtraFd,
+};
+#[cfg(target_os = "linux")]
+use io_process::{
+    io::{ProcessInput, ProcessOutput},
+    pty::Pty,
+    termination::OnDrop,
+};
+
+const HELPER: &str = env!("CARGO_BIN_EXE_io-process-test-helper");
+
+/// Returns a command running the test helper with the given script.
+fn helper(script: &[&str]) -> Command {
+    let mut command = Command::new(HELPER);
+    command.args(script);
+    command
+}
+
+#[test]
+fn spawn_out_tee() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        let mut command = helper(&["stdout", "out\n", "stderr", "tee\n"]);
+        command.tee_stderr(true);
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawnOut::new(command);
+
+        let (stdout, stderr) = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnOutResult::Ok { stdout, stderr, .. } => break (stdout, stderr),
+                ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).await.unwrap()),
+                ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(Some(b"out\n".to_vec()), stdout);
+        assert_eq!(Some(b"tee\n".to_vec()), stderr);
+    })
+}
+
+#[test]
+fn spawn_pipeline() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        let echo = helper(&["stdout", "hello world\n"]);
+        let cat = helper(&["cat"]);
+
+        let mut arg = None;
+        let mut spawn = SpawnPipeline::new([echo, cat]);
+
+        let (status, stdout, _stderr) = loop {
+            match spawn.resume(arg.take()) {
+                SpawnPipelineResult::Ok {
+                    status,
+                    stdout,
+                    stderr,
+                    ..
+                } => break (status, stdout, stderr),
+                SpawnPipelineResult::Io { input } => arg = Some(handle(input).await.unwrap()),
+                SpawnPipelineResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert!(status.success());
+        assert_eq!("hello world\n", String::from_utf8_lossy(&stdout.unwrap()));
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_in_timeout() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        // the helper never reads its stdin, which fills up the pipe
+        let mut cmd = helper(&["sleep", "10000"]);
+        cmd.timeout(Duration::from_millis(200));
+        let stdin = vec![b'x'; 1 << 20];
+
+        let start = Instant::now();
+        let err = handle(ProcessInput::SpawnIn { cmd, stdin })
+            .await
+            .unwrap_err();
+        assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_pipeline_timeout() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        // the first stage outlives the last one, and gets terminated at
+        // the deadline of the pipeline, whatever the tree mode of the
+        // last one
+        for tree in [false, true] {
+            let mut last = helper(&[]);
+            last.timeout(Duration::from_millis(200)).kill_tree(tree);
+            let cmds = vec![helper(&["sleep", "10000"]), last];
+
+            let start = Instant::now();
+            let err = handle(ProcessInput::SpawnPipeline { cmds })
+                .await
+                .unwrap_err();
+            assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn spawn_out_fds() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("status.log");
+
+        let script = [
+            "read-fd",
+            "3",
+            "write-fd",
+            "4",
+            "captured\n",
+            "write-fd",
+            "5",
+            "done\n",
+        ];
+        let mut command = helper(&script);
+        command
+            .fd(3, ExtraFd::Input(b"secret\n".to_vec()))
+            .fd(4, ExtraFd::Capture)
+            .fd(5, ExtraFd::WriteFile(path.to_string_lossy().to_string()));
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawnOut::new(command);
+
+        let (stdout, fds) = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnOutResult::Ok { stdout, fds, .. } => break (stdout, fds),
+                ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).await.unwrap()),
+                ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(Some(b"secret\n".to_vec()), stdout);
+        assert_eq!(Some(&b"captured\n".to_vec()), fds.get(&4));
+        assert_eq!("done\n", std::fs::read_to_string(path).unwrap());
+
+        let mut command = helper(&[]);
+        command.fd(3, ExtraFd::Capture);
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawn::new(command);
+
+        let err = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnResult::Ok { status } => panic!("unexpected status: {status:?}"),
+                ProcessSpawnResult::Io { input } => match handle(input).await {
+                    Ok(output) => arg = Some(output),
+                    Err(err) => break err,
+                },
+                ProcessSpawnResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_out_pty() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("test -t 0 && test -t 1 && echo tty; stty size >&2")
+            .pty(Pty::new(30, 100));
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawnOut::new(command);
+
+        let (stdout, stderr) = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnOutResult::Ok { stdout, stderr, .. } => break (stdout, stderr),
+                ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).await.unwrap()),
+                ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(Some(b"tty\r\n30 100\r\n".to_vec()), stdout);
+        assert_eq!(None, stderr);
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_cancel() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first");
+        let last = dir.path().join("last");
+
+        let stage = |path: &std::path::Path| {
+            let path = path.to_string_lossy();
+            helper(&["pid", &path, "sleep", "10000"])
+        };
+
+        // cancels a single process mid-run
+        let input = ProcessInput::SpawnOut { cmd: stage(&last) };
+        cancel(handle(input), wait_for_pid(&last)).await;
+
+        wait_for_reap(read_pid(&last)).await;
+
+        // cancels a whole pipeline mid-run
+        let cmds = vec![stage(&first), stage(&last)];
+        std::fs::remove_file(&last).unwrap();
+        let input = ProcessInput::SpawnPipeline { cmds };
+        cancel(handle(input), async {
+            wait_for_pid(&first).await;
+            wait_for_pid(&last).await
+        })
+        .await;
+
+        wait_for_reap(read_pid(&first)).await;
+        wait_for_reap(read_pid(&last)).await;
+
+        // cancels a whole pipeline in tree mode, whose processes
+        // ignore the termination request and get killed after the
+        // grace period
+        let grace = Duration::from_millis(500);
+        let stage = |path: &std::path::Path| {
+            let path = path.to_string_lossy();
+            let mut cmd = helper(&["ignore", "15", "pid", &path, "sleep", "10000"]);
+            cmd.kill_tree(true);
+            cmd
+        };
+        let mut cmds = vec![stage(&first), stage(&last)];
+        cmds[1].grace_period(grace);
+        std::fs::remove_file(&first).unwrap();
+        std::fs::remove_file(&last).unwrap();
+        let input = ProcessInput::SpawnPipeline { cmds };
+        cancel(handle(input), async {
+            wait_for_pid(&first).await;
+            wait_for_pid(&last).await
+        })
+        .await;
+
+        let cancelled = Instant::now();
+        smol::Timer::after(grace / 2).await;
+
+        assert!(alive(read_pid(&first)));
+        assert!(alive(read_pid(&last)));
+
+        wait_for_reap(read_pid(&first)).await;
+        wait_for_reap(read_pid(&last)).await;
+        assert!(cancelled.elapsed() >= grace);
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_cancel_detach() {
+    smol::block_on(async {
+        let _ = env_logger::try_init();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pid");
+
+        let mut command = helper(&["pid", &path.to_string_lossy(), "sleep", "10000"]);
+        command.on_drop(OnDrop::Detach);
+
+        let input = ProcessInput::Spawn { cmd: command };
+        cancel(handle(input), wait_for_pid(&path)).await;
+
+        let pid = read_pid(&path);
+        smol::Timer::after(Duration::from_millis(100)).await;
+        assert!(std::path::Path::new(&format!("/proc/{pid}")).exists());
+
+        std::process::Command::new("kill")
+            .arg(pid.to_string())
+            .status()
+            .unwrap();
+    })
+}
+
+/// Drops the given request once the given future completed.
+#[cfg(target_os = "linux")]
+async fn cancel(
+    request: impl Future<Output = std::io::Result<ProcessOutput>>,
+    until: impl Future<Output = ()>,
+) {
+    let request = async { panic!("unexpected output: {:?}", request.await) };
+    smol::future::or(request, until).await
+}
+
+/// Waits until the process writing its pid at the given path did it.
+#[cfg(target_os = "linux")]
+async fn wait_for_pid(path: &std::path::Path) {
+    while std::fs::read_to_string(path).map_or(true, |pid| !pid.ends_with('\n')) {
+        smol::Timer::after(Duration::from_millis(10)).await;
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_pid(path: &std::path::Path) -> u32 {
+    std::fs::read_to_string(path)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap()
+}
+
+/// Returns `true` if the process with the given pid is running, not
+/// exited waiting to be reaped.
+#[cfg(target_os = "linux")]
+fn alive(pid: u32) -> bool {
+    let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) else {
+        return false;
+    };
+
+    // the state follows the command name, which may contain spaces
+    let state = stat.rsplit(')').next().unwrap_or_default();
+    !state.starts_with(" Z")
+}
+
+/// Waits until the process with the given pid got killed and reaped.
+#[cfg(target_os = "linux")]
+async fn wait_for_reap(pid: u32) {
+    let start = Instant::now();
+    let proc = format!("/proc/{pid}");
+
+    while std::path::Path::new(&proc).exists() {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "{pid} still exists"
+        );
+        smol::Timer::after(Duration::from_millis(10)).await;
+    }
+}
+
+#[cfg(feature = "testkit")]
+#[test]
+fn conformance() {
+    let _ = env_logger::try_init();
+
+    io_process::runtimes::testkit::run(|input| smol::block_on(handle(input))).unwrap();
+}
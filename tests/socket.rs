@@ -0,0 +1,68 @@
+#![cfg(all(unix, feature = "socket"))]
+
+use std::{io::ErrorKind, os::unix::net::UnixListener, thread};
+
+use io_process::{
+    command::Command,
+    coroutines::spawn_out::{ProcessSpawnOut, ProcessSpawnOutResult},
+    io::ProcessInput,
+    runtimes::socket::{Client, serve},
+};
+
+#[test]
+fn client_server() {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("socket");
+
+    let listener = UnixListener::bind(&path).unwrap();
+    thread::spawn(move || serve(&listener));
+
+    let mut client = Client::connect(&path).unwrap();
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("printf 'hello\\377'; exit 3");
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (status, stdout) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { status, stdout, .. } => break (status, stdout),
+            ProcessSpawnOutResult::Io { input } => arg = Some(client.handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(3), status.code());
+    assert_eq!(Some(b"hello\xff".to_vec()), stdout);
+
+    // errors are propagated with their kind, and keep the
+    // connection usable
+    let input = ProcessInput::Spawn {
+        cmd: Command::new("/nonexistent/program"),
+    };
+    let err = client.handle(input).unwrap_err();
+    assert_eq!(ErrorKind::NotFound, err.kind());
+
+    let input = ProcessInput::Spawn {
+        cmd: Command::new(env!("CARGO_BIN_EXE_io-process-test-helper")),
+    };
+    assert!(client.handle(input).is_ok());
+}
+
+#[cfg(feature = "testkit")]
+#[test]
+fn conformance() {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("socket");
+
+    let listener = UnixListener::bind(&path).unwrap();
+    thread::spawn(move || serve(&listener));
+
+    let mut client = Client::connect(&path).unwrap();
+    io_process::runtimes::testkit::run(|input| client.handle(input)).unwrap();
+}
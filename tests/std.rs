@@ -1,65 +1,235 @@
 #![cfg(feature = "std")]
 
+use std::time::{Duration, Instant};
+
 use io_process::{
+    capture::{LimitAction, Stream},
+    check::ExitCheck,
     command::Command,
     coroutines::{
         spawn::{ProcessSpawn, ProcessSpawnResult},
         spawn_in::{SpawnIn, SpawnInResult},
-        spawn_out::{SpawnOut, SpawnOutResult},
+        spawn_out::{ProcessSpawnOut, ProcessSpawnOutError, ProcessSpawnOutResult},
         spawn_pipeline::{SpawnPipeline, SpawnPipelineResult},
     },
+    fd::ExtraFd,
+    io::ProcessInput,
+    limit::{LimitError, Resource, Rlimit},
+    pty::Pty,
     runtimes::std::handle,
+    stdio::Stdio,
 };
 
+const HELPER: &str = env!("CARGO_BIN_EXE_io-process-test-helper");
+
+/// Returns a command running the test helper with the given script.
+fn helper(script: &[&str]) -> Command {
+    let mut command = Command::new(HELPER);
+    command.args(script);
+    command
+}
+
 #[test]
 fn spawn() {
     let _ = env_logger::try_init();
 
     let mut arg = None;
-    let mut spawn = Spawn::new(Command::new("true"));
+    let mut spawn = ProcessSpawn::new(helper(&[]));
 
     let status = loop {
         match spawn.resume(arg.take()) {
-            SpawnResult::Ok { status } => break status,
-            SpawnResult::Io { input } => arg = Some(handle(input).unwrap()),
-            SpawnResult::Err { err } => panic!("{err}"),
+            ProcessSpawnResult::Ok { status } => break status,
+            ProcessSpawnResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnResult::Err { err } => panic!("{err}"),
         }
     };
 
     assert!(status.success());
 }
 
+#[test]
+fn spawn_status() {
+    let _ = env_logger::try_init();
+
+    let mut cases = vec![(helper(&["exit", "42"]), Some(42))];
+
+    #[cfg(unix)]
+    cases.push((helper(&["signal", "9"]), None));
+
+    for (command, code) in cases {
+        let mut arg = None;
+        let mut spawn = ProcessSpawn::new(command);
+
+        let status = loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnResult::Ok { status } => break status,
+                ProcessSpawnResult::Io { input } => arg = Some(handle(input).unwrap()),
+                ProcessSpawnResult::Err { err } => panic!("{err}"),
+            }
+        };
+
+        assert_eq!(code, status.code());
+    }
+}
+
 #[test]
 fn spawn_out() {
     let _ = env_logger::try_init();
 
-    let mut command = Command::new("echo");
-    command.arg("hello");
+    let command = helper(&["stdout", "hello\n"]);
 
     let mut arg = None;
-    let mut spawn = SpawnOut::new(command);
+    let mut spawn = ProcessSpawnOut::new(command);
 
     let (status, stdout, _stderr) = loop {
         match spawn.resume(arg.take()) {
-            SpawnOutResult::Ok {
+            ProcessSpawnOutResult::Ok {
                 status,
                 stdout,
                 stderr,
+                ..
             } => break (status, stdout, stderr),
-            SpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
-            SpawnOutResult::Err { err } => panic!("{err}"),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
         }
     };
 
     assert!(status.success());
-    assert_eq!("hello\n", String::from_utf8_lossy(&stdout));
+    assert_eq!("hello\n", String::from_utf8_lossy(&stdout.unwrap()));
+}
+
+#[test]
+fn spawn_out_checked() {
+    let _ = env_logger::try_init();
+
+    let command = helper(&["stderr", "oops", "exit", "3"]);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+    spawn.checked(ExitCheck::new());
+
+    let err = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { status, .. } => panic!("unexpected success: {status:?}"),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => break err,
+        }
+    };
+
+    let ProcessSpawnOutError::Exit(err) = err else {
+        panic!("unexpected error: {err}");
+    };
+
+    assert_eq!(Some(3), err.status.code());
+    assert_eq!(format!("{HELPER} stderr oops exit 3"), err.command);
+    assert_eq!("oops", err.stderr);
+
+    let command = helper(&["exit", "1"]);
+
+    let mut check = ExitCheck::new();
+    check.success_codes([0, 1]);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+    spawn.checked(check);
+
+    let status = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { status, .. } => break status,
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(1), status.code());
+}
+
+#[test]
+fn spawn_out_limits() {
+    let _ = env_logger::try_init();
+
+    let mut command = helper(&["fill-stdout", "100000", "stderr", "oops\n"]);
+    command.max_stdout(10);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (status, stdout, stderr, truncated) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok {
+                status,
+                stdout,
+                stderr,
+                truncated,
+                ..
+            } => break (status, stdout, stderr, truncated),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert!(status.success());
+    assert_eq!(Some(vec![b'x'; 10]), stdout);
+    assert_eq!("oops\n", String::from_utf8_lossy(&stderr.unwrap()));
+    assert!(truncated.stdout);
+    assert!(!truncated.stderr);
+
+    let mut command = helper(&["flood"]);
+    command.max_stdout(1000);
+    command.on_output_limit(LimitAction::Kill);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (status, stdout, truncated) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok {
+                status,
+                stdout,
+                truncated,
+                ..
+            } => break (status, stdout, truncated),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(None, status.code());
+    assert_eq!(1000, stdout.unwrap().len());
+    assert!(truncated.stdout);
+}
+
+#[test]
+fn spawn_out_stdio() {
+    let _ = env_logger::try_init();
+
+    let mut command = helper(&["stdout", "out\n", "stderr", "err\n"]);
+    command.stderr(Stdio::Null);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (stdout, stderr) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, stderr, .. } => break (stdout, stderr),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(b"out\n".to_vec()), stdout);
+    assert_eq!(None, stderr);
 }
 
 #[test]
 fn spawn_in() {
     let _ = env_logger::try_init();
 
-    let command = Command::new("cat");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("stdin.log");
+
+    let mut command = helper(&["cat"]);
+    command.stdout(Stdio::WriteFile(path.to_string_lossy().to_string()));
     let stdin = b"hello\n".to_vec();
 
     let mut arg = None;
@@ -74,20 +244,127 @@ fn spawn_in() {
     };
 
     assert!(status.success());
+    assert_eq!("hello\n", std::fs::read_to_string(path).unwrap());
+
+    let mut command = helper(&["cat"]);
+    command.stdin(Stdio::Null);
+
+    let mut arg = None;
+    let mut spawn = SpawnIn::new(command, b"hello\n".to_vec());
+
+    let err = loop {
+        match spawn.resume(arg.take()) {
+            SpawnInResult::Ok { status } => panic!("unexpected status: {status:?}"),
+            SpawnInResult::Io { input } => match handle(input) {
+                Ok(output) => arg = Some(output),
+                Err(err) => break err,
+            },
+            SpawnInResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
 }
 
 #[test]
-fn spawn_pipeline() {
+fn spawn_out_tee() {
+    let _ = env_logger::try_init();
+
+    let mut command = helper(&["stdout", "out\n", "stderr", "tee\n"]);
+    command.tee_stderr(true);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (stdout, stderr) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, stderr, .. } => break (stdout, stderr),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(b"out\n".to_vec()), stdout);
+    assert_eq!(Some(b"tee\n".to_vec()), stderr);
+}
+
+#[test]
+fn spawn_stdio_files() {
     let _ = env_logger::try_init();
 
-    let mut echo = Command::new("echo");
-    echo.arg("hello world");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.log");
+    let path = path.to_string_lossy();
+
+    for (stdio, line) in [
+        (Stdio::WriteFile(path.to_string()), "first\n"),
+        (Stdio::AppendFile(path.to_string()), "second\n"),
+    ] {
+        let mut command = helper(&["stdout", line]);
+        command.stdout(stdio);
+
+        let mut arg = None;
+        let mut spawn = ProcessSpawn::new(command);
+
+        loop {
+            match spawn.resume(arg.take()) {
+                ProcessSpawnResult::Ok { status } => break assert!(status.success()),
+                ProcessSpawnResult::Io { input } => arg = Some(handle(input).unwrap()),
+                ProcessSpawnResult::Err { err } => panic!("{err}"),
+            }
+        }
+    }
+
+    let mut command = helper(&["cat"]);
+    command.stdin(Stdio::ReadFile(path.to_string()));
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let stdout = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, .. } => break stdout,
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!("first\nsecond\n", String::from_utf8_lossy(&stdout.unwrap()));
+
+    let missing = dir.path().join("missing.eml");
+    let mut command = helper(&["cat"]);
+    command.stdin(Stdio::ReadFile(missing.to_string_lossy().to_string()));
+
+    let mut spawn = ProcessSpawnOut::new(command);
+    let ProcessSpawnOutResult::Io { input } = spawn.resume(None) else {
+        panic!("should emit process I/O");
+    };
+
+    let err = handle(input).unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+    assert!(err.to_string().starts_with("cannot open stdin file"));
+
+    // the conversions report the error with the path of the file
+    let mut command = helper(&["cat"]);
+    command.stdin(Stdio::ReadFile(missing.to_string_lossy().to_string()));
+
+    let err = command.clone().into_std_command().unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+
+    let err = std::process::Command::try_from(command).unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+    assert!(err.to_string().contains(&*missing.to_string_lossy()));
+}
+
+#[test]
+fn spawn_pipeline() {
+    let _ = env_logger::try_init();
 
-    let mut grep = Command::new("grep");
-    grep.arg("world");
+    let echo = helper(&["stdout", "hello world\n"]);
+    let cat = helper(&["cat"]);
 
     let mut arg = None;
-    let mut spawn = SpawnPipeline::new([echo, grep]);
+    let mut spawn = SpawnPipeline::new([echo, cat]);
 
     let (status, stdout, _stderr) = loop {
         match spawn.resume(arg.take()) {
@@ -95,6 +372,7 @@ fn spawn_pipeline() {
                 status,
                 stdout,
                 stderr,
+                ..
             } => break (status, stdout, stderr),
             SpawnPipelineResult::Io { input } => arg = Some(handle(input).unwrap()),
             SpawnPipelineResult::Err { err } => panic!("{err}"),
@@ -102,5 +380,424 @@ fn spawn_pipeline() {
     };
 
     assert!(status.success());
-    assert_eq!("hello world\n", String::from_utf8_lossy(&stdout));
+    assert_eq!("hello world\n", String::from_utf8_lossy(&stdout.unwrap()));
+}
+
+#[test]
+fn spawn_out_merged() {
+    let _ = env_logger::try_init();
+
+    let script = [
+        "stdout", "a\n", "stderr", "b\n", "stdout", "c\n", "stderr", "d\n",
+    ];
+    let mut command = helper(&script);
+    command.stderr(Stdio::Stdout);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (stdout, stderr) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, stderr, .. } => break (stdout, stderr),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(b"a\nb\nc\nd\n".to_vec()), stdout);
+    assert_eq!(None, stderr);
+}
+
+#[test]
+fn spawn_pipeline_merged() {
+    let _ = env_logger::try_init();
+
+    let mut first = helper(&["stdout", "out\n", "stderr", "err\n"]);
+    first.stderr(Stdio::Stdout);
+
+    let mut arg = None;
+    let mut spawn = SpawnPipeline::new([first, helper(&["cat"])]);
+
+    let stdout = loop {
+        match spawn.resume(arg.take()) {
+            SpawnPipelineResult::Ok { stdout, .. } => break stdout,
+            SpawnPipelineResult::Io { input } => arg = Some(handle(input).unwrap()),
+            SpawnPipelineResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(b"out\nerr\n".to_vec()), stdout);
+}
+
+#[test]
+fn spawn_out_transcript() {
+    let _ = env_logger::try_init();
+
+    let script = [
+        "stdout", "a", "sleep", "200", "stderr", "b", "sleep", "200", "stdout", "c",
+    ];
+    let mut command = helper(&script);
+    command.transcript(true);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (stdout, stderr, transcript) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok {
+                stdout,
+                stderr,
+                transcript,
+                ..
+            } => break (stdout, stderr, transcript),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(b"ac".to_vec()), stdout);
+    assert_eq!(Some(b"b".to_vec()), stderr);
+
+    let transcript = transcript.unwrap();
+    let events: Vec<_> = transcript
+        .iter()
+        .map(|event| (event.stream, event.bytes.as_slice()))
+        .collect();
+
+    assert_eq!(
+        vec![
+            (Stream::Stdout, b"a".as_slice()),
+            (Stream::Stderr, b"b".as_slice()),
+            (Stream::Stdout, b"c".as_slice()),
+        ],
+        events,
+    );
+
+    assert!(transcript[0].elapsed < transcript[1].elapsed);
+    assert!(transcript[1].elapsed < transcript[2].elapsed);
+}
+
+#[cfg(unix)]
+#[test]
+fn spawn_out_fds() {
+    let _ = env_logger::try_init();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("status.log");
+
+    let script = [
+        "read-fd",
+        "3",
+        "write-fd",
+        "4",
+        "captured\n",
+        "write-fd",
+        "5",
+        "done\n",
+    ];
+    let mut command = helper(&script);
+    command
+        .fd(3, ExtraFd::Input(b"secret\n".to_vec()))
+        .fd(4, ExtraFd::Capture)
+        .fd(5, ExtraFd::WriteFile(path.to_string_lossy().to_string()));
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (stdout, fds) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, fds, .. } => break (stdout, fds),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(b"secret\n".to_vec()), stdout);
+    assert_eq!(Some(&b"captured\n".to_vec()), fds.get(&4));
+    assert_eq!("done\n", std::fs::read_to_string(path).unwrap());
+
+    let mut command = helper(&[]);
+    command.fd(3, ExtraFd::Capture);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawn::new(command);
+
+    let err = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnResult::Ok { status } => panic!("unexpected status: {status:?}"),
+            ProcessSpawnResult::Io { input } => match handle(input) {
+                Ok(output) => arg = Some(output),
+                Err(err) => break err,
+            },
+            ProcessSpawnResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_out_pty() {
+    let _ = env_logger::try_init();
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("test -t 0 && test -t 1 && echo tty; stty size >&2")
+        .pty(Pty::new(30, 100));
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let (stdout, stderr) = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, stderr, .. } => break (stdout, stderr),
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(Some(b"tty\r\n30 100\r\n".to_vec()), stdout);
+    assert_eq!(None, stderr);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_out_unix_attrs() {
+    let _ = env_logger::try_init();
+
+    let script = "read -r pid comm state ppid pgrp session rest < /proc/$$/stat; \
+        test $pgrp -eq $pid && echo pgrp; \
+        test $session -eq $pid && echo session; \
+        umask";
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script).setsid(true).umask(0o077);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let stdout = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, .. } => break stdout,
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(
+        "pgrp\nsession\n0077\n",
+        String::from_utf8_lossy(&stdout.unwrap())
+    );
+
+    let mut command = helper(&[]);
+    command.setsid(true).process_group(0);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawn::new(command);
+
+    let err = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnResult::Ok { status } => panic!("unexpected status: {status:?}"),
+            ProcessSpawnResult::Io { input } => match handle(input) {
+                Ok(output) => arg = Some(output),
+                Err(err) => break err,
+            },
+            ProcessSpawnResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn spawn_out_rlimits() {
+    let _ = env_logger::try_init();
+
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("ulimit -n; ulimit -t; nice")
+        .rlimit(Resource::OpenFiles, Rlimit::new(64))
+        .rlimit(Resource::Cpu, Rlimit::new(10))
+        .nice(5);
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let stdout = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { stdout, .. } => break stdout,
+            ProcessSpawnOutResult::Io { input } => arg = Some(handle(input).unwrap()),
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!("64\n10\n5\n", String::from_utf8_lossy(&stdout.unwrap()));
+
+    // a soft limit above the hard limit is invalid
+    let mut command = helper(&[]);
+    command.rlimit(Resource::FileSize, Rlimit { soft: 10, hard: 5 });
+
+    let mut arg = None;
+    let mut spawn = ProcessSpawn::new(command);
+
+    let err = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnResult::Ok { status } => panic!("unexpected status: {status:?}"),
+            ProcessSpawnResult::Io { input } => match handle(input) {
+                Ok(output) => arg = Some(output),
+                Err(err) => break err,
+            },
+            ProcessSpawnResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    let err = err.into_inner().unwrap().downcast::<LimitError>().unwrap();
+    let LimitError::Rlimit { resource, .. } = *err else {
+        panic!("unexpected error: {err}");
+    };
+
+    assert_eq!(Resource::FileSize, resource);
+}
+
+#[cfg(unix)]
+#[test]
+fn spawn_out_timeout() {
+    let _ = env_logger::try_init();
+
+    // the background helper keeps stdout open, so the output can only
+    // be collected once the whole process tree got terminated
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg("\"$0\" sleep 10000 & \"$0\" sleep 10000")
+        .arg(HELPER)
+        .timeout(Duration::from_millis(200))
+        .kill_tree(true)
+        .grace_period(Duration::from_millis(100));
+
+    let start = Instant::now();
+    let mut arg = None;
+    let mut spawn = ProcessSpawnOut::new(command);
+
+    let err = loop {
+        match spawn.resume(arg.take()) {
+            ProcessSpawnOutResult::Ok { status, .. } => panic!("unexpected status: {status:?}"),
+            ProcessSpawnOutResult::Io { input } => match handle(input) {
+                Ok(output) => arg = Some(output),
+                Err(err) => break err,
+            },
+            ProcessSpawnOutResult::Err { err } => panic!("{err}"),
+        }
+    };
+
+    assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn spawn_in_timeout() {
+    let _ = env_logger::try_init();
+
+    // the helper never reads its stdin, which fills up the pipe
+    let mut cmd = helper(&["sleep", "10000"]);
+    cmd.timeout(Duration::from_millis(200));
+    let stdin = vec![b'x'; 1 << 20];
+
+    let start = Instant::now();
+    let err = handle(ProcessInput::SpawnIn { cmd, stdin }).unwrap_err();
+    assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn spawn_pipeline_timeout() {
+    let _ = env_logger::try_init();
+
+    // the first stage outlives the last one, and gets terminated at
+    // the deadline of the pipeline, whatever the tree mode of the
+    // last one
+    for tree in [false, true] {
+        let mut last = helper(&[]);
+        last.timeout(Duration::from_millis(200)).kill_tree(tree);
+        let cmds = vec![helper(&["sleep", "10000"]), last];
+
+        let start = Instant::now();
+        let err = handle(ProcessInput::SpawnPipeline { cmds }).unwrap_err();
+        assert_eq!(std::io::ErrorKind::TimedOut, err.kind());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn spawn_out_timeout_process_group() {
+    let _ = env_logger::try_init();
+
+    // the tree can only be terminated in a new process group, the one
+    // of the test process cannot be joined
+    for (pgroup, kind) in [
+        (0, std::io::ErrorKind::TimedOut),
+        (std::process::id() as i32, std::io::ErrorKind::InvalidInput),
+    ] {
+        let mut command = helper(&["sleep", "10000"]);
+        command
+            .timeout(Duration::from_millis(200))
+            .kill_tree(true)
+            .process_group(pgroup);
+
+        let start = Instant::now();
+        let mut spawn = ProcessSpawnOut::new(command);
+        let ProcessSpawnOutResult::Io { input } = spawn.resume(None) else {
+            panic!("should emit process I/O");
+        };
+
+        let err = handle(input).unwrap_err();
+        assert_eq!(kind, err.kind());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn pidfd() {
+    use io_process::{runtimes::pidfd::Pidfd, termination::Signal};
+
+    let _ = env_logger::try_init();
+
+    let command = helper(&["sleep", "10000"]);
+
+    let mut child = command.into_std_command().unwrap().spawn().unwrap();
+
+    let Some(pidfd) = Pidfd::from_child(&child).unwrap() else {
+        // pidfds are not supported by this kernel
+        child.kill().unwrap();
+        child.wait().unwrap();
+        return;
+    };
+
+    assert!(!pidfd.has_exited().unwrap());
+    assert!(!pidfd.wait_timeout(Some(Duration::from_millis(10))).unwrap());
+
+    pidfd.send_signal(Signal::Kill).unwrap();
+    assert!(pidfd.wait_timeout(Some(Duration::from_secs(5))).unwrap());
+    assert!(pidfd.has_exited().unwrap());
+
+    // the process is not reaped until waited for
+    let status = child.wait().unwrap();
+    assert_eq!(None, status.code());
+
+    // a reaped process still counts as exited
+    assert!(pidfd.has_exited().unwrap());
+}
+
+#[cfg(feature = "testkit")]
+#[test]
+fn conformance() {
+    let _ = env_logger::try_init();
+
+    io_process::runtimes::testkit::run(handle).unwrap();
 }